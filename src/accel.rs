@@ -0,0 +1,55 @@
+//! [`Accelerometer`] trait implementation from the `accelerometer` crate
+
+use crate::interface::Interface;
+use crate::{Bmi160, Error};
+use accelerometer::vector::{F32x3, I16x3};
+use accelerometer::{Accelerometer, ErrorKind, RawAccelerometer};
+use core::fmt::Debug;
+
+/// Wrap a driver error, keeping it as the cause. The `From` conversion of
+/// the `accelerometer` crate would report every error as a bus error.
+fn accel_error<E: Debug>(error: Error<E>) -> accelerometer::Error<Error<E>> {
+    let kind = match error {
+        Error::Bus(_) | Error::Pin => ErrorKind::Bus,
+        Error::InvalidConfig(_) => ErrorKind::Param,
+        Error::ReservedValue(..) | Error::Timeout | Error::Moving | Error::NotFound => {
+            ErrorKind::Device
+        }
+    };
+
+    accelerometer::Error::new_with_cause(kind, error)
+}
+
+impl<DI, E> Accelerometer for Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+    E: Debug,
+{
+    type Error = Error<E>;
+
+    /// Acceleration in g at the configured range, with the software
+    /// correction applied like [`Bmi160::read_accel_g`]
+    fn accel_norm(&mut self) -> Result<F32x3, accelerometer::Error<Error<E>>> {
+        let g = self.read_accel_g().map_err(accel_error)?;
+        Ok(F32x3::new(g[0], g[1], g[2]))
+    }
+
+    /// Accelerometer output data rate in Hz (ACC_CONF `acc_odr`)
+    fn sample_rate(&mut self) -> Result<f32, accelerometer::Error<Error<E>>> {
+        Ok(self.get_accel_config().map_err(accel_error)?.odr.hz())
+    }
+}
+
+impl<DI, E> RawAccelerometer<I16x3> for Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+    E: Debug,
+{
+    type Error = Error<E>;
+
+    /// Raw accelerometer reading (DATA_ACC)
+    fn accel_raw(&mut self) -> Result<I16x3, accelerometer::Error<Error<E>>> {
+        let accel = self.read_data().map_err(accel_error)?.accel;
+        Ok(I16x3::new(accel.x(), accel.y(), accel.z()))
+    }
+}
@@ -0,0 +1,299 @@
+//! Async driver on [embedded-hal-async], behind the `async` feature
+//!
+//! [`Bmi160`] mirrors the core of the blocking API: register access, data
+//! reads, power modes, sensor configuration and fast offset compensation.
+//! Power mode transitions and FOC are awaited on an async [`DelayNs`]
+//! instead of blocking the executor.
+//!
+//! [embedded-hal-async]: https://docs.rs/embedded-hal-async
+
+use crate::config::{AccelConfig, GyroConfig, PowerStatus};
+use crate::offset::{FocConfig, Offsets};
+use crate::register::{
+    AccelPowerMode, AccelRange, Cmd, GyroPowerMode, GyroRange, MagPowerMode, Register, StatusFlags,
+};
+use crate::{
+    temperature_from_bytes, Data, Error, SensorTime, ACCEL_PMU_DELAY_MS, ADDRESS,
+    GYRO_PMU_DELAY_MS, MAG_PMU_DELAY_MS,
+};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::{I2c, Operation};
+
+/// Upper bound on a FOC run in milliseconds, see
+/// [`crate::Bmi160::run_foc`]
+const FOC_TIMEOUT_MS: u32 = 300;
+
+/// Async BMI160 driver
+#[derive(Debug)]
+pub struct Bmi160<I2C> {
+    /// Underlying I2C device
+    i2c: I2C,
+
+    /// I2C address of the device
+    address: u8,
+
+    /// Accelerometer range last applied
+    accel_range: AccelRange,
+
+    /// Gyroscope range last applied
+    gyro_range: GyroRange,
+}
+
+impl<I2C, E> Bmi160<I2C>
+where
+    I2C: I2c<Error = E>,
+{
+    /// Create a new async BMI160 driver from the given I2C peripheral, at
+    /// [`ADDRESS`]
+    pub fn new(i2c: I2C) -> Self {
+        Bmi160 {
+            i2c,
+            address: ADDRESS,
+            accel_range: AccelRange::G2,
+            gyro_range: GyroRange::Dps2000,
+        }
+    }
+
+    /// Get the chip ID
+    pub async fn get_chip_id(&mut self) -> Result<u8, Error<E>> {
+        self.read_register(Register::CHIP_ID).await
+    }
+
+    /// Read the data (mag, gyro, RHALL, accel) from the DATA registers
+    pub async fn read_data(&mut self) -> Result<Data, Error<E>> {
+        let mut buffer = [0u8; 20];
+        self.write_read_register(Register::DATA, &mut buffer)
+            .await?;
+        Ok(Data::new_from_buffer(&mut buffer))
+    }
+
+    /// Read the 24-bit SENSORTIME counter
+    pub async fn read_sensortime(&mut self) -> Result<SensorTime, Error<E>> {
+        let mut buffer = [0u8; 3];
+        self.write_read_register(Register::SENSORTIME, &mut buffer)
+            .await?;
+        Ok(SensorTime::from_le_bytes(buffer))
+    }
+
+    /// Read the die temperature in °C, `None` while there is no valid
+    /// reading
+    pub async fn read_temperature(&mut self) -> Result<Option<f32>, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.write_read_register(Register::TEMPERATURE, &mut buffer)
+            .await?;
+        Ok(temperature_from_bytes(buffer))
+    }
+
+    /// Read the STATUS register flags
+    pub async fn get_status(&mut self) -> Result<StatusFlags, Error<E>> {
+        self.read_register(Register::STATUS)
+            .await
+            .map(StatusFlags::from_bits_truncate)
+    }
+
+    /// Read the current power modes (PMU_STATUS)
+    pub async fn get_power_status(&mut self) -> Result<PowerStatus, Error<E>> {
+        let pmu_status = self.read_register(Register::PMU_STATUS).await?;
+        let reserved = || Error::ReservedValue(Register::PMU_STATUS, pmu_status);
+
+        Ok(PowerStatus {
+            accel: AccelPowerMode::from_bits(pmu_status >> 4).ok_or_else(reserved)?,
+            gyro: GyroPowerMode::from_bits(pmu_status >> 2).ok_or_else(reserved)?,
+            mag: MagPowerMode::from_bits(pmu_status).ok_or_else(reserved)?,
+        })
+    }
+
+    /// Set the accelerometer power mode and wait for the transition to
+    /// complete
+    pub async fn set_accel_power_mode<D>(
+        &mut self,
+        mode: AccelPowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        let cmd = match mode {
+            AccelPowerMode::Suspend => Cmd::ACC_SET_PMU_MODE_SUSPEND,
+            AccelPowerMode::Normal => Cmd::ACC_SET_PMU_MODE_NORMAL,
+            AccelPowerMode::LowPower => Cmd::ACC_SET_PMU_MODE_LOW_POWER,
+        };
+        self.send_command(cmd).await?;
+        delay.delay_ms(ACCEL_PMU_DELAY_MS.into()).await;
+        Ok(())
+    }
+
+    /// Set the gyroscope power mode and wait for the transition to complete
+    pub async fn set_gyro_power_mode<D>(
+        &mut self,
+        mode: GyroPowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        let cmd = match mode {
+            GyroPowerMode::Suspend => Cmd::GYR_SET_PMU_MODE_SUSPEND,
+            GyroPowerMode::Normal => Cmd::GYR_SET_PMU_MODE_NORMAL,
+            GyroPowerMode::FastStartUp => Cmd::GYR_SET_PMU_MODE_FAST_STARTUP,
+        };
+        self.send_command(cmd).await?;
+        delay.delay_ms(GYRO_PMU_DELAY_MS.into()).await;
+        Ok(())
+    }
+
+    /// Set the magnetometer interface power mode and wait for the
+    /// transition to complete
+    pub async fn set_mag_power_mode<D>(
+        &mut self,
+        mode: MagPowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        let cmd = match mode {
+            MagPowerMode::Suspend => Cmd::MAG_IF_SET_PMU_MODE_SUSPEND,
+            MagPowerMode::Normal => Cmd::MAG_IF_SET_PMU_MODE_NORMAL,
+            MagPowerMode::LowPower => Cmd::MAG_IF_SET_PMU_MODE_LOW_POWER,
+        };
+        self.send_command(cmd).await?;
+        delay.delay_ms(MAG_PMU_DELAY_MS.into()).await;
+        Ok(())
+    }
+
+    /// Accelerometer range last applied by the driver
+    pub fn accel_range(&self) -> AccelRange {
+        self.accel_range
+    }
+
+    /// Gyroscope range last applied by the driver
+    pub fn gyro_range(&self) -> GyroRange {
+        self.gyro_range
+    }
+
+    /// Apply the given accelerometer configuration (ACC_CONF and
+    /// ACC_RANGE in a single burst)
+    pub async fn apply_accel_config(&mut self, config: AccelConfig) -> Result<(), Error<E>> {
+        config.validate()?;
+        self.write_registers(
+            Register::ACC_CONF,
+            &[config.conf_bits(), config.range as u8],
+        )
+        .await?;
+        self.accel_range = config.range;
+        Ok(())
+    }
+
+    /// Apply the given gyroscope configuration (GYR_CONF and GYR_RANGE in
+    /// a single burst)
+    pub async fn apply_gyro_config(&mut self, config: GyroConfig) -> Result<(), Error<E>> {
+        config.validate()?;
+        self.write_registers(
+            Register::GYR_CONF,
+            &[config.conf_bits(), config.range as u8],
+        )
+        .await?;
+        self.gyro_range = config.range;
+        Ok(())
+    }
+
+    /// Read the accelerometer in g at the current range
+    pub async fn read_accel_g(&mut self) -> Result<[f32; 3], Error<E>> {
+        Ok(self
+            .read_data()
+            .await?
+            .accel
+            .scaled(self.accel_range.lsb_per_g()))
+    }
+
+    /// Read the gyroscope in dps at the current range
+    pub async fn read_gyro_dps(&mut self) -> Result<[f32; 3], Error<E>> {
+        Ok(self
+            .read_data()
+            .await?
+            .gyro
+            .scaled(self.gyro_range.lsb_per_dps()))
+    }
+
+    /// Run fast offset compensation and return the resulting offsets.
+    ///
+    /// Like [`crate::Bmi160::run_foc`], but polls STATUS `foc_rdy` with
+    /// async 1 ms delays, failing with [`Error::Timeout`] after 300 ms.
+    pub async fn run_foc<D>(
+        &mut self,
+        config: FocConfig,
+        delay: &mut D,
+    ) -> Result<Offsets, Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.write_register(Register::FOC_CONF, config.bits())
+            .await?;
+        self.send_command(Cmd::START_OFC).await?;
+
+        let mut waited = 0;
+        while !self.get_status().await?.contains(StatusFlags::FOC_RDY) {
+            if waited >= FOC_TIMEOUT_MS {
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_ms(1).await;
+            waited += 1;
+        }
+
+        self.read_offsets().await
+    }
+
+    /// Read the raw OFFSET registers in a single burst
+    pub async fn read_offsets(&mut self) -> Result<Offsets, Error<E>> {
+        let mut bytes = [0u8; 7];
+        self.write_read_register(Register::OFFSET, &mut bytes)
+            .await?;
+        Ok(Offsets::from_bytes(bytes))
+    }
+
+    /// Write to the given register
+    pub async fn write_register(&mut self, register: Register, value: u8) -> Result<(), Error<E>> {
+        debug_assert!(!register.read_only(), "can't write to read-only register");
+        self.i2c
+            .write(self.address, &[register.addr(), value])
+            .await
+            .map_err(Error::Bus)
+    }
+
+    /// Write to a given register, then read the result
+    pub async fn write_read_register(
+        &mut self,
+        register: Register,
+        buffer: &mut [u8],
+    ) -> Result<(), Error<E>> {
+        self.i2c
+            .write_read(self.address, &[register.addr()], buffer)
+            .await
+            .map_err(Error::Bus)
+    }
+
+    /// Read a single register
+    async fn read_register(&mut self, register: Register) -> Result<u8, Error<E>> {
+        let mut buffer = [0u8];
+        self.write_read_register(register, &mut buffer).await?;
+        Ok(buffer[0])
+    }
+
+    /// Write a command to the CMD register
+    async fn send_command(&mut self, cmd: Cmd) -> Result<(), Error<E>> {
+        self.write_register(Register::CMD, cmd as u8).await
+    }
+
+    /// Burst write consecutive registers starting at the given register
+    async fn write_registers(&mut self, register: Register, data: &[u8]) -> Result<(), Error<E>> {
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [Operation::Write(&[register.addr()]), Operation::Write(data)],
+            )
+            .await
+            .map_err(Error::Bus)
+    }
+}
@@ -0,0 +1,539 @@
+//! BMM150 magnetometer attached to the secondary interface
+
+use crate::config::ConfigError;
+use crate::interface::Interface;
+use crate::mag::{MagBus, SecondaryMag};
+use crate::register::{MagBurst, MagPowerMode};
+use crate::{Bmi160, Error};
+use core::convert::TryInto;
+use hal::blocking::delay::DelayMs;
+
+/// BMM150 I2C address on the secondary interface (CSB low, SDO low)
+pub const BMM150_ADDRESS: u8 = 0x10;
+
+/// BMM150 DATAX_LSB, start of the 8-byte data block (X, Y, Z, RHALL)
+pub(crate) const BMM150_DATA: u8 = 0x42;
+
+/// BMM150 DIG_X1, start of the trim registers
+const BMM150_TRIM: u8 = 0x5D;
+
+/// Number of trim bytes, DIG_X1 (0x5D) to DIG_XY1 (0x71)
+const BMM150_TRIM_LEN: usize = 21;
+
+/// X/Y raw value reported on overflow
+const BMM150_XY_OVERFLOW: i16 = -4096;
+
+/// Z raw value reported on overflow
+const BMM150_Z_OVERFLOW: i16 = -16384;
+
+/// Compensated output resolution in LSB per µT
+const BMM150_LSB_PER_UT: f32 = 16.0;
+
+/// BMM150 power control register
+const BMM150_POWER_CONTROL: u8 = 0x4B;
+
+/// BMM150 operation mode register
+pub(crate) const BMM150_OP_MODE: u8 = 0x4C;
+
+/// BMM150 XY repetitions register
+const BMM150_REP_XY: u8 = 0x51;
+
+/// BMM150 Z repetitions register
+const BMM150_REP_Z: u8 = 0x52;
+
+/// BMM150 power control `power_control` bit, leaves suspend for sleep
+const BMM150_POWER_ON: u8 = 0x01;
+
+/// BMM150 operation mode: normal, continuous measurements at the BMM150
+/// data rate (10 Hz)
+const BMM150_NORMAL_MODE: u8 = 0b000;
+
+/// BMM150 operation mode: forced, one measurement then back to sleep
+pub(crate) const BMM150_FORCED_MODE: u8 = 0b010;
+
+/// BMM150 operation mode: sleep
+const BMM150_SLEEP_MODE: u8 = 0b110;
+
+/// BMM150 operation mode register `Self Test` bit, cleared by the BMM150
+/// when the test is done
+const BMM150_SELF_TEST: u8 = 0b0000_0001;
+
+/// Longest wait for the BMM150 self-test in milliseconds
+const BMM150_SELF_TEST_TIMEOUT_MS: u32 = 10;
+
+/// BMM150 start-up time from suspend to sleep in milliseconds
+const BMM150_STARTUP_MS: u8 = 3;
+
+/// BMM150 repetition presets recommended by Bosch, trading noise for
+/// current
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Bmm150Preset {
+    /// 3 XY and 3 Z repetitions
+    LowPower,
+
+    /// 9 XY and 15 Z repetitions (default)
+    #[default]
+    Regular,
+
+    /// 15 XY and 27 Z repetitions
+    Enhanced,
+
+    /// 47 XY and 83 Z repetitions
+    HighAccuracy,
+}
+
+impl Bmm150Preset {
+    /// REP_XY and REP_Z register values. XY takes `2 * rep + 1` and Z
+    /// `rep + 1` repetitions.
+    fn repetitions(self) -> (u8, u8) {
+        match self {
+            Bmm150Preset::LowPower => (0x01, 0x02),
+            Bmm150Preset::Regular => (0x04, 0x0E),
+            Bmm150Preset::Enhanced => (0x07, 0x1A),
+            Bmm150Preset::HighAccuracy => (0x17, 0x52),
+        }
+    }
+}
+
+/// BMM150 RHALL register: the 14-bit hall resistance used for
+/// temperature compensation, plus the data ready flag
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Rhall {
+    /// Hall resistance, 14-bit. 0 means no valid measurement, e.g. after an
+    /// overflow, and the sample can't be compensated.
+    pub resistance: u16,
+
+    /// The BMM150 had a new sample ready (`Data Ready Status` bit)
+    pub data_ready: bool,
+}
+
+impl Rhall {
+    /// Decode RHALL_LSB and RHALL_MSB
+    pub fn from_bytes(lsb: u8, msb: u8) -> Self {
+        Rhall {
+            resistance: u16::from_le_bytes([lsb, msb]) >> 2,
+            data_ready: lsb & 0b1 != 0,
+        }
+    }
+
+    /// Is the resistance usable for compensation?
+    pub fn is_valid(&self) -> bool {
+        self.resistance != 0
+    }
+}
+
+/// BMM150 factory trim data, used to compensate raw readings
+///
+/// Read once by [`Bmi160::setup_bmm150`]. The compensation follows Bosch's
+/// BMM150 reference floating point implementation.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Bmm150Trim {
+    x1: i8,
+    y1: i8,
+    x2: i8,
+    y2: i8,
+    z1: u16,
+    z2: i16,
+    z3: i16,
+    z4: i16,
+    xy1: u8,
+    xy2: i8,
+    xyz1: u16,
+}
+
+impl Bmm150Trim {
+    /// Decode the trim registers 0x5D-0x71
+    fn from_bytes(bytes: &[u8; BMM150_TRIM_LEN]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let i16_at = |i: usize| i16::from_le_bytes([bytes[i], bytes[i + 1]]);
+
+        Bmm150Trim {
+            x1: bytes[0] as i8,
+            y1: bytes[1] as i8,
+            z4: i16_at(5),
+            x2: bytes[7] as i8,
+            y2: bytes[8] as i8,
+            z2: i16_at(11),
+            z1: u16_at(13),
+            xyz1: u16_at(15) & 0x7FFF,
+            z3: i16_at(17),
+            xy2: bytes[19] as i8,
+            xy1: bytes[20],
+        }
+    }
+
+    /// Compensate a raw 8-byte BMM150 data block (X, Y, Z and RHALL, as
+    /// found in DATA_MAG and FIFO magnetometer frames) to µT.
+    ///
+    /// Returns `None` if any axis overflowed or RHALL is invalid, i.e. the
+    /// sample is unusable.
+    pub fn compensate(&self, raw: &[u8; 8]) -> Option<[f32; 3]> {
+        let x = i16::from_le_bytes([raw[0], raw[1]]) >> 3;
+        let y = i16::from_le_bytes([raw[2], raw[3]]) >> 3;
+        let z = i16::from_le_bytes([raw[4], raw[5]]) >> 1;
+
+        let rhall = Rhall::from_bytes(raw[6], raw[7]);
+        if !rhall.is_valid() || self.xyz1 == 0 {
+            return None;
+        }
+        let rhall = rhall.resistance;
+
+        Some([
+            self.compensate_xy(x, rhall, self.x1, self.x2)?,
+            self.compensate_xy(y, rhall, self.y1, self.y2)?,
+            self.compensate_z(z, rhall)?,
+        ])
+    }
+
+    /// Compensate an X or Y reading with that axis' trim values
+    fn compensate_xy(&self, raw: i16, rhall: u16, dig1: i8, dig2: i8) -> Option<f32> {
+        if raw == BMM150_XY_OVERFLOW {
+            return None;
+        }
+
+        let r0 = f32::from(self.xyz1) * 16384.0 / f32::from(rhall) - 16384.0;
+        let r1 =
+            f32::from(self.xy2) * (r0 * r0 / 268_435_456.0) + r0 * f32::from(self.xy1) / 16384.0;
+        let value = f32::from(raw) * (r1 + 256.0) * (f32::from(dig2) + 160.0);
+
+        Some((value / 8192.0 + f32::from(dig1) * 8.0) / BMM150_LSB_PER_UT)
+    }
+
+    /// Compensate a Z reading
+    fn compensate_z(&self, raw: i16, rhall: u16) -> Option<f32> {
+        if raw == BMM150_Z_OVERFLOW || self.z1 == 0 || self.z2 == 0 {
+            return None;
+        }
+
+        let z0 = f32::from(raw) - f32::from(self.z4);
+        let z1 = f32::from(self.z3) * (f32::from(rhall) - f32::from(self.xyz1));
+        let z2 = f32::from(self.z2) + f32::from(self.z1) * f32::from(rhall) / 32768.0;
+
+        Some((z0 * 131_072.0 - z1) / (z2 * 4.0) / BMM150_LSB_PER_UT)
+    }
+}
+
+/// BMM150 as a [`SecondaryMag`]
+///
+/// Initialization powers the BMM150 up, reads its trim data and applies the
+/// repetitions of the chosen preset; the read loop triggers a forced
+/// measurement every cycle and reads all 8 data bytes.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Bmm150 {
+    preset: Bmm150Preset,
+    trim: Bmm150Trim,
+}
+
+impl Bmm150 {
+    /// BMM150 using the given repetition preset
+    pub fn new(preset: Bmm150Preset) -> Self {
+        Bmm150 {
+            preset,
+            trim: Bmm150Trim::default(),
+        }
+    }
+
+    /// Trim data read during initialization
+    pub fn trim(&self) -> Bmm150Trim {
+        self.trim
+    }
+}
+
+impl SecondaryMag for Bmm150 {
+    fn address(&self) -> u8 {
+        BMM150_ADDRESS
+    }
+
+    fn init<B: MagBus>(&mut self, bus: &mut B) -> Result<(), B::Error> {
+        bus.write_register(BMM150_POWER_CONTROL, BMM150_POWER_ON)?;
+        bus.delay_ms(BMM150_STARTUP_MS);
+
+        let mut trim = [0u8; BMM150_TRIM_LEN];
+        bus.read_registers(BMM150_TRIM, &mut trim)?;
+        self.trim = Bmm150Trim::from_bytes(&trim);
+
+        let (rep_xy, rep_z) = self.preset.repetitions();
+        bus.write_register(BMM150_REP_XY, rep_xy)?;
+        bus.write_register(BMM150_REP_Z, rep_z)
+    }
+
+    fn trigger(&self) -> Option<(u8, u8)> {
+        Some((BMM150_OP_MODE, BMM150_FORCED_MODE))
+    }
+
+    fn data_register(&self) -> u8 {
+        BMM150_DATA
+    }
+
+    fn burst(&self) -> MagBurst {
+        MagBurst::Bytes8
+    }
+
+    fn compensate(&self, raw: &[u8]) -> Option<[f32; 3]> {
+        self.trim.compensate(raw.try_into().ok()?)
+    }
+}
+
+/// Result of the BMM150 normal self-test, per axis
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MagSelfTest {
+    /// X axis passed
+    pub x: bool,
+
+    /// Y axis passed
+    pub y: bool,
+
+    /// Z axis passed
+    pub z: bool,
+}
+
+impl MagSelfTest {
+    /// Did all three axes pass?
+    pub fn passed(&self) -> bool {
+        self.x && self.y && self.z
+    }
+}
+
+/// BMM150 power mode
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bmm150PowerMode {
+    /// Lowest current, only the power control register is accessible. The
+    /// BMI160 magnetometer interface is suspended as well.
+    Suspend,
+
+    /// Registers accessible, no measurements. The read loop is stopped.
+    Sleep,
+
+    /// The read loop triggers one measurement per MAG_CONF period, so the
+    /// magnetometer only runs when sampled
+    Forced,
+
+    /// The BMM150 measures continuously at its own 10 Hz data rate and the
+    /// read loop just collects the latest sample
+    Normal,
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Initialize a BMM150 on the secondary interface.
+    ///
+    /// Runs [`Bmi160::setup_mag`] with a [`Bmm150`]: powers the
+    /// magnetometer interface, brings the BMM150 out of suspend, reads its
+    /// trim data for [`Bmi160::read_mag_scaled`] and applies the
+    /// repetitions of `preset`. Each read loop cycle writes forced mode to
+    /// trigger a measurement and reads the data block at DATAX_LSB. The
+    /// interface is left in manual mode; call
+    /// [`Bmi160::start_bmm150_data_mode`] to start the automatic read loop.
+    pub fn setup_bmm150<D>(&mut self, preset: Bmm150Preset, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let mut bmm150 = Bmm150::new(preset);
+        self.setup_mag(&mut bmm150, delay)?;
+        self.bmm150 = Some(bmm150);
+        Ok(())
+    }
+
+    /// Read the latest magnetometer sample from DATA_MAG, compensated to
+    /// µT with the BMM150 trim data.
+    ///
+    /// Requires [`Bmi160::setup_bmm150`] and the read loop in data mode.
+    /// Returns `None` if the sample is invalid: an axis overflowed or RHALL
+    /// reads 0.
+    pub fn read_mag_scaled(&mut self) -> Result<Option<[f32; 3]>, Error<E>> {
+        let bmm150 = self.bmm150.ok_or(ConfigError::MagNotSetUp)?;
+        self.read_mag_compensated(&bmm150)
+    }
+
+    /// BMM150 set up by [`Bmi160::setup_bmm150`], for compensating
+    /// samples read elsewhere, e.g. from the FIFO with
+    /// [`FifoData::mag_ut`](crate::FifoData::mag_ut). `None` before setup.
+    pub fn bmm150(&self) -> Option<Bmm150> {
+        self.bmm150
+    }
+
+    /// Run the BMM150 normal self-test.
+    ///
+    /// Requires [`Bmi160::setup_bmm150`]. Stops the read loop, puts the
+    /// BMM150 in sleep mode and sets its self-test bit, polls once per
+    /// millisecond until the BMM150 clears it (failing with
+    /// [`Error::Timeout`] after 10 ms), then reads the per-axis result bits
+    /// from the data registers. The BMM150 is left in
+    /// [`Bmm150PowerMode::Sleep`]; restore the previous mode with
+    /// [`Bmi160::set_bmm150_power_mode`].
+    pub fn run_mag_self_test<D>(&mut self, delay: &mut D) -> Result<MagSelfTest, Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        if self.bmm150.is_none() {
+            return Err(ConfigError::MagNotSetUp.into());
+        }
+
+        self.enter_mag_setup_mode(delay)?;
+        self.mag_write_register(BMM150_OP_MODE, BMM150_SLEEP_MODE)?;
+        self.mag_write_register(BMM150_OP_MODE, BMM150_SLEEP_MODE | BMM150_SELF_TEST)?;
+
+        let mut waited = 0;
+        while self.mag_read_register(BMM150_OP_MODE)? & BMM150_SELF_TEST != 0 {
+            if waited >= BMM150_SELF_TEST_TIMEOUT_MS {
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_ms(1);
+            waited += 1;
+        }
+
+        let mut data = [0u8; 6];
+        self.mag_read_registers(BMM150_DATA, &mut data)?;
+
+        Ok(MagSelfTest {
+            x: data[0] & 0b1 != 0,
+            y: data[2] & 0b1 != 0,
+            z: data[4] & 0b1 != 0,
+        })
+    }
+
+    /// Switch the BMM150 power mode, keeping the BMI160 magnetometer
+    /// interface in step.
+    ///
+    /// Every transition goes through manual mode, so the read loop is
+    /// stopped while the BMM150 is reconfigured. [`Bmm150PowerMode::Forced`]
+    /// and [`Bmm150PowerMode::Normal`] restart the loop in data mode with
+    /// the matching operation mode written each cycle. Leaving suspend
+    /// waits for the 3 ms BMM150 start-up.
+    pub fn set_bmm150_power_mode<D>(
+        &mut self,
+        mode: Bmm150PowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.enter_mag_setup_mode(delay)?;
+
+        if mode == Bmm150PowerMode::Suspend {
+            self.mag_write_register(BMM150_POWER_CONTROL, 0)?;
+            return self.set_mag_power_mode(MagPowerMode::Suspend, delay);
+        }
+
+        if self.mag_read_register(BMM150_POWER_CONTROL)? & BMM150_POWER_ON == 0 {
+            self.mag_write_register(BMM150_POWER_CONTROL, BMM150_POWER_ON)?;
+            delay.delay_ms(BMM150_STARTUP_MS);
+        }
+
+        let op_mode = match mode {
+            Bmm150PowerMode::Forced => BMM150_FORCED_MODE,
+            Bmm150PowerMode::Normal => BMM150_NORMAL_MODE,
+            _ => return self.mag_write_register(BMM150_OP_MODE, BMM150_SLEEP_MODE),
+        };
+
+        self.mag_write_register(BMM150_OP_MODE, op_mode)?;
+        self.start_bmm150_data_mode()
+    }
+
+    /// Start the automatic read loop for a BMM150 set up with
+    /// [`Bmi160::setup_bmm150`], reading all 8 data bytes (X, Y, Z and
+    /// RHALL) per cycle
+    pub fn start_bmm150_data_mode(&mut self) -> Result<(), Error<E>> {
+        self.start_mag_data_mode(BMM150_DATA, MagBurst::Bytes8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trim registers 0x5D-0x71 of a production BMM150
+    fn trim_bytes(x1: i8, y1: i8, z4: i16, z3: i16) -> [u8; BMM150_TRIM_LEN] {
+        let mut bytes = [0u8; BMM150_TRIM_LEN];
+        bytes[0] = x1 as u8;
+        bytes[1] = y1 as u8;
+        bytes[5..7].copy_from_slice(&z4.to_le_bytes());
+        bytes[7] = 26;
+        bytes[8] = 26;
+        bytes[11..13].copy_from_slice(&763i16.to_le_bytes());
+        bytes[13..15].copy_from_slice(&24747u16.to_le_bytes());
+        // Bit 15 of dig_xyz1 is not part of the value
+        bytes[15..17].copy_from_slice(&(7053u16 | 0x8000).to_le_bytes());
+        bytes[17..19].copy_from_slice(&z3.to_le_bytes());
+        bytes[19] = -3i8 as u8;
+        bytes[20] = 29;
+        bytes
+    }
+
+    /// Raw data block as the BMM150 returns it
+    fn raw(x: i16, y: i16, z: i16, rhall: u16) -> [u8; 8] {
+        let mut raw = [0u8; 8];
+        raw[0..2].copy_from_slice(&(x << 3).to_le_bytes());
+        raw[2..4].copy_from_slice(&(y << 3).to_le_bytes());
+        raw[4..6].copy_from_slice(&(z << 1).to_le_bytes());
+        raw[6..8].copy_from_slice(&(rhall << 2 | 1).to_le_bytes());
+        raw
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn trim_from_bytes() {
+        let trim = Bmm150Trim::from_bytes(&trim_bytes(3, -2, -50, -120));
+
+        assert_eq!(
+            trim,
+            Bmm150Trim {
+                x1: 3,
+                y1: -2,
+                x2: 26,
+                y2: 26,
+                z1: 24747,
+                z2: 763,
+                z3: -120,
+                z4: -50,
+                xy1: 29,
+                xy2: -3,
+                xyz1: 7053,
+            }
+        );
+    }
+
+    // Expected values computed with Bosch's BMM150 floating point
+    // compensation (bmm150.c, compensate_x/y/z) in double precision
+    #[test]
+    fn compensate_matches_reference() {
+        let trim = Bmm150Trim::from_bytes(&trim_bytes(0, 0, 0, 0));
+
+        assert_close(
+            trim.compensate(&raw(100, -200, 300, 6500)).unwrap(),
+            [36.675_16, -73.350_32, 108.323_1],
+        );
+        assert_close(
+            trim.compensate(&raw(-1500, 800, -2000, 6200)).unwrap(),
+            [-553.293_8, 295.09, -752.200_8],
+        );
+
+        let trim = Bmm150Trim::from_bytes(&trim_bytes(3, -2, -50, -120));
+        assert_close(
+            trim.compensate(&raw(100, -200, 300, 6500)).unwrap(),
+            [38.175_16, -74.350_32, 126.194_14],
+        );
+    }
+
+    #[test]
+    fn compensate_rejects_invalid_samples() {
+        let trim = Bmm150Trim::from_bytes(&trim_bytes(0, 0, 0, 0));
+
+        assert_eq!(trim.compensate(&raw(100, -200, 300, 0)), None);
+        assert_eq!(trim.compensate(&raw(BMM150_XY_OVERFLOW, 0, 0, 6500)), None);
+        assert_eq!(trim.compensate(&raw(0, BMM150_XY_OVERFLOW, 0, 6500)), None);
+        assert_eq!(trim.compensate(&raw(0, 0, BMM150_Z_OVERFLOW, 6500)), None);
+        assert_eq!(
+            Bmm150Trim::default().compensate(&raw(100, -200, 300, 6500)),
+            None
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Software calibration applied by the driver on top of the hardware
+//! offsets
+
+use crate::interface::Interface;
+use crate::offset::Offsets;
+use crate::register::{InterruptFlags, StatusFlags};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+/// Longest wait for a new gyroscope sample in milliseconds, long enough
+/// for the slowest gyroscope ODR (25 Hz)
+const GYRO_SAMPLE_TIMEOUT_MS: u32 = 100;
+
+/// Driver-side accelerometer correction, for corrections the OFFSET
+/// registers can't represent
+///
+/// Applied by [`Bmi160::read_accel_g`] as `(raw + offset) * scale` per
+/// axis, on top of any hardware offset compensation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AccelCorrection {
+    /// Offsets in mg added to each axis, same sign convention as the
+    /// OFFSET registers
+    pub offset_mg: [f32; 3],
+
+    /// Scale factor per axis, applied after the offset
+    pub scale: [f32; 3],
+}
+
+impl Default for AccelCorrection {
+    /// No correction
+    fn default() -> Self {
+        AccelCorrection {
+            offset_mg: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl AccelCorrection {
+    /// Apply to a reading in g
+    pub(crate) fn apply(&self, g: [f32; 3]) -> [f32; 3] {
+        let axis = |i: usize| (g[i] + self.offset_mg[i] / 1000.0) * self.scale[i];
+        [axis(0), axis(1), axis(2)]
+    }
+}
+
+/// Gyroscope bias as a function of die temperature, consulted by
+/// [`Bmi160::read_gyro_dps_with`]
+pub trait BiasModel {
+    /// Bias in dps to subtract at the given temperature in °C
+    fn bias_dps(&self, temperature_c: f32) -> [f32; 3];
+}
+
+/// Bias varying linearly with temperature around a reference point
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LinearBiasModel {
+    /// Temperature in °C at which `bias_dps` was measured
+    pub reference_c: f32,
+
+    /// Bias in dps at the reference temperature
+    pub bias_dps: [f32; 3],
+
+    /// Change in bias per °C, in dps/°C
+    pub slope_dps_per_c: [f32; 3],
+}
+
+impl BiasModel for LinearBiasModel {
+    fn bias_dps(&self, temperature_c: f32) -> [f32; 3] {
+        let delta = temperature_c - self.reference_c;
+        let axis = |i: usize| self.bias_dps[i] + self.slope_dps_per_c[i] * delta;
+        [axis(0), axis(1), axis(2)]
+    }
+}
+
+/// Calibration captured at the factory or on first boot, to be persisted by
+/// the application and re-applied with [`Bmi160::apply_calibration`]
+///
+/// Serialize with [`CalibrationData::to_bytes`], or with serde when the
+/// `serde` feature is enabled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CalibrationData {
+    /// Accelerometer offsets in mg, written to the OFFSET registers where
+    /// they fit and applied in software otherwise
+    pub accel_offset_mg: [f32; 3],
+
+    /// Accelerometer scale factors, applied by [`Bmi160::read_accel_g`]
+    pub accel_scale: [f32; 3],
+
+    /// Gyroscope bias in dps, subtracted by [`Bmi160::read_gyro_dps`]
+    pub gyro_offset_dps: [f32; 3],
+
+    /// Die temperature in °C when the calibration was taken, if known
+    pub temperature_c: Option<f32>,
+}
+
+impl Default for CalibrationData {
+    /// No correction
+    fn default() -> Self {
+        CalibrationData {
+            accel_offset_mg: [0.0; 3],
+            accel_scale: [1.0; 3],
+            gyro_offset_dps: [0.0; 3],
+            temperature_c: None,
+        }
+    }
+}
+
+impl CalibrationData {
+    /// Length of the [`CalibrationData::to_bytes`] encoding
+    pub const LEN: usize = 40;
+
+    /// Encode as ten little-endian `f32`s: accelerometer offsets, scales,
+    /// gyroscope offsets and the temperature, with NaN for an unknown
+    /// temperature
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let temperature = self.temperature_c.unwrap_or(f32::NAN);
+        let values = self
+            .accel_offset_mg
+            .iter()
+            .chain(&self.accel_scale)
+            .chain(&self.gyro_offset_dps)
+            .chain(core::iter::once(&temperature));
+
+        let mut bytes = [0u8; Self::LEN];
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Decode bytes produced by [`CalibrationData::to_bytes`]
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        let value = |i: usize| {
+            let mut le = [0u8; 4];
+            le.copy_from_slice(&bytes[4 * i..4 * i + 4]);
+            f32::from_le_bytes(le)
+        };
+        let temperature = value(9);
+
+        CalibrationData {
+            accel_offset_mg: [value(0), value(1), value(2)],
+            accel_scale: [value(3), value(4), value(5)],
+            gyro_offset_dps: [value(6), value(7), value(8)],
+            temperature_c: if temperature.is_nan() {
+                None
+            } else {
+                Some(temperature)
+            },
+        }
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Estimate the gyroscope bias by averaging `samples` consecutive
+    /// readings, and apply it to [`Bmi160::read_gyro_dps`].
+    ///
+    /// The device must be still and the gyroscope in normal mode. Each
+    /// reading waits for STATUS `drdy_gyr`, polling once per millisecond and
+    /// failing with [`Error::Timeout`] if no sample arrives within 100 ms.
+    /// Returns the bias in dps. The hardware OFFSET registers are not
+    /// touched.
+    pub fn estimate_gyro_bias<D>(
+        &mut self,
+        samples: u16,
+        delay: &mut D,
+    ) -> Result<[f32; 3], Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let mut sum = [0i32; 3];
+
+        for _ in 0..samples {
+            self.wait_gyro_sample(delay)?;
+            let gyro = self.read_data()?.gyro;
+            sum[0] += i32::from(gyro.x());
+            sum[1] += i32::from(gyro.y());
+            sum[2] += i32::from(gyro.z());
+        }
+
+        let lsb_per_dps = self.gyro_range.lsb_per_dps();
+        let count = f32::from(samples.max(1));
+        let bias = |sum: i32| sum as f32 / count / lsb_per_dps;
+        self.gyro_bias = [bias(sum[0]), bias(sum[1]), bias(sum[2])];
+
+        Ok(self.gyro_bias)
+    }
+
+    /// Like [`Bmi160::estimate_gyro_bias`], but only accepts the result if
+    /// the no-motion engine reports the device still both before and after
+    /// sampling; otherwise fails with [`Error::Moving`] and keeps the
+    /// previous bias.
+    ///
+    /// No-motion detection must already be enabled in no-motion mode, see
+    /// [`Bmi160::configure_no_motion`].
+    pub fn estimate_gyro_bias_when_still<D>(
+        &mut self,
+        samples: u16,
+        delay: &mut D,
+    ) -> Result<[f32; 3], Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        if !self.no_motion_active()? {
+            return Err(Error::Moving);
+        }
+
+        let previous = self.gyro_bias;
+        let bias = self.estimate_gyro_bias(samples, delay)?;
+
+        if !self.no_motion_active()? {
+            self.gyro_bias = previous;
+            return Err(Error::Moving);
+        }
+
+        Ok(bias)
+    }
+
+    /// Apply stored calibration.
+    ///
+    /// The accelerometer offsets go to the OFFSET registers with
+    /// accelerometer offset compensation enabled; the gyroscope OFFSET
+    /// values and enable are kept. Whatever the registers can't hold
+    /// (beyond ±499.2 mg), the accelerometer scales and the gyroscope bias
+    /// are applied in software by [`Bmi160::read_accel_g`] and
+    /// [`Bmi160::read_gyro_dps`].
+    pub fn apply_calibration(&mut self, calibration: &CalibrationData) -> Result<(), Error<E>> {
+        let current = self.read_offsets()?;
+        let mut offsets = Offsets::from_units(calibration.accel_offset_mg, [0.0; 3]);
+        offsets.gyro = current.gyro;
+        self.write_offsets(offsets)?;
+
+        let (_, gyro_enabled) = self.offset_compensation_enabled()?;
+        self.enable_offset_compensation(true, gyro_enabled)?;
+
+        let hardware = offsets.accel_mg();
+        let residual = |i: usize| calibration.accel_offset_mg[i] - hardware[i];
+        let correction = AccelCorrection {
+            offset_mg: [residual(0), residual(1), residual(2)],
+            scale: calibration.accel_scale,
+        };
+        self.accel_correction = if correction == AccelCorrection::default() {
+            None
+        } else {
+            Some(correction)
+        };
+
+        self.gyro_bias = calibration.gyro_offset_dps;
+        Ok(())
+    }
+
+    /// Capture the calibration currently in effect: the accelerometer
+    /// OFFSET registers plus the software correction, the software
+    /// gyroscope bias, and the current die temperature
+    pub fn read_calibration(&mut self) -> Result<CalibrationData, Error<E>> {
+        let hardware = self.read_offsets()?.accel_mg();
+        let correction = self.accel_correction.unwrap_or_default();
+        let offset = |i: usize| hardware[i] + correction.offset_mg[i];
+
+        Ok(CalibrationData {
+            accel_offset_mg: [offset(0), offset(1), offset(2)],
+            accel_scale: correction.scale,
+            gyro_offset_dps: self.gyro_bias,
+            temperature_c: self.read_temperature()?,
+        })
+    }
+
+    /// Read the accelerometer in g at the current range, with the software
+    /// correction applied if set
+    pub fn read_accel_g(&mut self) -> Result<[f32; 3], Error<E>> {
+        let g = self.read_data()?.accel.scaled(self.accel_range.lsb_per_g());
+        Ok(match self.accel_correction {
+            Some(correction) => correction.apply(g),
+            None => g,
+        })
+    }
+
+    /// Software accelerometer correction applied by
+    /// [`Bmi160::read_accel_g`], if any
+    pub fn accel_correction(&self) -> Option<AccelCorrection> {
+        self.accel_correction
+    }
+
+    /// Set or clear the software accelerometer correction. Raw reads,
+    /// FIFO data and the interrupt engines are not affected.
+    pub fn set_accel_correction(&mut self, correction: Option<AccelCorrection>) {
+        self.accel_correction = correction;
+    }
+
+    /// Software gyroscope bias in dps, subtracted by
+    /// [`Bmi160::read_gyro_dps`]
+    pub fn gyro_bias(&self) -> [f32; 3] {
+        self.gyro_bias
+    }
+
+    /// Set the software gyroscope bias in dps, e.g. one saved from an
+    /// earlier [`Bmi160::estimate_gyro_bias`]. Use `[0.0; 3]` to disable.
+    pub fn set_gyro_bias(&mut self, bias: [f32; 3]) {
+        self.gyro_bias = bias;
+    }
+
+    /// Read the gyroscope in dps at the current range, with the software
+    /// bias subtracted
+    pub fn read_gyro_dps(&mut self) -> Result<[f32; 3], Error<E>> {
+        let dps = self.read_data()?.gyro.scaled(self.gyro_range.lsb_per_dps());
+        Ok([
+            dps[0] - self.gyro_bias[0],
+            dps[1] - self.gyro_bias[1],
+            dps[2] - self.gyro_bias[2],
+        ])
+    }
+
+    /// Read the gyroscope like [`Bmi160::read_gyro_dps`], additionally
+    /// subtracting the bias `model` predicts at the current die
+    /// temperature.
+    ///
+    /// Costs an extra TEMPERATURE read. If the temperature sensor has no
+    /// valid reading, the model is skipped.
+    pub fn read_gyro_dps_with<M>(&mut self, model: &M) -> Result<[f32; 3], Error<E>>
+    where
+        M: BiasModel + ?Sized,
+    {
+        let temperature = self.read_temperature()?;
+        let dps = self.read_gyro_dps()?;
+
+        Ok(match temperature {
+            Some(temperature) => {
+                let bias = model.bias_dps(temperature);
+                [dps[0] - bias[0], dps[1] - bias[1], dps[2] - bias[2]]
+            }
+            None => dps,
+        })
+    }
+
+    /// Is the no-motion interrupt currently asserted?
+    fn no_motion_active(&mut self) -> Result<bool, Error<E>> {
+        Ok(self
+            .get_interrupt_status()?
+            .fired
+            .contains(InterruptFlags::NO_MOTION))
+    }
+
+    /// Poll STATUS `drdy_gyr` until a new gyroscope sample is available
+    fn wait_gyro_sample<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let mut waited = 0;
+        while !self.get_status()?.contains(StatusFlags::DRDY_GYR) {
+            if waited >= GYRO_SAMPLE_TIMEOUT_MS {
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_ms(1);
+            waited += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Mock;
+
+    fn calibration(temperature_c: Option<f32>) -> CalibrationData {
+        CalibrationData {
+            accel_offset_mg: [600.0, -11.7, 0.0],
+            accel_scale: [1.01, 0.99, 1.0],
+            gyro_offset_dps: [0.25, -0.5, 1.5],
+            temperature_c,
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        for &temperature in &[Some(23.5), Some(-40.0), None] {
+            let data = calibration(temperature);
+            assert_eq!(CalibrationData::from_bytes(&data.to_bytes()), data);
+        }
+
+        assert_eq!(
+            CalibrationData::from_bytes(&CalibrationData::default().to_bytes()),
+            CalibrationData::default()
+        );
+    }
+
+    #[test]
+    fn bytes_layout() {
+        let bytes = calibration(None).to_bytes();
+
+        assert_eq!(bytes[..4], 600.0f32.to_le_bytes());
+        assert_eq!(bytes[12..16], 1.01f32.to_le_bytes());
+        assert_eq!(bytes[32..36], 1.5f32.to_le_bytes());
+        assert!(f32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]]).is_nan());
+    }
+
+    #[test]
+    fn apply_and_read_back() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let data = calibration(None);
+
+        imu.apply_calibration(&data).unwrap();
+
+        // X saturates the OFFSET register, the rest is corrected in software
+        let offsets = imu.read_offsets().unwrap();
+        assert_eq!(offsets.accel, [127, -3, 0]);
+        assert_eq!(imu.offset_compensation_enabled().unwrap(), (true, false));
+        assert!(imu.accel_correction().is_some());
+
+        let read = imu.read_calibration().unwrap();
+        for i in 0..3 {
+            assert!((read.accel_offset_mg[i] - data.accel_offset_mg[i]).abs() < 1e-3);
+        }
+        assert_eq!(read.accel_scale, data.accel_scale);
+        assert_eq!(read.gyro_offset_dps, data.gyro_offset_dps);
+    }
+}
@@ -0,0 +1,960 @@
+//! Typed accelerometer/gyroscope configuration and the full-device
+//! configuration builder
+
+use crate::fifo::{FifoConfig, FifoMode};
+use crate::interface::Interface;
+use crate::interrupt::{
+    IntPinConfig, InterruptConfig, InterruptMap, InterruptRoute, InterruptSource,
+};
+use crate::register::{
+    AccelBandwidth, AccelOdr, AccelPowerMode, AccelRange, GyroBandwidth, GyroOdr, GyroPowerMode,
+    GyroRange, InterruptEnable, MagOdr, MagPowerMode, Register, SecondaryInterface, SpiWireMode,
+};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+/// Highest accelerometer ODR in Hz with undersampling
+const ACCEL_UNDERSAMPLING_ODR_MAX_HZ: f32 = 400.0;
+
+/// Internal accelerometer sampling rate in Hz that undersampling averages
+const ACCEL_SAMPLE_RATE_HZ: f32 = 1600.0;
+
+/// Highest gyroscope data rate in Hz, which oversampling multiplies the ODR
+/// up to
+const GYRO_SAMPLE_RATE_MAX_HZ: f32 = 3200.0;
+
+/// Single register writes need 450 µs between them while the device is in
+/// suspend or low power mode
+const SUSPEND_WRITE_DELAY_MS: u8 = 1;
+
+/// Reason a configuration was rejected before being written to the device.
+///
+/// These mirror the combinations the BMI160 itself refuses (reporting them
+/// via `err_code` in ERR_REG while silently keeping the old settings).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConfigError {
+    /// Accelerometer ODRs below 12.5 Hz need undersampling (an `Avg*` filter)
+    AccelOdrRequiresUndersampling,
+
+    /// Accelerometer undersampling is only valid in low power mode
+    AccelUndersamplingRequiresLowPower,
+
+    /// Accelerometer low power mode needs undersampling (an `Avg*` filter)
+    AccelLowPowerRequiresUndersampling,
+
+    /// Accelerometer undersampling is limited to 400 Hz
+    AccelUndersamplingOdrTooHigh,
+
+    /// Averaging window doesn't fit in one output period: the number of
+    /// averaged samples times the ODR exceeds the 1600 Hz sampling rate
+    AccelAveragingTooLong,
+
+    /// Accelerometer low power mode needs the gyroscope suspended or in
+    /// fast start-up mode
+    AccelLowPowerRequiresGyroOff,
+
+    /// Gyroscope oversampling times the ODR exceeds the 3200 Hz maximum
+    /// data rate
+    GyroOversamplingTooHigh,
+
+    /// Headerless FIFO mode needs all enabled sources to share one ODR
+    FifoHeaderlessOdrMismatch,
+
+    /// No data sources are enabled in the FIFO
+    FifoNoSources,
+
+    /// FIFO watermark exceeds the 1020 byte maximum
+    FifoWatermarkTooLarge,
+
+    /// Requested batch doesn't fit in the 1024 byte FIFO
+    FifoBatchTooLarge,
+
+    /// Maximum transfer length is too small to hold a complete FIFO frame
+    TransferTooSmall,
+
+    /// Burst read would run past the last register (0x7F)
+    ReadPastLastRegister,
+
+    /// Magnetometer compensation needs the trim data read by
+    /// [`Bmi160::setup_bmm150`]
+    MagNotSetUp,
+
+    /// Magnetometer read offset exceeds the 4-bit `mag_offset` field
+    MagReadOffsetTooLarge,
+
+    /// Indirect magnetometer access needs the interface in setup mode, see
+    /// [`Bmi160::enter_mag_setup_mode`]
+    MagNotInSetupMode,
+}
+
+/// Accelerometer configuration, covering ACC_CONF (0x40) and ACC_RANGE (0x41).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AccelConfig {
+    /// Output data rate
+    pub odr: AccelOdr,
+
+    /// Filter mode (oversampling, or undersampling with averaging)
+    pub bandwidth: AccelBandwidth,
+
+    /// Measurement range
+    pub range: AccelRange,
+}
+
+impl AccelConfig {
+    /// Check that the ODR and filter mode can be used together
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let slow = matches!(
+            self.odr,
+            AccelOdr::Hz0_78 | AccelOdr::Hz1_56 | AccelOdr::Hz3_12 | AccelOdr::Hz6_25
+        );
+
+        if !self.bandwidth.undersampling() {
+            if slow {
+                return Err(ConfigError::AccelOdrRequiresUndersampling);
+            }
+            return Ok(());
+        }
+
+        if self.odr.hz() > ACCEL_UNDERSAMPLING_ODR_MAX_HZ {
+            return Err(ConfigError::AccelUndersamplingOdrTooHigh);
+        }
+
+        let samples = 1u16 << ((self.bandwidth.bits() >> 4) & 0b111);
+        if f32::from(samples) * self.odr.hz() > ACCEL_SAMPLE_RATE_HZ {
+            return Err(ConfigError::AccelAveragingTooLong);
+        }
+
+        Ok(())
+    }
+
+    /// Check that this configuration can be used in the given power mode
+    pub fn validate_for(&self, mode: AccelPowerMode) -> Result<(), ConfigError> {
+        self.validate()?;
+
+        match (mode, self.bandwidth.undersampling()) {
+            (AccelPowerMode::Normal, true) => Err(ConfigError::AccelUndersamplingRequiresLowPower),
+            (AccelPowerMode::LowPower, false) => {
+                Err(ConfigError::AccelLowPowerRequiresUndersampling)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Value of the ACC_CONF register for this configuration
+    pub(crate) fn conf_bits(&self) -> u8 {
+        self.bandwidth.bits() | self.odr as u8
+    }
+}
+
+impl Default for AccelConfig {
+    /// Power-on reset configuration: 100 Hz, normal filter, ±2 g
+    fn default() -> Self {
+        AccelConfig {
+            odr: AccelOdr::Hz100,
+            bandwidth: AccelBandwidth::Normal,
+            range: AccelRange::G2,
+        }
+    }
+}
+
+/// Gyroscope configuration, covering GYR_CONF (0x42) and GYR_RANGE (0x43).
+///
+/// Every field is a typed enum, so reserved `gyr_odr`/`gyr_bwp`/`gyr_range`
+/// encodings (which the chip flags in ERR_REG) can't be expressed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GyroConfig {
+    /// Output data rate
+    pub odr: GyroOdr,
+
+    /// Filter mode
+    pub bandwidth: GyroBandwidth,
+
+    /// Measurement range
+    pub range: GyroRange,
+}
+
+impl GyroConfig {
+    /// Check that the ODR and filter mode can be used together
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let oversampling = match self.bandwidth {
+            GyroBandwidth::Osr4 => 4.0,
+            GyroBandwidth::Osr2 => 2.0,
+            GyroBandwidth::Normal => 1.0,
+        };
+
+        if oversampling * self.odr.hz() > GYRO_SAMPLE_RATE_MAX_HZ {
+            return Err(ConfigError::GyroOversamplingTooHigh);
+        }
+
+        Ok(())
+    }
+
+    /// Value of the GYR_CONF register for this configuration
+    pub(crate) fn conf_bits(&self) -> u8 {
+        (self.bandwidth as u8) << 4 | self.odr as u8
+    }
+}
+
+impl Default for GyroConfig {
+    /// Power-on reset configuration: 100 Hz, normal filter, ±2000 °/s
+    fn default() -> Self {
+        GyroConfig {
+            odr: GyroOdr::Hz100,
+            bandwidth: GyroBandwidth::Normal,
+            range: GyroRange::Dps2000,
+        }
+    }
+}
+
+/// Digital interface configuration, covering IF_CONF (0x6B).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct InterfaceConfig {
+    /// SPI 3-wire or 4-wire mode
+    pub spi_mode: SpiWireMode,
+
+    /// What the secondary interface is used for
+    pub secondary: SecondaryInterface,
+}
+
+impl InterfaceConfig {
+    /// Value of the IF_CONF register for this configuration
+    pub(crate) fn bits(&self) -> u8 {
+        (self.secondary as u8) << 4 | self.spi_mode as u8
+    }
+
+    /// Decode the IF_CONF register
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        InterfaceConfig {
+            spi_mode: if bits & 0b1 != 0 {
+                SpiWireMode::ThreeWire
+            } else {
+                SpiWireMode::FourWire
+            },
+            secondary: SecondaryInterface::from_bits(bits >> 4),
+        }
+    }
+}
+
+/// Current power modes, as reported by PMU_STATUS (0x03)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PowerStatus {
+    /// Accelerometer power mode
+    pub accel: AccelPowerMode,
+
+    /// Gyroscope power mode
+    pub gyro: GyroPowerMode,
+
+    /// Magnetometer interface power mode
+    pub mag: MagPowerMode,
+}
+
+/// Full-device configuration, applied with [`Bmi160::configure`].
+///
+/// Starts from the power-on reset state (everything suspended, default
+/// filter/range settings) and is built up with the chained setters below.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Bmi160Config {
+    accel_power: AccelPowerMode,
+    gyro_power: GyroPowerMode,
+    mag_power: MagPowerMode,
+    accel: AccelConfig,
+    gyro: GyroConfig,
+    mag_odr: MagOdr,
+    fifo: FifoConfig,
+    interrupts: Option<InterruptConfig>,
+}
+
+impl Bmi160Config {
+    /// Create a new configuration matching the power-on reset state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the accelerometer power mode
+    pub fn accel_power_mode(mut self, mode: AccelPowerMode) -> Self {
+        self.accel_power = mode;
+        self
+    }
+
+    /// Set the gyroscope power mode
+    pub fn gyro_power_mode(mut self, mode: GyroPowerMode) -> Self {
+        self.gyro_power = mode;
+        self
+    }
+
+    /// Set the magnetometer interface power mode
+    pub fn mag_power_mode(mut self, mode: MagPowerMode) -> Self {
+        self.mag_power = mode;
+        self
+    }
+
+    /// Set the accelerometer configuration
+    pub fn accel(mut self, config: AccelConfig) -> Self {
+        self.accel = config;
+        self
+    }
+
+    /// Set the gyroscope configuration
+    pub fn gyro(mut self, config: GyroConfig) -> Self {
+        self.gyro = config;
+        self
+    }
+
+    /// Set the magnetometer interface read loop rate
+    pub fn mag_odr(mut self, odr: MagOdr) -> Self {
+        self.mag_odr = odr;
+        self
+    }
+
+    /// Set the FIFO configuration
+    pub fn fifo(mut self, config: FifoConfig) -> Self {
+        self.fifo = config;
+        self
+    }
+
+    /// Set the interrupt engines, pin mapping and pin configuration.
+    ///
+    /// Left unset, [`Bmi160::configure`] doesn't touch the interrupt
+    /// registers.
+    pub fn interrupts(mut self, config: InterruptConfig) -> Self {
+        self.interrupts = Some(config);
+        self
+    }
+
+    /// Check that the ODR, filter and power mode combinations are accepted
+    /// by the device
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.accel.validate_for(self.accel_power)?;
+        self.gyro.validate()?;
+
+        if self.accel_power == AccelPowerMode::LowPower && self.gyro_power == GyroPowerMode::Normal
+        {
+            return Err(ConfigError::AccelLowPowerRequiresGyroOff);
+        }
+
+        if self.fifo.mode == FifoMode::Headerless
+            && self.fifo.accel
+            && self.fifo.gyro
+            && self.accel.odr.hz() != self.gyro.odr.hz()
+        {
+            return Err(ConfigError::FifoHeaderlessOdrMismatch);
+        }
+
+        Ok(())
+    }
+}
+
+/// Opinionated starting configurations for common workloads.
+///
+/// Each preset maps to a [`Bmi160Config`] (see [`Preset::config`]), which can
+/// be tweaked further before being applied, or applied directly with
+/// [`Bmi160::apply_preset`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Preset {
+    /// Flight controller running a ~1 kHz control loop.
+    ///
+    /// Gyroscope at 1600 Hz (normal filter, ±2000 °/s) so every loop
+    /// iteration sees a fresh sample, accelerometer at 1600 Hz and ±16 g to
+    /// stay out of saturation under motor vibration. No FIFO: data ready is
+    /// routed to INT1 (active high, push-pull) to pace the loop, and the
+    /// latest sample is read directly.
+    Drone1kHz,
+
+    /// Battery powered wearable tracking activity.
+    ///
+    /// Accelerometer only, in low power mode at 50 Hz averaging 4 samples,
+    /// ±4 g, buffered in the FIFO so the host can sleep between batches,
+    /// woken by the FIFO watermark interrupt on INT1 (active high,
+    /// push-pull). The watermark level itself is set separately, see
+    /// [`Bmi160::set_fifo_watermark_frames`]. Gyroscope and magnetometer
+    /// interface are suspended.
+    WearableLowPower,
+
+    /// Head tracking for AR/VR headsets.
+    ///
+    /// Gyroscope at 800 Hz and ±1000 °/s for low-latency rotation tracking,
+    /// accelerometer at 400 Hz and ±4 g for gravity/tilt correction. Both are
+    /// buffered in the FIFO (header mode, as the ODRs differ) with
+    /// sensortime for timestamping, and the FIFO watermark interrupt is
+    /// routed to INT1 (active high, push-pull).
+    ArHeadset,
+
+    /// Accelerometer-only step counting at the lowest current.
+    ///
+    /// Accelerometer in low power mode at 25 Hz without averaging, ±2 g;
+    /// gyroscope and magnetometer interface suspended, FIFO off. The step
+    /// detector interrupt is routed to INT1 (active high, push-pull). See
+    /// [`Bmi160::enable_low_power_pedometer`], which also enables the step
+    /// counter and no-motion detection.
+    Pedometer,
+}
+
+impl Preset {
+    /// Get the full-device configuration for this preset
+    pub fn config(self) -> Bmi160Config {
+        match self {
+            Preset::Drone1kHz => Bmi160Config::new()
+                .accel_power_mode(AccelPowerMode::Normal)
+                .gyro_power_mode(GyroPowerMode::Normal)
+                .accel(AccelConfig {
+                    odr: AccelOdr::Hz1600,
+                    bandwidth: AccelBandwidth::Normal,
+                    range: AccelRange::G16,
+                })
+                .gyro(GyroConfig {
+                    odr: GyroOdr::Hz1600,
+                    bandwidth: GyroBandwidth::Normal,
+                    range: GyroRange::Dps2000,
+                })
+                .interrupts(int1_config(
+                    InterruptEnable::DATA_READY,
+                    InterruptSource::DataReady,
+                )),
+            Preset::WearableLowPower => Bmi160Config::new()
+                .accel_power_mode(AccelPowerMode::LowPower)
+                .accel(AccelConfig {
+                    odr: AccelOdr::Hz50,
+                    bandwidth: AccelBandwidth::Avg4,
+                    range: AccelRange::G4,
+                })
+                .fifo(FifoConfig {
+                    accel: true,
+                    ..FifoConfig::default()
+                })
+                .interrupts(int1_config(
+                    InterruptEnable::FIFO_WATERMARK,
+                    InterruptSource::FifoWatermark,
+                )),
+            Preset::ArHeadset => Bmi160Config::new()
+                .accel_power_mode(AccelPowerMode::Normal)
+                .gyro_power_mode(GyroPowerMode::Normal)
+                .accel(AccelConfig {
+                    odr: AccelOdr::Hz400,
+                    bandwidth: AccelBandwidth::Normal,
+                    range: AccelRange::G4,
+                })
+                .gyro(GyroConfig {
+                    odr: GyroOdr::Hz800,
+                    bandwidth: GyroBandwidth::Normal,
+                    range: GyroRange::Dps1000,
+                })
+                .fifo(FifoConfig {
+                    accel: true,
+                    gyro: true,
+                    sensortime: true,
+                    ..FifoConfig::default()
+                })
+                .interrupts(int1_config(
+                    InterruptEnable::FIFO_WATERMARK,
+                    InterruptSource::FifoWatermark,
+                )),
+            Preset::Pedometer => Bmi160Config::new()
+                .accel_power_mode(AccelPowerMode::LowPower)
+                .accel(AccelConfig {
+                    odr: AccelOdr::Hz25,
+                    bandwidth: AccelBandwidth::Avg1,
+                    range: AccelRange::G2,
+                })
+                .interrupts(int1_config(
+                    InterruptEnable::STEP_DETECTOR,
+                    InterruptSource::StepDetector,
+                )),
+        }
+    }
+}
+
+/// Interrupt setup for presets: one engine routed to INT1, driven active
+/// high push-pull, non-latched
+pub(crate) fn int1_config(enabled: InterruptEnable, source: InterruptSource) -> InterruptConfig {
+    InterruptConfig {
+        enabled,
+        map: InterruptMap::new().map(source, InterruptRoute::Int1),
+        int1: IntPinConfig::ACTIVE_HIGH_OUTPUT,
+        ..InterruptConfig::default()
+    }
+}
+
+impl From<Preset> for Bmi160Config {
+    fn from(preset: Preset) -> Self {
+        preset.config()
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Apply the given accelerometer configuration.
+    ///
+    /// ACC_CONF and ACC_RANGE are written in a single burst, so the device
+    /// must not be in suspend or low power mode (see [`Bmi160::configure`]).
+    pub fn apply_accel_config(&mut self, config: AccelConfig) -> Result<(), Error<E>> {
+        config.validate()?;
+        self.write_registers(
+            Register::ACC_CONF,
+            &[config.conf_bits(), config.range as u8],
+        )?;
+
+        if config.range != self.accel_range {
+            self.accel_range = config.range;
+            self.rescale_mg_thresholds()?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the given gyroscope configuration.
+    ///
+    /// GYR_CONF and GYR_RANGE are adjacent, so both are written in a single
+    /// burst and the gyroscope never runs with a mix of old and new settings.
+    pub fn apply_gyro_config(&mut self, config: GyroConfig) -> Result<(), Error<E>> {
+        config.validate()?;
+        self.write_registers(
+            Register::GYR_CONF,
+            &[config.conf_bits(), config.range as u8],
+        )?;
+        self.gyro_range = config.range;
+        Ok(())
+    }
+
+    /// Apply the given digital interface configuration (IF_CONF).
+    ///
+    /// Selecting [`SecondaryInterface::Ois`] locks the primary interface to
+    /// I2C; switching to 3-wire SPI takes effect on the next SPI transaction.
+    pub fn apply_interface_config(&mut self, config: InterfaceConfig) -> Result<(), Error<E>> {
+        self.write_register(Register::IF_CONF, config.bits())
+    }
+
+    /// Read the current digital interface configuration (IF_CONF)
+    pub fn get_interface_config(&mut self) -> Result<InterfaceConfig, Error<E>> {
+        self.read_register(Register::IF_CONF)
+            .map(InterfaceConfig::from_bits)
+    }
+
+    /// Apply a full-device configuration.
+    ///
+    /// The accelerometer is brought to normal mode first so that register
+    /// bursts are accepted, sensor configuration is written, and only then
+    /// are the gyroscope, magnetometer interface and accelerometer switched
+    /// to their requested power modes, waiting out each transition.
+    ///
+    /// ACC_CONF must match the accelerometer power mode at all times:
+    /// undersampling is only valid in low power mode and vice versa. So the
+    /// accelerometer is suspended around every ACC_CONF change, and while it
+    /// runs in normal mode for the bursts it uses a normal-mode filter.
+    /// The requested undersampling ACC_CONF is written last, in suspend,
+    /// right before entering low power mode.
+    ///
+    /// Staged interrupts are written once the gyroscope and magnetometer
+    /// interface are powered, while the accelerometer still accepts bursts.
+    ///
+    /// The configuration is validated up front; nothing is written if it is
+    /// rejected.
+    pub fn configure<D>(&mut self, config: &Bmi160Config, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        config.validate()?;
+
+        let normal_accel = if config.accel.bandwidth.undersampling() {
+            AccelConfig {
+                range: config.accel.range,
+                ..AccelConfig::default()
+            }
+        } else {
+            config.accel
+        };
+
+        self.enter_accel_normal_mode(normal_accel, delay)?;
+
+        self.apply_accel_config(normal_accel)?;
+        self.apply_gyro_config(config.gyro)?;
+        self.set_mag_odr(config.mag_odr)?;
+        self.apply_fifo_config(config.fifo)?;
+
+        self.set_gyro_power_mode(config.gyro_power, delay)?;
+        self.set_mag_power_mode(config.mag_power, delay)?;
+
+        if let Some(interrupts) = &config.interrupts {
+            self.apply_interrupt_config(interrupts)?;
+        }
+
+        if config.accel_power != AccelPowerMode::Normal {
+            self.switch_accel_mode(config.accel_power, config.accel, delay)?;
+        }
+
+        Ok(())
+    }
+
+    /// Bring the accelerometer to normal mode, where burst writes are
+    /// accepted, running with `accel`'s filter and ODR.
+    ///
+    /// Goes through suspend so ACC_CONF is never left undersampling while
+    /// the accelerometer runs in normal mode. `accel` must not use
+    /// undersampling.
+    pub(crate) fn enter_accel_normal_mode<D>(
+        &mut self,
+        accel: AccelConfig,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.switch_accel_mode(AccelPowerMode::Normal, accel, delay)
+    }
+
+    /// Put the accelerometer in `mode`, running with `accel`'s filter and
+    /// ODR.
+    ///
+    /// ACC_CONF is written while the accelerometer is suspended, so it never
+    /// runs with a filter that doesn't match its power mode. The range is
+    /// left untouched.
+    pub(crate) fn switch_accel_mode<D>(
+        &mut self,
+        mode: AccelPowerMode,
+        accel: AccelConfig,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.set_accel_power_mode(AccelPowerMode::Suspend, delay)?;
+        self.write_register(Register::ACC_CONF, accel.conf_bits())?;
+        delay.delay_ms(SUSPEND_WRITE_DELAY_MS);
+
+        if mode != AccelPowerMode::Suspend {
+            self.set_accel_power_mode(mode, delay)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read back the current accelerometer configuration
+    pub fn get_accel_config(&mut self) -> Result<AccelConfig, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.write_read_register(Register::ACC_CONF, &mut buffer)?;
+        let config = decode_accel_config(buffer[0], buffer[1])?;
+        self.accel_range = config.range;
+        Ok(config)
+    }
+
+    /// Read back the current gyroscope configuration
+    pub fn get_gyro_config(&mut self) -> Result<GyroConfig, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.write_read_register(Register::GYR_CONF, &mut buffer)?;
+        let config = decode_gyro_config(buffer[0], buffer[1])?;
+        self.gyro_range = config.range;
+        Ok(config)
+    }
+
+    /// Read back the accelerometer and gyroscope configuration in one burst
+    pub(crate) fn get_accel_gyro_config(&mut self) -> Result<(AccelConfig, GyroConfig), Error<E>> {
+        let mut buffer = [0u8; 4];
+        self.write_read_register(Register::ACC_CONF, &mut buffer)?;
+        let accel = decode_accel_config(buffer[0], buffer[1])?;
+        let gyro = decode_gyro_config(buffer[2], buffer[3])?;
+        self.accel_range = accel.range;
+        self.gyro_range = gyro.range;
+        Ok((accel, gyro))
+    }
+
+    /// Read the current power modes from PMU_STATUS
+    pub fn get_power_status(&mut self) -> Result<PowerStatus, Error<E>> {
+        let pmu_status = self.read_register(Register::PMU_STATUS)?;
+        let reserved = || Error::ReservedValue(Register::PMU_STATUS, pmu_status);
+
+        Ok(PowerStatus {
+            accel: AccelPowerMode::from_bits(pmu_status >> 4).ok_or_else(reserved)?,
+            gyro: GyroPowerMode::from_bits(pmu_status >> 2).ok_or_else(reserved)?,
+            mag: MagPowerMode::from_bits(pmu_status).ok_or_else(reserved)?,
+        })
+    }
+
+    /// Read back the full-device configuration.
+    ///
+    /// The interrupt setup is always read back, so the result compares equal
+    /// to the [`Bmi160Config`] passed to [`Bmi160::configure`] when it was
+    /// applied successfully and included [`Bmi160Config::interrupts`].
+    pub fn get_config(&mut self) -> Result<Bmi160Config, Error<E>> {
+        let power = self.get_power_status()?;
+
+        let mut buffer = [0u8; 5];
+        self.write_read_register(Register::ACC_CONF, &mut buffer)?;
+
+        let accel = decode_accel_config(buffer[0], buffer[1])?;
+        let gyro = decode_gyro_config(buffer[2], buffer[3])?;
+        let mag_odr = MagOdr::from_bits(buffer[4])
+            .ok_or(Error::ReservedValue(Register::MAG_CONF, buffer[4]))?;
+        self.accel_range = accel.range;
+        self.gyro_range = gyro.range;
+
+        Ok(Bmi160Config {
+            accel_power: power.accel,
+            gyro_power: power.gyro,
+            mag_power: power.mag,
+            accel,
+            gyro,
+            mag_odr,
+            fifo: self.get_fifo_config()?,
+            interrupts: Some(self.read_interrupt_config()?),
+        })
+    }
+
+    /// Apply one of the built-in application presets
+    pub fn apply_preset<D>(&mut self, preset: Preset, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.configure(&preset.config(), delay)
+    }
+}
+
+/// Decode ACC_CONF and ACC_RANGE into an [`AccelConfig`]
+fn decode_accel_config<E>(acc_conf: u8, acc_range: u8) -> Result<AccelConfig, Error<E>> {
+    let reserved_conf = || Error::ReservedValue(Register::ACC_CONF, acc_conf);
+
+    Ok(AccelConfig {
+        odr: AccelOdr::from_bits(acc_conf).ok_or_else(reserved_conf)?,
+        bandwidth: AccelBandwidth::from_bits(acc_conf).ok_or_else(reserved_conf)?,
+        range: AccelRange::from_bits(acc_range)
+            .ok_or(Error::ReservedValue(Register::ACC_RANGE, acc_range))?,
+    })
+}
+
+/// Decode GYR_CONF and GYR_RANGE into a [`GyroConfig`]
+fn decode_gyro_config<E>(gyr_conf: u8, gyr_range: u8) -> Result<GyroConfig, Error<E>> {
+    let reserved_conf = || Error::ReservedValue(Register::GYR_CONF, gyr_conf);
+
+    Ok(GyroConfig {
+        odr: GyroOdr::from_bits(gyr_conf).ok_or_else(reserved_conf)?,
+        bandwidth: GyroBandwidth::from_bits(gyr_conf >> 4).ok_or_else(reserved_conf)?,
+        range: GyroRange::from_bits(gyr_range)
+            .ok_or(Error::ReservedValue(Register::GYR_RANGE, gyr_range))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Mock, NoDelay};
+
+    fn accel(odr: AccelOdr, bandwidth: AccelBandwidth) -> AccelConfig {
+        AccelConfig {
+            odr,
+            bandwidth,
+            range: AccelRange::G2,
+        }
+    }
+
+    #[test]
+    fn slow_odr_needs_undersampling() {
+        assert_eq!(
+            accel(AccelOdr::Hz6_25, AccelBandwidth::Normal).validate(),
+            Err(ConfigError::AccelOdrRequiresUndersampling)
+        );
+        assert_eq!(
+            accel(AccelOdr::Hz6_25, AccelBandwidth::Avg1).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            accel(AccelOdr::Hz1600, AccelBandwidth::Osr4).validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn undersampling_odr_limit() {
+        assert_eq!(
+            accel(AccelOdr::Hz400, AccelBandwidth::Avg1).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            accel(AccelOdr::Hz800, AccelBandwidth::Avg1).validate(),
+            Err(ConfigError::AccelUndersamplingOdrTooHigh)
+        );
+    }
+
+    #[test]
+    fn averaging_window_limit() {
+        assert_eq!(
+            accel(AccelOdr::Hz400, AccelBandwidth::Avg4).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            accel(AccelOdr::Hz400, AccelBandwidth::Avg8).validate(),
+            Err(ConfigError::AccelAveragingTooLong)
+        );
+        assert_eq!(
+            accel(AccelOdr::Hz25, AccelBandwidth::Avg64).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            accel(AccelOdr::Hz50, AccelBandwidth::Avg64).validate(),
+            Err(ConfigError::AccelAveragingTooLong)
+        );
+    }
+
+    #[test]
+    fn filter_must_match_power_mode() {
+        let undersampled = accel(AccelOdr::Hz50, AccelBandwidth::Avg4);
+        let normal = accel(AccelOdr::Hz100, AccelBandwidth::Normal);
+
+        assert_eq!(undersampled.validate_for(AccelPowerMode::LowPower), Ok(()));
+        assert_eq!(
+            undersampled.validate_for(AccelPowerMode::Normal),
+            Err(ConfigError::AccelUndersamplingRequiresLowPower)
+        );
+        assert_eq!(
+            normal.validate_for(AccelPowerMode::LowPower),
+            Err(ConfigError::AccelLowPowerRequiresUndersampling)
+        );
+        assert_eq!(undersampled.validate_for(AccelPowerMode::Suspend), Ok(()));
+    }
+
+    #[test]
+    fn gyro_oversampling_limit() {
+        let gyro = |odr, bandwidth| GyroConfig {
+            odr,
+            bandwidth,
+            range: GyroRange::Dps2000,
+        };
+
+        assert_eq!(
+            gyro(GyroOdr::Hz3200, GyroBandwidth::Normal).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            gyro(GyroOdr::Hz1600, GyroBandwidth::Osr2).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            gyro(GyroOdr::Hz3200, GyroBandwidth::Osr2).validate(),
+            Err(ConfigError::GyroOversamplingTooHigh)
+        );
+        assert_eq!(gyro(GyroOdr::Hz800, GyroBandwidth::Osr4).validate(), Ok(()));
+        assert_eq!(
+            gyro(GyroOdr::Hz1600, GyroBandwidth::Osr4).validate(),
+            Err(ConfigError::GyroOversamplingTooHigh)
+        );
+
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        assert!(imu
+            .apply_gyro_config(gyro(GyroOdr::Hz3200, GyroBandwidth::Osr4))
+            .is_err());
+        assert!(imu.iface.writes.is_empty());
+    }
+
+    #[test]
+    fn low_power_needs_gyro_off() {
+        let config = Bmi160Config::new()
+            .accel_power_mode(AccelPowerMode::LowPower)
+            .accel(accel(AccelOdr::Hz50, AccelBandwidth::Avg4));
+
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(
+            config.gyro_power_mode(GyroPowerMode::Normal).validate(),
+            Err(ConfigError::AccelLowPowerRequiresGyroOff)
+        );
+        assert_eq!(
+            config
+                .gyro_power_mode(GyroPowerMode::FastStartUp)
+                .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn presets_validate() {
+        for preset in [
+            Preset::Drone1kHz,
+            Preset::WearableLowPower,
+            Preset::ArHeadset,
+            Preset::Pedometer,
+        ] {
+            assert_eq!(preset.config().validate(), Ok(()), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn presets_read_back() {
+        for preset in [
+            Preset::Drone1kHz,
+            Preset::WearableLowPower,
+            Preset::ArHeadset,
+            Preset::Pedometer,
+        ] {
+            let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+            imu.apply_preset(preset, &mut NoDelay).unwrap();
+            assert_eq!(imu.get_config().unwrap(), preset.config(), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn configure_low_power_keeps_acc_conf_valid() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let config = Preset::WearableLowPower.config();
+
+        // Twice, so the second run starts from low power with undersampling
+        imu.configure(&config, &mut NoDelay).unwrap();
+        imu.configure(&config, &mut NoDelay).unwrap();
+
+        let mock = &imu.iface;
+        assert!(!mock.invalid_acc_conf);
+        assert_eq!(mock.acc_pmu(), 0b10);
+        assert_eq!(mock.reg(Register::ACC_CONF), config.accel.conf_bits());
+        assert_eq!(mock.reg(Register::ACC_RANGE), config.accel.range as u8);
+    }
+
+    #[test]
+    fn configure_applies_interrupts() {
+        use crate::register::InterruptLatch;
+
+        let interrupts = InterruptConfig {
+            enabled: InterruptEnable::FIFO_WATERMARK | InterruptEnable::ANY_MOTION,
+            map: InterruptMap::new()
+                .map(InterruptSource::FifoWatermark, InterruptRoute::Int1)
+                .map(InterruptSource::AnyMotion, InterruptRoute::Int2),
+            int1: IntPinConfig::ACTIVE_HIGH_OUTPUT,
+            latch: InterruptLatch::Ms10,
+            ..InterruptConfig::default()
+        };
+        let config = Preset::ArHeadset.config().interrupts(interrupts);
+
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.configure(&config, &mut NoDelay).unwrap();
+
+        assert_eq!(
+            imu.iface.regs[Register::INT_EN as usize..][..8],
+            interrupts.bytes()
+        );
+        assert_eq!(imu.get_config().unwrap(), config);
+    }
+
+    #[test]
+    fn configure_without_interrupts_leaves_them() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let config = Bmi160Config::new()
+            .accel_power_mode(AccelPowerMode::Normal)
+            .accel(accel(AccelOdr::Hz100, AccelBandwidth::Normal));
+        imu.configure(&config, &mut NoDelay).unwrap();
+
+        assert!(imu
+            .iface
+            .writes
+            .iter()
+            .all(|&(addr, _)| !(0x50..=0x57).contains(&addr)));
+    }
+
+    #[test]
+    fn configure_normal_writes_requested_filter() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let config = Preset::Drone1kHz.config();
+
+        imu.configure(&config, &mut NoDelay).unwrap();
+
+        let mock = &imu.iface;
+        assert!(!mock.invalid_acc_conf);
+        assert_eq!(mock.acc_pmu(), 0b01);
+        assert_eq!(mock.reg(Register::ACC_CONF), config.accel.conf_bits());
+    }
+}
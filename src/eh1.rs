@@ -0,0 +1,138 @@
+//! [embedded-hal 1.0] support, behind the `embedded-hal-1` feature
+//!
+//! The driver itself is written against embedded-hal 0.2. These adapters
+//! let it run on HALs that only implement the 1.0 traits: [`I2cInterface`]
+//! and [`SpiInterface`] implement [`Interface`] for 1.0 buses, and
+//! [`Delay`] turns a 1.0 [`DelayNs`] into the 0.2 delay the blocking
+//! methods take. Shared-bus devices from `embedded-hal-bus`, such as
+//! `RefCellDevice` or `CriticalSectionDevice`, implement the 1.0 bus traits
+//! and can be passed in directly.
+//!
+//! [embedded-hal 1.0]: https://docs.rs/embedded-hal/1
+
+use crate::interface::Interface;
+use crate::spi::{SPI_DUMMY_REGISTER, SPI_READ};
+use crate::{Bmi160, Error, ADDRESS};
+use embedded_hal_1::delay::DelayNs;
+use embedded_hal_1::i2c::{I2c, Operation};
+use embedded_hal_1::spi::{Operation as SpiOperation, SpiDevice};
+use hal::blocking::delay::{DelayMs, DelayUs};
+
+/// embedded-hal 1.0 I2C bus and the device address the BMI160 answers on
+#[derive(Debug)]
+pub struct I2cInterface<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> I2cInterface<I2C> {
+    /// Talk to the BMI160 at the given 7-bit `address`
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        I2cInterface { i2c, address }
+    }
+}
+
+impl<I2C> Interface for I2cInterface<I2C>
+where
+    I2C: I2c,
+{
+    type Error = I2C::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.i2c.write(self.address, bytes)
+    }
+
+    fn write_registers(&mut self, register: u8, data: &[u8]) -> Result<(), Self::Error> {
+        self.i2c.transaction(
+            self.address,
+            &mut [Operation::Write(&[register]), Operation::Write(data)],
+        )
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.i2c.write_read(self.address, &[register], buffer)
+    }
+}
+
+/// embedded-hal 1.0 SPI device. Chip select is handled by the
+/// [`SpiDevice`] implementation; the read flag and the switch to SPI mode
+/// are handled here.
+#[derive(Debug)]
+pub struct SpiInterface<SPI> {
+    spi: SPI,
+}
+
+impl<SPI> SpiInterface<SPI>
+where
+    SPI: SpiDevice,
+{
+    /// Take the device and switch the BMI160 to 4-wire SPI mode with a
+    /// dummy read, whose rising edge of CSB at the end does the switch
+    pub fn new(spi: SPI) -> Result<Self, SPI::Error> {
+        let mut interface = SpiInterface { spi };
+        interface.read(SPI_DUMMY_REGISTER, &mut [0u8])?;
+        Ok(interface)
+    }
+}
+
+impl<SPI> Interface for SpiInterface<SPI>
+where
+    SPI: SpiDevice,
+{
+    type Error = SPI::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.spi.write(bytes)
+    }
+
+    fn write_registers(&mut self, register: u8, data: &[u8]) -> Result<(), Self::Error> {
+        self.spi
+            .transaction(&mut [SpiOperation::Write(&[register]), SpiOperation::Write(data)])
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.spi.transaction(&mut [
+            SpiOperation::Write(&[register | SPI_READ]),
+            SpiOperation::Read(buffer),
+        ])
+    }
+}
+
+/// Adapter implementing the embedded-hal 0.2 delay traits on top of an
+/// embedded-hal 1.0 [`DelayNs`]
+#[derive(Debug)]
+pub struct Delay<D>(pub D);
+
+impl<D: DelayNs> DelayMs<u8> for Delay<D> {
+    fn delay_ms(&mut self, ms: u8) {
+        self.0.delay_ms(u32::from(ms));
+    }
+}
+
+impl<D: DelayNs> DelayUs<u8> for Delay<D> {
+    fn delay_us(&mut self, us: u8) {
+        self.0.delay_us(u32::from(us));
+    }
+}
+
+impl<I2C> Bmi160<I2cInterface<I2C>>
+where
+    I2C: I2c,
+{
+    /// Create a new BMI160 driver from an embedded-hal 1.0 I2C peripheral,
+    /// at [`ADDRESS`]
+    pub fn new_eh1(i2c: I2C) -> Result<Self, Error<I2C::Error>> {
+        Self::from_interface(I2cInterface::new(i2c, ADDRESS))
+    }
+}
+
+impl<SPI> Bmi160<SpiInterface<SPI>>
+where
+    SPI: SpiDevice,
+{
+    /// Create a new BMI160 driver on an embedded-hal 1.0 4-wire SPI device,
+    /// see [`SpiInterface::new`]
+    pub fn new_spi_eh1(spi: SPI) -> Result<Self, Error<SPI::Error>> {
+        Self::from_interface(SpiInterface::new(spi).map_err(Error::Bus)?)
+    }
+}
@@ -0,0 +1,235 @@
+//! Decoded interrupt events
+
+use crate::interface::Interface;
+use crate::interrupt::{Axis, Direction, InterruptStatus};
+use crate::lowhigh::HighGEvent;
+use crate::orientation::{Face, FlatEvent, Orientation};
+use crate::register::InterruptFlags;
+use crate::tap::TapEvent;
+use crate::{Bmi160, Error};
+
+/// A typed interrupt event
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// Step detected
+    Step,
+
+    /// Significant motion detected
+    SignificantMotion,
+
+    /// Any-motion detected
+    AnyMotion {
+        /// Axis that triggered, `None` if the engine didn't flag one
+        axis: Option<Axis>,
+
+        /// Direction of the slope on `axis`
+        direction: Direction,
+    },
+
+    /// PMU trigger (gyroscope wakeup)
+    PmuTrigger,
+
+    /// Single or double tap
+    Tap(TapEvent),
+
+    /// Orientation changed
+    Orientation(Orientation, Face),
+
+    /// Flat state changed
+    Flat(FlatEvent),
+
+    /// High-g detected
+    HighG(HighGEvent),
+
+    /// Low-g (free fall) detected
+    LowG,
+
+    /// New sensor data ready
+    DataReady,
+
+    /// New magnetometer data ready. Follows [`Event::DataReady`] when
+    /// STATUS `drdy_mag` is set.
+    MagDataReady,
+
+    /// FIFO full
+    FifoFull,
+
+    /// FIFO watermark reached
+    FifoWatermark,
+
+    /// No-motion / slow-motion detected
+    NoMotion,
+}
+
+/// Iterator over the events in an [`InterruptStatus`], in INT_STATUS bit
+/// order
+#[derive(Clone, Debug)]
+pub struct Events {
+    status: InterruptStatus,
+    pending: InterruptFlags,
+    mag_pending: bool,
+}
+
+impl Events {
+    /// Events flagged in `status`
+    pub fn new(status: InterruptStatus) -> Self {
+        Events {
+            status,
+            pending: status.fired,
+            mag_pending: status.mag_data_ready(),
+        }
+    }
+
+    /// The decoded INT_STATUS the events come from
+    pub fn status(&self) -> &InterruptStatus {
+        &self.status
+    }
+}
+
+impl Iterator for Events {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        if self.mag_pending && !self.pending.contains(InterruptFlags::DATA_READY) {
+            self.mag_pending = false;
+            return Some(Event::MagDataReady);
+        }
+
+        while !self.pending.is_empty() {
+            let bits = self.pending.bits();
+            let flag = InterruptFlags::from_bits_truncate(bits & bits.wrapping_neg());
+            self.pending.remove(flag);
+
+            let status = &self.status;
+            let event = match flag {
+                InterruptFlags::STEP => Event::Step,
+                InterruptFlags::SIGNIFICANT_MOTION => Event::SignificantMotion,
+                InterruptFlags::ANY_MOTION => Event::AnyMotion {
+                    axis: status.any_motion.axis(),
+                    direction: status.any_motion.direction(),
+                },
+                InterruptFlags::PMU_TRIGGER => Event::PmuTrigger,
+                InterruptFlags::DOUBLE_TAP | InterruptFlags::SINGLE_TAP => Event::Tap(TapEvent {
+                    axis: status.tap.axis(),
+                    direction: status.tap.direction(),
+                    double: flag == InterruptFlags::DOUBLE_TAP,
+                }),
+                InterruptFlags::ORIENTATION => Event::Orientation(status.orientation, status.face),
+                InterruptFlags::FLAT => Event::Flat(if status.flat {
+                    FlatEvent::Flat
+                } else {
+                    FlatEvent::NotFlat
+                }),
+                InterruptFlags::HIGH_G => Event::HighG(HighGEvent {
+                    axis: status.high_g.axis(),
+                    direction: status.high_g.direction(),
+                }),
+                InterruptFlags::LOW_G => Event::LowG,
+                InterruptFlags::DATA_READY => Event::DataReady,
+                InterruptFlags::FIFO_FULL => Event::FifoFull,
+                InterruptFlags::FIFO_WATERMARK => Event::FifoWatermark,
+                InterruptFlags::NO_MOTION => Event::NoMotion,
+                _ => continue,
+            };
+
+            return Some(event);
+        }
+
+        None
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Read INT_STATUS once and iterate over every event it reports.
+    ///
+    /// Intended as the single call an interrupt handler needs. With latched
+    /// interrupts, follow up with [`Bmi160::clear_latched_interrupts`].
+    pub fn poll_events(&mut self) -> Result<Events, Error<E>> {
+        Ok(Events::new(self.get_interrupt_status()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::register::StatusFlags;
+    use std::vec::Vec;
+
+    fn events(status: u8, int_status: [u8; 4]) -> Vec<Event> {
+        Events::new(InterruptStatus::from_bytes(status, int_status)).collect()
+    }
+
+    #[test]
+    fn events_in_bit_order_with_mag_after_data_ready() {
+        // INT_STATUS_0 `step_int`; INT_STATUS_1 `highg_int`, `drdy_int`,
+        // `fwm_int` and `nomo_int`; INT_STATUS_3 `high_first_z`
+        let events = events(
+            StatusFlags::DRDY_MAG.bits(),
+            [0b0000_0001, 0b1101_0100, 0, 0b0000_0100],
+        );
+
+        assert_eq!(
+            events,
+            [
+                Event::Step,
+                Event::HighG(HighGEvent {
+                    axis: Some(Axis::Z),
+                    direction: Direction::Positive,
+                }),
+                Event::DataReady,
+                Event::MagDataReady,
+                Event::FifoWatermark,
+                Event::NoMotion,
+            ]
+        );
+    }
+
+    #[test]
+    fn mag_data_ready_as_last_event() {
+        let events = events(
+            StatusFlags::DRDY_MAG.bits(),
+            [0b0010_0000, 0b0001_0000, 0, 0],
+        );
+
+        assert_eq!(
+            events,
+            [
+                Event::Tap(TapEvent {
+                    axis: None,
+                    direction: Direction::Positive,
+                    double: false,
+                }),
+                Event::DataReady,
+                Event::MagDataReady,
+            ]
+        );
+    }
+
+    #[test]
+    fn no_mag_data_ready_without_data_ready() {
+        let events = events(
+            StatusFlags::DRDY_MAG.bits(),
+            [0b0000_0100, 0b1000_0000, 0, 0],
+        );
+
+        assert_eq!(
+            events,
+            [
+                Event::AnyMotion {
+                    axis: None,
+                    direction: Direction::Positive,
+                },
+                Event::NoMotion,
+            ]
+        );
+    }
+
+    #[test]
+    fn data_ready_without_mag() {
+        assert_eq!(events(0, [0, 0b0001_0000, 0, 0]), [Event::DataReady]);
+        assert_eq!(events(0, [0; 4]), []);
+    }
+}
@@ -0,0 +1,1039 @@
+//! FIFO configuration
+
+use crate::fifo_frames::{FifoData, FifoFrame, FifoFrameRefs, FifoFrames, HeaderlessFrames};
+use crate::interface::Interface;
+use crate::interrupt::{InterruptPin, PinLevel};
+use crate::register::{Cmd, FifoConfigFlags, InterruptEnable, Register};
+use crate::{Bmi160, ConfigError, Error};
+use hal::blocking::delay::DelayMs;
+use hal::digital::v2::InputPin;
+
+/// FIFO capacity in bytes
+pub const FIFO_SIZE: usize = 1024;
+
+/// Largest watermark that can be configured, in bytes
+pub const FIFO_MAX_WATERMARK: u16 = 1020;
+
+/// Largest possible FIFO frame: header, magnetometer, gyroscope and
+/// accelerometer data
+pub const FIFO_MAX_FRAME_LEN: usize = 1 + FIFO_MAG_LEN + 2 * FIFO_XYZ_LEN;
+
+/// Size of the stack buffer used by [`Bmi160::drain_fifo`]
+const DRAIN_BUFFER_SIZE: usize = 256;
+
+/// INT_EN_0 to INT_MAP_2: interrupt enables, pin setup and mapping
+const INT_SETUP_LEN: usize = 8;
+
+/// Valid bits of FIFO_LENGTH; the upper 5 bits are reserved
+const FIFO_LENGTH_MASK: u16 = 0x07FF;
+
+/// Size of an accelerometer or gyroscope payload in a FIFO frame
+pub(crate) const FIFO_XYZ_LEN: usize = 6;
+
+/// Size of a magnetometer payload (XYZ and RHALL) in a FIFO frame
+pub(crate) const FIFO_MAG_LEN: usize = 8;
+
+/// Running FIFO counters, kept by the driver across reads.
+///
+/// Handy for tuning watermark levels and spotting throughput problems in
+/// the field. See [`Bmi160::fifo_stats`] and [`Bmi160::reset_fifo_stats`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FifoStats {
+    /// Bytes read from FIFO_DATA
+    pub bytes_read: u32,
+
+    /// Frames carrying sensor data
+    pub data_frames: u32,
+
+    /// Sensortime frames
+    pub sensortime_frames: u32,
+
+    /// Skip frames, each reporting at least one frame lost to overflow
+    pub skip_frames: u32,
+
+    /// Configuration change frames
+    pub config_change_frames: u32,
+
+    /// Reads that returned data which couldn't be parsed as any frame
+    pub parse_errors: u32,
+
+    /// Data frames read by a batch capture such as
+    /// [`Bmi160::capture_batch`] after its storage was already full
+    pub dropped_frames: u32,
+}
+
+impl FifoStats {
+    fn record(&mut self, frame: &FifoFrame) {
+        let counter = match frame {
+            FifoFrame::Data(_) => &mut self.data_frames,
+            FifoFrame::SensorTime(_) => &mut self.sensortime_frames,
+            FifoFrame::Skipped(_) => &mut self.skip_frames,
+            FifoFrame::ConfigChange(_) => &mut self.config_change_frames,
+        };
+        *counter = counter.wrapping_add(1);
+    }
+}
+
+/// FIFO frame format
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FifoMode {
+    /// Every frame starts with a header byte describing its contents (default)
+    #[default]
+    Header,
+
+    /// Frames carry data only; all enabled sources must share the same ODR
+    Headerless,
+}
+
+/// FIFO downsampling ratio: only every Nth sample is stored in the FIFO
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum DownsamplingRatio {
+    /// Store every sample (default)
+    #[default]
+    X1 = 0,
+
+    /// Store every 2nd sample
+    X2 = 1,
+
+    /// Store every 4th sample
+    X4 = 2,
+
+    /// Store every 8th sample
+    X8 = 3,
+
+    /// Store every 16th sample
+    X16 = 4,
+
+    /// Store every 32nd sample
+    X32 = 5,
+
+    /// Store every 64th sample
+    X64 = 6,
+
+    /// Store every 128th sample
+    X128 = 7,
+}
+
+impl DownsamplingRatio {
+    /// Decode a 3-bit downsampling field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b111 {
+            0 => DownsamplingRatio::X1,
+            1 => DownsamplingRatio::X2,
+            2 => DownsamplingRatio::X4,
+            3 => DownsamplingRatio::X8,
+            4 => DownsamplingRatio::X16,
+            5 => DownsamplingRatio::X32,
+            6 => DownsamplingRatio::X64,
+            _ => DownsamplingRatio::X128,
+        }
+    }
+
+    /// Number of sensor samples per stored sample
+    pub fn factor(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// FIFO downsampling configuration, covering FIFO_DOWNS (0x45).
+///
+/// Lets the FIFO be filled at a fraction of the sensor ODR without any
+/// host-side decimation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FifoDownsampling {
+    /// Accelerometer downsampling ratio (`acc_fifo_downs`)
+    pub accel: DownsamplingRatio,
+
+    /// Store filtered (`true`) or pre-filtered accelerometer data
+    /// (`acc_fifo_filt_data`)
+    pub accel_filtered: bool,
+
+    /// Gyroscope downsampling ratio (`gyr_fifo_downs`)
+    pub gyro: DownsamplingRatio,
+
+    /// Store filtered (`true`) or pre-filtered gyroscope data
+    /// (`gyr_fifo_filt_data`)
+    pub gyro_filtered: bool,
+}
+
+impl FifoDownsampling {
+    /// Value of the FIFO_DOWNS register for this configuration
+    pub(crate) fn bits(&self) -> u8 {
+        (self.accel_filtered as u8) << 7
+            | (self.accel as u8) << 4
+            | (self.gyro_filtered as u8) << 3
+            | self.gyro as u8
+    }
+
+    /// Decode the FIFO_DOWNS register
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        FifoDownsampling {
+            accel: DownsamplingRatio::from_bits(bits >> 4),
+            accel_filtered: bits & 0x80 != 0,
+            gyro: DownsamplingRatio::from_bits(bits),
+            gyro_filtered: bits & 0x08 != 0,
+        }
+    }
+}
+
+impl Default for FifoDownsampling {
+    /// Power-on reset configuration: no downsampling, filtered data
+    fn default() -> Self {
+        FifoDownsampling {
+            accel: DownsamplingRatio::X1,
+            accel_filtered: true,
+            gyro: DownsamplingRatio::X1,
+            gyro_filtered: true,
+        }
+    }
+}
+
+/// FIFO configuration, covering FIFO_DOWNS (0x45) and FIFO_CONFIG_1 (0x47).
+///
+/// The default matches the power-on reset state: header mode with no data
+/// sources enabled, no downsampling and filtered data.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FifoConfig {
+    /// Store accelerometer data
+    pub accel: bool,
+
+    /// Store gyroscope data
+    pub gyro: bool,
+
+    /// Store magnetometer data
+    pub mag: bool,
+
+    /// Frame format
+    pub mode: FifoMode,
+
+    /// Return a sensortime frame after the last valid data frame
+    /// (`fifo_time_en`)
+    pub sensortime: bool,
+
+    /// Tag INT1 pin events into the FIFO (`fifo_tag_int1_en`). The pin must
+    /// be configured as an input. Tags are only available in header mode.
+    pub tag_int1: bool,
+
+    /// Tag INT2 pin events into the FIFO (`fifo_tag_int2_en`). The pin must
+    /// be configured as an input. Tags are only available in header mode.
+    pub tag_int2: bool,
+
+    /// Downsampling and filtered/pre-filtered data selection.
+    ///
+    /// Pre-filtered data bypasses the digital low-pass filter, keeping
+    /// content above the filter cutoff (useful for vibration analysis) at
+    /// the cost of aliasing.
+    pub downsampling: FifoDownsampling,
+}
+
+impl FifoConfig {
+    /// Size in bytes of a data frame carrying every enabled source,
+    /// including the header byte in header mode. Returns 0 if no data
+    /// sources are enabled.
+    pub fn frame_size(&self) -> usize {
+        let payload = self.accel as usize * FIFO_XYZ_LEN
+            + self.gyro as usize * FIFO_XYZ_LEN
+            + self.mag as usize * FIFO_MAG_LEN;
+
+        match (payload, self.mode) {
+            (0, _) => 0,
+            (_, FifoMode::Header) => payload + 1,
+            (_, FifoMode::Headerless) => payload,
+        }
+    }
+
+    /// Value of the FIFO_CONFIG_1 register for this configuration
+    pub(crate) fn flags(&self) -> FifoConfigFlags {
+        let mut flags = FifoConfigFlags::empty();
+        flags.set(FifoConfigFlags::ACC_EN, self.accel);
+        flags.set(FifoConfigFlags::GYR_EN, self.gyro);
+        flags.set(FifoConfigFlags::MAG_EN, self.mag);
+        flags.set(FifoConfigFlags::HEADER_EN, self.mode == FifoMode::Header);
+        flags.set(FifoConfigFlags::TIME_EN, self.sensortime);
+        flags.set(FifoConfigFlags::TAG_INT1_EN, self.tag_int1);
+        flags.set(FifoConfigFlags::TAG_INT2_EN, self.tag_int2);
+        flags
+    }
+
+    /// Decode the FIFO_DOWNS and FIFO_CONFIG_1 registers
+    pub(crate) fn from_registers(fifo_downs: u8, flags: FifoConfigFlags) -> Self {
+        FifoConfig {
+            accel: flags.contains(FifoConfigFlags::ACC_EN),
+            gyro: flags.contains(FifoConfigFlags::GYR_EN),
+            mag: flags.contains(FifoConfigFlags::MAG_EN),
+            mode: if flags.contains(FifoConfigFlags::HEADER_EN) {
+                FifoMode::Header
+            } else {
+                FifoMode::Headerless
+            },
+            sensortime: flags.contains(FifoConfigFlags::TIME_EN),
+            tag_int1: flags.contains(FifoConfigFlags::TAG_INT1_EN),
+            tag_int2: flags.contains(FifoConfigFlags::TAG_INT2_EN),
+            downsampling: FifoDownsampling::from_bits(fifo_downs),
+        }
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Apply the given FIFO configuration
+    pub fn apply_fifo_config(&mut self, config: FifoConfig) -> Result<(), Error<E>> {
+        self.set_fifo_downsampling(config.downsampling)?;
+        self.write_register(Register::FIFO_CONFIG_1, config.flags().bits())
+    }
+
+    /// Read back the current FIFO configuration
+    pub fn get_fifo_config(&mut self) -> Result<FifoConfig, Error<E>> {
+        let fifo_downs = self.read_register(Register::FIFO_DOWNS)?;
+        let flags =
+            FifoConfigFlags::from_bits_truncate(self.read_register(Register::FIFO_CONFIG_1)?);
+        Ok(FifoConfig::from_registers(fifo_downs, flags))
+    }
+
+    /// Set the FIFO watermark level in bytes.
+    ///
+    /// The hardware counts the watermark in units of 4 bytes, so `bytes` is
+    /// rounded up to the next multiple of 4. The maximum is 1020 bytes.
+    pub fn set_fifo_watermark_bytes(&mut self, bytes: u16) -> Result<(), Error<E>> {
+        if bytes > FIFO_MAX_WATERMARK {
+            return Err(ConfigError::FifoWatermarkTooLarge.into());
+        }
+
+        self.write_register(Register::FIFO_CONFIG, bytes.div_ceil(4) as u8)
+    }
+
+    /// Get the FIFO watermark level in bytes
+    pub fn get_fifo_watermark_bytes(&mut self) -> Result<u16, Error<E>> {
+        Ok(u16::from(self.read_register(Register::FIFO_CONFIG)?) * 4)
+    }
+
+    /// Set the FIFO watermark to the given number of frames.
+    ///
+    /// The frame size is computed from the sources currently enabled in
+    /// FIFO_CONFIG_1, so configure the FIFO before calling this. In header
+    /// mode with sources at different ODRs not every frame carries every
+    /// source; the watermark then corresponds to at least `frames` frames.
+    pub fn set_fifo_watermark_frames(&mut self, frames: u16) -> Result<(), Error<E>> {
+        let frame_size = self.get_fifo_config()?.frame_size();
+
+        if frame_size == 0 {
+            return Err(ConfigError::FifoNoSources.into());
+        }
+
+        let bytes = usize::from(frames) * frame_size;
+
+        if bytes > usize::from(FIFO_MAX_WATERMARK) {
+            return Err(ConfigError::FifoWatermarkTooLarge.into());
+        }
+
+        self.set_fifo_watermark_bytes(bytes as u16)
+    }
+
+    /// Read the FIFO fill level in bytes (FIFO_LENGTH `fifo_byte_counter`)
+    pub fn read_fifo_length(&mut self) -> Result<u16, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.write_read_register(Register::FIFO_LENGTH, &mut buffer)?;
+        Ok(u16::from_le_bytes(buffer) & FIFO_LENGTH_MASK)
+    }
+
+    /// Read buffered FIFO data into `buffer`.
+    ///
+    /// Reads `min(fifo_length, buffer.len())` bytes from FIFO_DATA and
+    /// returns how many bytes of `buffer` now hold valid FIFO data.
+    ///
+    /// Reads longer than [`Bmi160::max_transfer_len`] are split into
+    /// several bursts. The device re-sends a frame that was only partially read, so
+    /// each burst after the first starts at the last complete frame of the
+    /// previous one; the result is the same as a single long read.
+    pub fn read_fifo(&mut self, buffer: &mut [u8]) -> Result<usize, Error<E>> {
+        let len = usize::from(self.read_fifo_length()?).min(buffer.len());
+        let chunk_size = self.max_transfer_len;
+
+        if len <= chunk_size {
+            if len > 0 {
+                self.write_read_register(Register::FIFO_DATA, &mut buffer[..len])?;
+                self.fifo_stats.bytes_read = self.fifo_stats.bytes_read.wrapping_add(len as u32);
+            }
+            return Ok(len);
+        }
+
+        let config = self.get_fifo_config()?;
+        let mut offset = 0;
+
+        while offset < len {
+            let end = len.min(offset + chunk_size);
+            self.write_read_register(Register::FIFO_DATA, &mut buffer[offset..end])?;
+
+            self.fifo_stats.bytes_read = self
+                .fifo_stats
+                .bytes_read
+                .wrapping_add((end - offset) as u32);
+
+            if end == len {
+                return Ok(len);
+            }
+
+            let complete = complete_frames_len(&buffer[offset..end], config);
+            if complete == 0 {
+                // Nothing parseable (over-read or garbage); hand back what we have
+                return Ok(end);
+            }
+            offset += complete;
+        }
+
+        Ok(offset)
+    }
+
+    /// Discard all data in the FIFO (`fifo_flush` command).
+    ///
+    /// FIFO_CONFIG and FIFO_DOWNS are left untouched. Flush whenever FIFO
+    /// sources are reconfigured at runtime: in headerless mode frames carry
+    /// no header, so data written under the old configuration can't be told
+    /// apart from new data, and the first sensortime frame after a flush is
+    /// the only reliable point to resynchronise host timestamps.
+    pub fn flush_fifo(&mut self) -> Result<(), Error<E>> {
+        self.send_command(Cmd::FIFO_FLUSH)
+    }
+
+    /// Apply the given FIFO downsampling configuration (FIFO_DOWNS)
+    pub fn set_fifo_downsampling(&mut self, config: FifoDownsampling) -> Result<(), Error<E>> {
+        self.write_register(Register::FIFO_DOWNS, config.bits())
+    }
+
+    /// Read back the current FIFO downsampling configuration (FIFO_DOWNS)
+    pub fn get_fifo_downsampling(&mut self) -> Result<FifoDownsampling, Error<E>> {
+        self.read_register(Register::FIFO_DOWNS)
+            .map(FifoDownsampling::from_bits)
+    }
+
+    /// Read and parse everything currently in the FIFO, calling `f` for
+    /// every frame. Returns the number of frames passed to `f`.
+    ///
+    /// Data is read in bursts of up to 256 bytes through a stack buffer. A
+    /// frame cut off at the end of a burst isn't lost: the BMI160 delivers a
+    /// partially read frame again in full on the next read, so the trailing
+    /// bytes are simply dropped. In headerless mode samples are passed as
+    /// [`FifoFrame::Data`].
+    ///
+    /// On a [`FifoFrame::ConfigChange`] the accelerometer and gyroscope
+    /// configuration is read back before the frame is passed on, so it
+    /// carries the new ranges and [`Bmi160::accel_range`] and
+    /// [`Bmi160::gyro_range`] match the frames that follow.
+    pub fn drain_fifo<F>(&mut self, f: F) -> Result<usize, Error<E>>
+    where
+        F: FnMut(FifoFrame),
+    {
+        self.drain_fifo_up_to(usize::MAX, f)
+    }
+
+    /// [`Bmi160::drain_fifo`] stopping after `max_data` data frames.
+    ///
+    /// Each burst is limited to the size of the data frames still wanted,
+    /// so later frames stay in the FIFO. Only header mode with sources at
+    /// different ODRs can fit more data frames into a burst; those are
+    /// counted in [`FifoStats::dropped_frames`] instead of passed to `f`.
+    fn drain_fifo_up_to<F>(&mut self, max_data: usize, mut f: F) -> Result<usize, Error<E>>
+    where
+        F: FnMut(FifoFrame),
+    {
+        let config = self.get_fifo_config()?;
+        let frame_size = config.frame_size().max(1);
+        let mut buffer = [0u8; DRAIN_BUFFER_SIZE];
+        let mut count = 0;
+        let mut data_frames = 0;
+
+        while data_frames < max_data {
+            let burst = (max_data - data_frames)
+                .saturating_mul(frame_size)
+                .min(buffer.len());
+            let len = self.read_fifo(&mut buffer[..burst])?;
+            let data = &buffer[..len];
+
+            let mut handle = |imu: &mut Self, mut frame: FifoFrame| -> Result<(), Error<E>> {
+                let stats = &mut imu.fifo_stats;
+                match &mut frame {
+                    FifoFrame::Data(_) if data_frames == max_data => {
+                        stats.dropped_frames = stats.dropped_frames.wrapping_add(1);
+                        return Ok(());
+                    }
+                    FifoFrame::Data(_) => data_frames += 1,
+                    // Later frames use the new ranges; pick them up before
+                    // the caller scales any of them
+                    FifoFrame::ConfigChange(update) => {
+                        update.config = Some(imu.get_accel_gyro_config()?);
+                    }
+                    _ => {}
+                }
+
+                imu.fifo_stats.record(&frame);
+                f(frame);
+                count += 1;
+                Ok(())
+            };
+
+            let (parsed, end_of_data) = match config.mode {
+                FifoMode::Header => {
+                    let mut frames = FifoFrames::new(data);
+                    for frame in &mut frames {
+                        handle(self, frame)?;
+                    }
+                    (len - frames.remaining().len(), frames.end_of_data())
+                }
+                FifoMode::Headerless => {
+                    let mut frames = HeaderlessFrames::new(data, config);
+                    for data in &mut frames {
+                        handle(self, FifoFrame::Data(data))?;
+                    }
+                    (len - frames.remaining().len(), frames.end_of_data())
+                }
+            };
+
+            // A full frame's worth of bytes that didn't parse is corrupt data,
+            // not just a frame cut off at the end of the burst
+            let stats = &mut self.fifo_stats;
+            if parsed == 0 && !end_of_data && len >= frame_size {
+                stats.parse_errors = stats.parse_errors.wrapping_add(1);
+            }
+
+            // Stop once the FIFO fit in one burst, or nothing could be parsed
+            if len < burst || parsed == 0 {
+                break;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// FIFO counters accumulated since creation or the last
+    /// [`Bmi160::reset_fifo_stats`]
+    pub fn fifo_stats(&self) -> FifoStats {
+        self.fifo_stats
+    }
+
+    /// Reset all FIFO counters to zero
+    pub fn reset_fifo_stats(&mut self) {
+        self.fifo_stats = FifoStats::default();
+    }
+
+    /// Capture a batch of `storage.len()` FIFO samples.
+    ///
+    /// Applies `config`, flushes the FIFO, then polls FIFO_LENGTH once per
+    /// millisecond until enough data for the whole batch is buffered (or
+    /// `timeout_ms` elapses) and drains it into `storage`. Only data frames
+    /// are stored; control frames are dropped. Draining stops once
+    /// `storage` is full, leaving newer samples in the FIFO. Returns the
+    /// number of samples written to `storage`.
+    ///
+    /// The host is free to sleep while the FIFO fills, which makes this far
+    /// cheaper than per-sample polling for low duty-cycle loggers.
+    pub fn capture_batch<D>(
+        &mut self,
+        config: FifoConfig,
+        storage: &mut [FifoData],
+        timeout_ms: u32,
+        delay: &mut D,
+    ) -> Result<usize, Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let needed = self.start_batch(config, storage.len())?;
+        self.flush_fifo()?;
+
+        self.wait_for_batch(timeout_ms, delay, |imu| {
+            Ok(usize::from(imu.read_fifo_length()?) >= needed)
+        })?;
+
+        self.store_batch(storage)
+    }
+
+    /// [`Bmi160::capture_batch`] waiting on the FIFO watermark interrupt
+    /// instead of FIFO_LENGTH.
+    ///
+    /// Sets the watermark to `storage.len()` frames and enables the FIFO
+    /// watermark interrupt, then polls INT_STATUS `fwm` once per
+    /// millisecond. The previous watermark and interrupt setup are restored
+    /// afterwards, including when the capture fails.
+    pub fn capture_batch_on_watermark<D>(
+        &mut self,
+        config: FifoConfig,
+        storage: &mut [FifoData],
+        timeout_ms: u32,
+        delay: &mut D,
+    ) -> Result<usize, Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.start_batch(config, storage.len())?;
+
+        self.with_watermark_setup_restored(|imu| {
+            imu.set_fifo_watermark_frames(storage.len() as u16)?;
+            imu.enable_interrupts(InterruptEnable::FIFO_WATERMARK)?;
+            imu.flush_fifo()?;
+
+            imu.wait_for_batch(timeout_ms, delay, |imu| {
+                Ok(imu.get_interrupt_status()?.fifo_watermark())
+            })?;
+
+            imu.store_batch(storage)
+        })
+    }
+
+    /// [`Bmi160::capture_batch`] waiting for the FIFO watermark interrupt
+    /// on a host GPIO.
+    ///
+    /// Sets the watermark to `storage.len()` frames and routes the FIFO
+    /// watermark interrupt to `int_pin` (see
+    /// [`Bmi160::enable_fifo_watermark_interrupt`]), then polls `pin`, the
+    /// GPIO wired to it, once per millisecond at the active level
+    /// configured in INT_OUT_CTRL. The previous watermark and interrupt
+    /// setup, including the mapping, are restored afterwards, including
+    /// when the capture fails.
+    pub fn capture_batch_on_pin<P, D>(
+        &mut self,
+        config: FifoConfig,
+        storage: &mut [FifoData],
+        int_pin: InterruptPin,
+        pin: &P,
+        timeout_ms: u32,
+        delay: &mut D,
+    ) -> Result<usize, Error<E>>
+    where
+        P: InputPin,
+        D: DelayMs<u8>,
+    {
+        self.start_batch(config, storage.len())?;
+
+        self.with_watermark_setup_restored(|imu| {
+            imu.enable_fifo_watermark_interrupt(storage.len() as u16, int_pin)?;
+            let level = imu.get_int_pin_config(int_pin)?.level;
+            imu.flush_fifo()?;
+
+            imu.wait_for_batch(timeout_ms, delay, |_| {
+                match level {
+                    PinLevel::ActiveHigh => pin.is_high(),
+                    PinLevel::ActiveLow => pin.is_low(),
+                }
+                .map_err(|_| Error::Pin)
+            })?;
+
+            imu.store_batch(storage)
+        })
+    }
+
+    /// Run `f`, then put back the FIFO watermark and INT_EN to INT_MAP as
+    /// they were before. The error of `f` is returned after restoring.
+    fn with_watermark_setup_restored<F>(&mut self, f: F) -> Result<usize, Error<E>>
+    where
+        F: FnOnce(&mut Self) -> Result<usize, Error<E>>,
+    {
+        let watermark = self.read_register(Register::FIFO_CONFIG)?;
+        let mut interrupts = [0u8; INT_SETUP_LEN];
+        self.write_read_register(Register::INT_EN, &mut interrupts)?;
+
+        let result = f(self);
+
+        // Single writes, which the accelerometer accepts in any power mode
+        self.write_register(Register::FIFO_CONFIG, watermark)?;
+        for (i, &value) in interrupts.iter().enumerate() {
+            let register = Register::INT_EN.addr() + i as u8;
+            self.iface.write(&[register, value]).map_err(Error::Bus)?;
+        }
+
+        result
+    }
+
+    /// Check that `samples` frames fit in the FIFO and apply `config`.
+    /// Returns the size of the batch in bytes.
+    fn start_batch(&mut self, config: FifoConfig, samples: usize) -> Result<usize, Error<E>> {
+        let frame_size = config.frame_size();
+
+        if frame_size == 0 {
+            return Err(ConfigError::FifoNoSources.into());
+        }
+
+        let needed = samples * frame_size;
+
+        if needed > FIFO_SIZE {
+            return Err(ConfigError::FifoBatchTooLarge.into());
+        }
+
+        self.apply_fifo_config(config)?;
+        Ok(needed)
+    }
+
+    /// Call `ready` once per millisecond until it returns `true`, or fail
+    /// with [`Error::Timeout`] after `timeout_ms`
+    fn wait_for_batch<D, F>(
+        &mut self,
+        timeout_ms: u32,
+        delay: &mut D,
+        mut ready: F,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+        F: FnMut(&mut Self) -> Result<bool, Error<E>>,
+    {
+        let mut waited = 0;
+
+        while !ready(self)? {
+            if waited >= timeout_ms {
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_ms(1);
+            waited += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Drain data frames into `storage`, stopping once it's full
+    fn store_batch(&mut self, storage: &mut [FifoData]) -> Result<usize, Error<E>> {
+        let mut stored = 0;
+
+        self.drain_fifo_up_to(storage.len(), |frame| {
+            if let FifoFrame::Data(data) = frame {
+                storage[stored] = data;
+                stored += 1;
+            }
+        })?;
+
+        Ok(stored)
+    }
+}
+
+/// Number of leading bytes of `data` that form complete frames
+fn complete_frames_len(data: &[u8], config: FifoConfig) -> usize {
+    let remaining = match config.mode {
+        FifoMode::Header => {
+            let mut frames = FifoFrameRefs::new(data);
+            frames.by_ref().for_each(drop);
+            frames.remaining().len()
+        }
+        FifoMode::Headerless => {
+            let mut frames = HeaderlessFrames::new(data, config);
+            frames.by_ref().for_each(drop);
+            frames.remaining().len()
+        }
+    };
+
+    data.len() - remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupt::{IntPinConfig, InterruptRoute, InterruptSource};
+    use crate::mock::{Mock, NoDelay, Pin};
+    use crate::register::{AccelRange, FifoConfigChange};
+    use crate::DataXYZRaw;
+    use std::vec::Vec;
+
+    /// Headerless accelerometer and gyroscope FIFO holding `frames` frames
+    fn headerless_fifo(frames: u8) -> (Bmi160<Mock>, Vec<u8>) {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let config = FifoConfig {
+            accel: true,
+            gyro: true,
+            mode: FifoMode::Headerless,
+            ..FifoConfig::default()
+        };
+        imu.apply_fifo_config(config).unwrap();
+
+        let data: Vec<u8> = (0..frames)
+            .flat_map(|frame| (0..12).map(move |byte| frame * 12 + byte))
+            .collect();
+        imu.iface.fifo = data.clone();
+        imu.iface.fifo_frame_len = config.frame_size();
+        (imu, data)
+    }
+
+    fn fifo_reads(imu: &Bmi160<Mock>) -> Vec<usize> {
+        imu.iface
+            .reads
+            .iter()
+            .filter(|&&(register, _)| register == Register::FIFO_DATA.addr())
+            .map(|&(_, len)| len)
+            .collect()
+    }
+
+    #[test]
+    fn read_fifo_single_burst() {
+        let (mut imu, data) = headerless_fifo(10);
+        let mut buffer = [0u8; 256];
+
+        assert_eq!(imu.read_fifo(&mut buffer).unwrap(), 120);
+        assert_eq!(buffer[..120], data[..]);
+        assert_eq!(fifo_reads(&imu), [120]);
+        assert!(imu.iface.fifo.is_empty());
+    }
+
+    #[test]
+    fn read_fifo_chunks_restart_at_frame_boundary() {
+        let (mut imu, data) = headerless_fifo(10);
+        imu.set_max_transfer_len(50).unwrap();
+        let mut buffer = [0u8; 256];
+
+        assert_eq!(imu.read_fifo(&mut buffer).unwrap(), 120);
+        assert_eq!(buffer[..120], data[..]);
+        // 48 + 48 bytes of complete frames, then the remaining 24
+        assert_eq!(fifo_reads(&imu), [50, 50, 24]);
+        assert!(imu.iface.fifo.is_empty());
+        assert_eq!(imu.fifo_stats().bytes_read, 124);
+    }
+
+    #[test]
+    fn read_fifo_limited_by_buffer() {
+        let (mut imu, data) = headerless_fifo(10);
+        let mut buffer = [0u8; 36];
+
+        assert_eq!(imu.read_fifo(&mut buffer).unwrap(), 36);
+        assert_eq!(buffer[..], data[..36]);
+        assert_eq!(imu.iface.fifo.len(), 84);
+    }
+
+    #[test]
+    fn transfer_limit_must_fit_a_frame() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+
+        assert!(matches!(
+            imu.set_max_transfer_len(FIFO_MAX_FRAME_LEN - 1),
+            Err(Error::InvalidConfig(ConfigError::TransferTooSmall))
+        ));
+        assert!(imu.set_max_transfer_len(FIFO_MAX_FRAME_LEN).is_ok());
+    }
+
+    const ACCEL_GYRO: FifoConfig = FifoConfig {
+        accel: true,
+        gyro: true,
+        mag: false,
+        mode: FifoMode::Headerless,
+        sensortime: false,
+        tag_int1: false,
+        tag_int2: false,
+        downsampling: FifoDownsampling {
+            accel: DownsamplingRatio::X1,
+            accel_filtered: true,
+            gyro: DownsamplingRatio::X1,
+            gyro_filtered: true,
+        },
+    };
+
+    /// Device that samples `frames` headerless accelerometer and gyroscope
+    /// frames into the FIFO once it's flushed
+    fn batch_imu(frames: u8) -> (Bmi160<Mock>, Vec<u8>) {
+        let (mut imu, data) = headerless_fifo(frames);
+        imu.iface.fifo_after_flush = core::mem::take(&mut imu.iface.fifo);
+        (imu, data)
+    }
+
+    fn frame_data(data: &[u8], frame: usize) -> FifoData {
+        let frame = &data[frame * 12..][..12];
+        FifoData {
+            gyro: Some(DataXYZRaw::from_bytes(&frame[..6])),
+            accel: Some(DataXYZRaw::from_bytes(&frame[6..])),
+            ..FifoData::default()
+        }
+    }
+
+    #[test]
+    fn capture_batch_stops_at_capacity() {
+        let (mut imu, data) = batch_imu(10);
+        let mut storage = [FifoData::default(); 4];
+
+        let stored = imu
+            .capture_batch(ACCEL_GYRO, &mut storage, 10, &mut NoDelay)
+            .unwrap();
+
+        assert_eq!(stored, 4);
+        for (i, sample) in storage.iter().enumerate() {
+            assert_eq!(*sample, frame_data(&data, i));
+        }
+        // The rest stays buffered for the next batch
+        assert_eq!(imu.iface.fifo, data[48..]);
+        assert_eq!(fifo_reads(&imu), [48]);
+        assert_eq!(imu.fifo_stats().dropped_frames, 0);
+    }
+
+    #[test]
+    fn capture_batch_times_out() {
+        let (mut imu, _) = batch_imu(3);
+        let mut storage = [FifoData::default(); 4];
+
+        assert!(matches!(
+            imu.capture_batch(ACCEL_GYRO, &mut storage, 10, &mut NoDelay),
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn capture_batch_counts_frames_past_capacity() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let config = FifoConfig {
+            mode: FifoMode::Header,
+            ..ACCEL_GYRO
+        };
+
+        // Accelerometer-only frames are smaller than the configured frame
+        // size, so a burst sized for two full frames holds three of them
+        let frames = 6;
+        let data: Vec<u8> = (0..frames)
+            .flat_map(|frame| core::iter::once(0x84).chain((0..6).map(move |b| frame * 6 + b)))
+            .collect();
+        imu.iface.fifo_after_flush = data.clone();
+        imu.iface.fifo_frame_len = 7;
+
+        let mut storage = [FifoData::default(); 2];
+        let stored = imu
+            .capture_batch(config, &mut storage, 10, &mut NoDelay)
+            .unwrap();
+
+        assert_eq!(stored, 2);
+        assert_eq!(storage[1].accel, Some(DataXYZRaw::from_bytes(&data[8..14])));
+        assert_eq!(fifo_reads(&imu), [26]);
+        assert_eq!(imu.fifo_stats().dropped_frames, 1);
+        assert_eq!(imu.iface.fifo, data[21..]);
+    }
+
+    /// INT_EN to INT_MAP and FIFO_CONFIG, as restored by the watermark
+    /// captures
+    fn watermark_setup(imu: &Bmi160<Mock>) -> Vec<u8> {
+        let mut regs = imu.iface.regs[Register::INT_EN as usize..][..INT_SETUP_LEN].to_vec();
+        regs.push(imu.iface.reg(Register::FIFO_CONFIG));
+        regs
+    }
+
+    #[test]
+    fn capture_batch_on_watermark() {
+        let (mut imu, data) = batch_imu(10);
+        imu.set_fifo_watermark_bytes(200).unwrap();
+        imu.enable_interrupts(InterruptEnable::FIFO_WATERMARK | InterruptEnable::DATA_READY)
+            .unwrap();
+        let before = watermark_setup(&imu);
+        let mut storage = [FifoData::default(); 4];
+
+        let stored = imu
+            .capture_batch_on_watermark(ACCEL_GYRO, &mut storage, 10, &mut NoDelay)
+            .unwrap();
+
+        assert_eq!(stored, 4);
+        assert_eq!(storage[3], frame_data(&data, 3));
+        // 4 frames of 12 bytes while capturing, the caller's setup after
+        assert!(imu
+            .iface
+            .writes
+            .contains(&(Register::FIFO_CONFIG.addr(), 12)));
+        assert_eq!(watermark_setup(&imu), before);
+        assert_eq!(imu.get_fifo_watermark_bytes().unwrap(), 200);
+    }
+
+    #[test]
+    fn capture_batch_on_watermark_times_out() {
+        let (mut imu, _) = batch_imu(3);
+        let before = watermark_setup(&imu);
+        let mut storage = [FifoData::default(); 4];
+
+        assert!(matches!(
+            imu.capture_batch_on_watermark(ACCEL_GYRO, &mut storage, 10, &mut NoDelay),
+            Err(Error::Timeout)
+        ));
+        assert_eq!(watermark_setup(&imu), before);
+        assert!(!imu
+            .get_enabled_interrupts()
+            .unwrap()
+            .contains(InterruptEnable::FIFO_WATERMARK));
+    }
+
+    #[test]
+    fn capture_batch_on_pin() {
+        let (mut imu, data) = batch_imu(10);
+        imu.set_int_pin_config(
+            InterruptPin::Int2,
+            IntPinConfig {
+                output_enable: false,
+                ..IntPinConfig::ACTIVE_HIGH_OUTPUT
+            },
+        )
+        .unwrap();
+        let before = watermark_setup(&imu);
+        let mut storage = [FifoData::default(); 4];
+
+        let stored = imu
+            .capture_batch_on_pin(
+                ACCEL_GYRO,
+                &mut storage,
+                InterruptPin::Int2,
+                &Pin(true),
+                10,
+                &mut NoDelay,
+            )
+            .unwrap();
+
+        assert_eq!(stored, 4);
+        assert_eq!(storage[0], frame_data(&data, 0));
+        assert_eq!(watermark_setup(&imu), before);
+        let map = imu.get_interrupt_map().unwrap();
+        assert_eq!(
+            map.route(InterruptSource::FifoWatermark),
+            InterruptRoute::None
+        );
+    }
+
+    #[test]
+    fn capture_batch_on_pin_times_out() {
+        let (mut imu, _) = batch_imu(10);
+        imu.set_int_pin_config(InterruptPin::Int1, IntPinConfig::ACTIVE_HIGH_OUTPUT)
+            .unwrap();
+        let before = watermark_setup(&imu);
+        let mut storage = [FifoData::default(); 4];
+
+        assert!(matches!(
+            imu.capture_batch_on_pin(
+                ACCEL_GYRO,
+                &mut storage,
+                InterruptPin::Int1,
+                &Pin(false),
+                10,
+                &mut NoDelay,
+            ),
+            Err(Error::Timeout)
+        ));
+        assert_eq!(watermark_setup(&imu), before);
+    }
+
+    #[test]
+    fn drain_fifo_reads_back_config_on_change() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.apply_fifo_config(FifoConfig {
+            accel: true,
+            ..FifoConfig::default()
+        })
+        .unwrap();
+        assert_eq!(imu.accel_range(), AccelRange::G2);
+
+        // The range was switched to ±16 g behind the driver's back
+        imu.iface
+            .set_reg(Register::ACC_RANGE, AccelRange::G16 as u8);
+        imu.iface.fifo = std::vec![
+            0x84, 1, 0, 2, 0, 3, 0, // data recorded at ±2 g
+            0x48, 0x01, // accelerometer configuration changed
+            0x84, 4, 0, 5, 0, 6, 0, // data recorded at ±16 g
+        ];
+
+        let mut frames = Vec::new();
+        assert_eq!(imu.drain_fifo(|frame| frames.push(frame)).unwrap(), 3);
+
+        let FifoFrame::ConfigChange(update) = frames[1] else {
+            panic!("expected a configuration change frame");
+        };
+        assert_eq!(update.changed, FifoConfigChange::ACCEL);
+        let (accel, gyro) = update.config.unwrap();
+        assert_eq!(accel.range, AccelRange::G16);
+        assert_eq!(gyro, imu.get_gyro_config().unwrap());
+        assert_eq!(imu.accel_range(), AccelRange::G16);
+        assert_eq!(imu.fifo_stats().config_change_frames, 1);
+    }
+}
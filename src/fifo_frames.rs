@@ -0,0 +1,829 @@
+//! FIFO frame parsing
+
+use crate::bmm150::Rhall;
+use crate::config::{AccelConfig, GyroConfig};
+use crate::fifo::{FifoConfig, FifoMode, FIFO_MAG_LEN, FIFO_XYZ_LEN};
+use crate::mag::SecondaryMag;
+use crate::register::{AccelRange, FifoConfigChange, GyroRange};
+use crate::{DataXYZRaw, SensorTime};
+
+/// Header `fh_mode` field
+const HEADER_MODE_MASK: u8 = 0b1100_0000;
+
+/// `fh_mode` of a regular (data) frame
+const HEADER_MODE_REGULAR: u8 = 0b1000_0000;
+
+/// `fh_mode` of a control frame
+const HEADER_MODE_CONTROL: u8 = 0b0100_0000;
+
+/// Reserved `fh_parm` bit of a regular frame
+const HEADER_PARM_RESERVED: u8 = 0b0010_0000;
+
+/// Regular frame carries magnetometer data
+const HEADER_MAG: u8 = 0b0001_0000;
+
+/// Regular frame carries gyroscope data
+const HEADER_GYR: u8 = 0b0000_1000;
+
+/// Regular frame carries accelerometer data
+const HEADER_ACC: u8 = 0b0000_0100;
+
+/// Regular frame is tagged with an INT2 event (`fh_ext`)
+const HEADER_TAG_INT2: u8 = 0b0000_0010;
+
+/// Regular frame is tagged with an INT1 event (`fh_ext`)
+const HEADER_TAG_INT1: u8 = 0b0000_0001;
+
+/// Byte returned when reading past the FIFO fill level; appears as the
+/// over-read header in header mode
+const OVER_READ: u8 = 0x80;
+
+/// Skip frame header
+const HEADER_SKIP: u8 = 0x40;
+
+/// Sensortime frame header
+const HEADER_SENSORTIME: u8 = 0x44;
+
+/// Fifo_Input_Config frame header
+const HEADER_INPUT_CONFIG: u8 = 0x48;
+
+/// Magnetometer payload of a FIFO data frame
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FifoMagData {
+    /// Magnetometer XYZ Raw Data
+    pub mag: DataXYZRaw,
+
+    /// RHALL LSB Data
+    pub rhall_lsb: u8,
+
+    /// RHALL MSB Data
+    pub rhall_msb: u8,
+}
+
+impl FifoMagData {
+    /// The 8 payload bytes as read from the magnetometer (XYZ, RHALL)
+    pub fn bytes(&self) -> [u8; 8] {
+        let mag = &self.mag;
+        [
+            mag.x_lsb,
+            mag.x_msb,
+            mag.y_lsb,
+            mag.y_msb,
+            mag.z_lsb,
+            mag.z_msb,
+            self.rhall_lsb,
+            self.rhall_msb,
+        ]
+    }
+
+    /// Decode the RHALL bytes (BMM150 layout)
+    pub fn rhall(&self) -> Rhall {
+        Rhall::from_bytes(self.rhall_lsb, self.rhall_msb)
+    }
+
+    /// Compensate to µT with the given magnetometer, `None` if the sample
+    /// is invalid
+    pub fn compensate<M>(&self, mag: &M) -> Option<[f32; 3]>
+    where
+        M: SecondaryMag,
+    {
+        mag.compensate(&self.bytes())
+    }
+}
+
+/// Sensor data carried by a single FIFO data frame.
+///
+/// In header mode each source is only present when it produced a new
+/// sample, so frames may carry any combination of sources.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FifoData {
+    /// Magnetometer data
+    pub mag: Option<FifoMagData>,
+
+    /// Gyroscope data
+    pub gyro: Option<DataXYZRaw>,
+
+    /// Accelerometer data
+    pub accel: Option<DataXYZRaw>,
+
+    /// An INT1 pin event occurred while this frame was recorded
+    /// (header mode with `tag_int1` only)
+    pub int1_tag: bool,
+
+    /// An INT2 pin event occurred while this frame was recorded
+    /// (header mode with `tag_int2` only)
+    pub int2_tag: bool,
+}
+
+impl FifoData {
+    /// Accelerometer data in g, scaled for the given range
+    pub fn accel_g(&self, range: AccelRange) -> Option<[f32; 3]> {
+        self.accel.map(|accel| accel.scaled(range.lsb_per_g()))
+    }
+
+    /// Gyroscope data in °/s, scaled for the given range
+    pub fn gyro_dps(&self, range: GyroRange) -> Option<[f32; 3]> {
+        self.gyro.map(|gyro| gyro.scaled(range.lsb_per_dps()))
+    }
+
+    /// Magnetometer data in µT, compensated with the given magnetometer.
+    /// `None` if the frame has no magnetometer data or it is invalid.
+    pub fn mag_ut<M>(&self, mag: &M) -> Option<[f32; 3]>
+    where
+        M: SecondaryMag,
+    {
+        self.mag?.compensate(mag)
+    }
+}
+
+/// A frame parsed from the FIFO, with sensor data copied out of the
+/// FIFO buffer
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FifoFrame {
+    /// Sensor data from one or more sources
+    Data(FifoData),
+
+    /// Sensortime when the last data frame was read. Only returned when
+    /// `fifo_time_en` is set, after the last data frame in the FIFO, so it
+    /// timestamps the newest sample of the batch.
+    SensorTime(SensorTime),
+
+    /// The FIFO overflowed and frames were dropped; the payload is the
+    /// number of skipped frames reported by the skip frame
+    Skipped(u8),
+
+    /// The sensor configuration changed while the FIFO was running.
+    ///
+    /// Frames before this one were recorded with the old configuration,
+    /// frames after it with the new one, so scale later data frames with
+    /// the ranges carried here.
+    ConfigChange(FifoConfigUpdate),
+}
+
+/// Payload of a [`FifoFrame::ConfigChange`] frame
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FifoConfigUpdate {
+    /// Sensors whose configuration changed
+    pub changed: FifoConfigChange,
+
+    /// Accelerometer and gyroscope configuration read back from ACC_CONF to
+    /// GYR_RANGE by [`Bmi160::drain_fifo`](crate::Bmi160::drain_fifo) when
+    /// it reached this frame. If the configuration changed again since,
+    /// this is the newest one. `None` when parsed from a caller-owned
+    /// buffer.
+    pub config: Option<(AccelConfig, GyroConfig)>,
+}
+
+/// Sensor data frame borrowing its payload from the FIFO buffer.
+///
+/// Nothing is decoded until one of the accessors is called.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FifoDataRef<'a> {
+    header: u8,
+    payload: &'a [u8],
+}
+
+impl<'a> FifoDataRef<'a> {
+    /// Raw frame header byte
+    pub fn header(&self) -> u8 {
+        self.header
+    }
+
+    /// Raw payload, laid out as mag, gyro, accel with only the sources
+    /// flagged in the header present
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Raw magnetometer bytes (XYZ and RHALL), if present
+    pub fn mag_bytes(&self) -> Option<&'a [u8]> {
+        if self.header & HEADER_MAG == 0 {
+            return None;
+        }
+
+        Some(&self.payload[..FIFO_MAG_LEN])
+    }
+
+    /// Raw gyroscope bytes, if present
+    pub fn gyro_bytes(&self) -> Option<&'a [u8]> {
+        if self.header & HEADER_GYR == 0 {
+            return None;
+        }
+
+        let start = self.mag_len();
+        Some(&self.payload[start..start + FIFO_XYZ_LEN])
+    }
+
+    /// Raw accelerometer bytes, if present
+    pub fn accel_bytes(&self) -> Option<&'a [u8]> {
+        if self.header & HEADER_ACC == 0 {
+            return None;
+        }
+
+        let start = self.mag_len() + self.gyro_len();
+        Some(&self.payload[start..start + FIFO_XYZ_LEN])
+    }
+
+    /// Decoded magnetometer data, if present
+    pub fn mag(&self) -> Option<FifoMagData> {
+        self.mag_bytes().map(|bytes| FifoMagData {
+            mag: DataXYZRaw::from_bytes(bytes),
+            rhall_lsb: bytes[6],
+            rhall_msb: bytes[7],
+        })
+    }
+
+    /// Decoded gyroscope data, if present
+    pub fn gyro(&self) -> Option<DataXYZRaw> {
+        self.gyro_bytes().map(DataXYZRaw::from_bytes)
+    }
+
+    /// Decoded accelerometer data, if present
+    pub fn accel(&self) -> Option<DataXYZRaw> {
+        self.accel_bytes().map(DataXYZRaw::from_bytes)
+    }
+
+    /// Was an INT1 pin event tagged into this frame?
+    pub fn int1_tag(&self) -> bool {
+        self.header & HEADER_TAG_INT1 != 0
+    }
+
+    /// Was an INT2 pin event tagged into this frame?
+    pub fn int2_tag(&self) -> bool {
+        self.header & HEADER_TAG_INT2 != 0
+    }
+
+    /// Decode every source into a [`FifoData`]
+    pub fn to_data(&self) -> FifoData {
+        FifoData {
+            mag: self.mag(),
+            gyro: self.gyro(),
+            accel: self.accel(),
+            int1_tag: self.int1_tag(),
+            int2_tag: self.int2_tag(),
+        }
+    }
+
+    fn mag_len(&self) -> usize {
+        (self.header & HEADER_MAG != 0) as usize * FIFO_MAG_LEN
+    }
+
+    fn gyro_len(&self) -> usize {
+        (self.header & HEADER_GYR != 0) as usize * FIFO_XYZ_LEN
+    }
+
+    fn accel_len(&self) -> usize {
+        (self.header & HEADER_ACC != 0) as usize * FIFO_XYZ_LEN
+    }
+}
+
+/// A frame parsed from the FIFO, borrowing sensor data from the FIFO
+/// buffer
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FifoFrameRef<'a> {
+    /// Sensor data from one or more sources
+    Data(FifoDataRef<'a>),
+
+    /// See [`FifoFrame::SensorTime`]
+    SensorTime(SensorTime),
+
+    /// See [`FifoFrame::Skipped`]
+    Skipped(u8),
+
+    /// See [`FifoFrame::ConfigChange`]
+    ConfigChange(FifoConfigChange),
+}
+
+impl<'a> FifoFrameRef<'a> {
+    /// Copy the frame's data out of the FIFO buffer
+    pub fn to_frame(&self) -> FifoFrame {
+        match *self {
+            FifoFrameRef::Data(data) => FifoFrame::Data(data.to_data()),
+            FifoFrameRef::SensorTime(time) => FifoFrame::SensorTime(time),
+            FifoFrameRef::Skipped(count) => FifoFrame::Skipped(count),
+            FifoFrameRef::ConfigChange(changed) => FifoFrame::ConfigChange(FifoConfigUpdate {
+                changed,
+                config: None,
+            }),
+        }
+    }
+}
+
+/// Zero-copy iterator over the frames in a header-mode FIFO dump.
+///
+/// Works on any caller-owned buffer and holds no borrow of the driver, so
+/// the FIFO can be read in one context (e.g. a DMA completion handler) and
+/// parsed in another. Stops under the same conditions as [`FifoFrames`].
+#[derive(Clone, Debug)]
+pub struct FifoFrameRefs<'a> {
+    data: &'a [u8],
+    parsed: usize,
+    skipped: u32,
+    over_read: bool,
+}
+
+impl<'a> FifoFrameRefs<'a> {
+    /// Parse frames from the given FIFO data
+    pub fn new(data: &'a [u8]) -> Self {
+        FifoFrameRefs {
+            data,
+            parsed: 0,
+            skipped: 0,
+            over_read: false,
+        }
+    }
+
+    /// Bytes that have not been parsed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Total number of frames dropped due to FIFO overflow, summed over the
+    /// skip frames parsed so far
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+
+    /// Number of valid frames parsed so far
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// Did parsing stop at the 0x80 marker the BMI160 returns when reading
+    /// past the FIFO fill level? Everything from that point on is padding,
+    /// not frame data.
+    pub fn end_of_data(&self) -> bool {
+        self.over_read
+    }
+}
+
+impl<'a> Iterator for FifoFrameRefs<'a> {
+    type Item = FifoFrameRef<'a>;
+
+    fn next(&mut self) -> Option<FifoFrameRef<'a>> {
+        let (&header, payload) = self.data.split_first()?;
+
+        if header == OVER_READ {
+            self.over_read = true;
+            return None;
+        }
+
+        let (frame, len) = match header & HEADER_MODE_MASK {
+            HEADER_MODE_REGULAR => parse_data(header, payload)?,
+            HEADER_MODE_CONTROL => parse_control(header, payload)?,
+            _ => return None,
+        };
+
+        if let FifoFrameRef::Skipped(count) = frame {
+            self.skipped += u32::from(count);
+        }
+
+        self.parsed += 1;
+        self.data = &payload[len..];
+        Some(frame)
+    }
+}
+
+/// Iterator over the frames in a header-mode FIFO dump.
+///
+/// Iteration stops at the end of the data, at a trailing partial frame, at
+/// the over-read marker, or at a header that isn't a valid frame. Any
+/// unparsed bytes are available from [`FifoFrames::remaining`].
+#[derive(Clone, Debug)]
+pub struct FifoFrames<'a> {
+    frames: FifoFrameRefs<'a>,
+}
+
+impl<'a> FifoFrames<'a> {
+    /// Parse frames from the given FIFO data
+    pub fn new(data: &'a [u8]) -> Self {
+        FifoFrames {
+            frames: FifoFrameRefs::new(data),
+        }
+    }
+
+    /// Bytes that have not been parsed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.frames.remaining()
+    }
+
+    /// Total number of frames dropped due to FIFO overflow, summed over the
+    /// skip frames parsed so far
+    pub fn skipped(&self) -> u32 {
+        self.frames.skipped()
+    }
+
+    /// Number of valid frames parsed so far
+    pub fn parsed(&self) -> usize {
+        self.frames.parsed()
+    }
+
+    /// Did parsing stop at the over-read marker? See
+    /// [`FifoFrameRefs::end_of_data`].
+    pub fn end_of_data(&self) -> bool {
+        self.frames.end_of_data()
+    }
+}
+
+impl<'a> Iterator for FifoFrames<'a> {
+    type Item = FifoFrame;
+
+    fn next(&mut self) -> Option<FifoFrame> {
+        self.frames.next().map(|frame| frame.to_frame())
+    }
+}
+
+/// Iterator over the samples in a headerless FIFO dump.
+///
+/// Headerless frames have no header byte, so the set of enabled sources
+/// (and with it the frame size) must be supplied up front. A trailing
+/// partial frame is left in [`HeaderlessFrames::remaining`].
+#[derive(Clone, Debug)]
+pub struct HeaderlessFrames<'a> {
+    data: &'a [u8],
+    config: FifoConfig,
+    parsed: usize,
+    over_read: bool,
+}
+
+impl<'a> HeaderlessFrames<'a> {
+    /// Parse frames from the given FIFO data, captured with the sources
+    /// enabled in `config`. The `mode` of `config` is ignored.
+    pub fn new(data: &'a [u8], config: FifoConfig) -> Self {
+        HeaderlessFrames {
+            data,
+            config: FifoConfig {
+                mode: FifoMode::Headerless,
+                ..config
+            },
+            parsed: 0,
+            over_read: false,
+        }
+    }
+
+    /// Bytes that have not been parsed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Number of valid frames parsed so far
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// Did parsing stop at a frame made up entirely of the 0x80 over-read
+    /// marker, returned when reading past the FIFO fill level?
+    pub fn end_of_data(&self) -> bool {
+        self.over_read
+    }
+}
+
+impl<'a> Iterator for HeaderlessFrames<'a> {
+    type Item = FifoData;
+
+    fn next(&mut self) -> Option<FifoData> {
+        let len = self.config.frame_size();
+
+        if len == 0 || self.data.len() < len {
+            return None;
+        }
+
+        let (frame, rest) = self.data.split_at(len);
+
+        if frame.iter().all(|&byte| byte == OVER_READ) {
+            self.over_read = true;
+            return None;
+        }
+
+        self.parsed += 1;
+        self.data = rest;
+
+        Some(parse_payload(
+            frame,
+            self.config.mag,
+            self.config.gyro,
+            self.config.accel,
+        ))
+    }
+}
+
+/// Parse a regular frame, returning the frame and the payload length
+fn parse_data(header: u8, payload: &[u8]) -> Option<(FifoFrameRef<'_>, usize)> {
+    if header & HEADER_PARM_RESERVED != 0 || header & (HEADER_MAG | HEADER_GYR | HEADER_ACC) == 0 {
+        return None;
+    }
+
+    let data = FifoDataRef { header, payload };
+    let len = data.mag_len() + data.gyro_len() + data.accel_len();
+
+    let payload = payload.get(..len)?;
+    Some((FifoFrameRef::Data(FifoDataRef { header, payload }), len))
+}
+
+/// Parse a data payload laid out as mag, gyro, accel with only the given
+/// sources present
+pub(crate) fn parse_payload(
+    mut payload: &[u8],
+    has_mag: bool,
+    has_gyro: bool,
+    has_accel: bool,
+) -> FifoData {
+    let mut data = FifoData::default();
+
+    if has_mag {
+        data.mag = Some(FifoMagData {
+            mag: DataXYZRaw::from_bytes(payload),
+            rhall_lsb: payload[6],
+            rhall_msb: payload[7],
+        });
+        payload = &payload[FIFO_MAG_LEN..];
+    }
+
+    if has_gyro {
+        data.gyro = Some(DataXYZRaw::from_bytes(payload));
+        payload = &payload[FIFO_XYZ_LEN..];
+    }
+
+    if has_accel {
+        data.accel = Some(DataXYZRaw::from_bytes(payload));
+    }
+
+    data
+}
+
+/// Parse a control frame, returning the frame and the payload length
+fn parse_control(header: u8, payload: &[u8]) -> Option<(FifoFrameRef<'_>, usize)> {
+    match header {
+        HEADER_SKIP => payload
+            .first()
+            .map(|&count| (FifoFrameRef::Skipped(count), 1)),
+        HEADER_SENSORTIME => payload.get(..3).map(|time| {
+            let time = SensorTime::from_le_bytes([time[0], time[1], time[2]]);
+            (FifoFrameRef::SensorTime(time), 3)
+        }),
+        HEADER_INPUT_CONFIG => payload.first().map(|&change| {
+            let change = FifoConfigChange::from_bits_truncate(change);
+            (FifoFrameRef::ConfigChange(change), 1)
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCEL: [u8; 6] = [0x01, 0x00, 0xFF, 0xFF, 0x00, 0x40];
+    const GYRO: [u8; 6] = [0x10, 0x00, 0x20, 0x00, 0x30, 0x80];
+    const MAG: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn xyz(data: Option<DataXYZRaw>) -> Option<(i16, i16, i16)> {
+        data.map(|d| (d.x(), d.y(), d.z()))
+    }
+
+    #[test]
+    fn header_data_frames() {
+        let mut fifo = std::vec![HEADER_MODE_REGULAR | HEADER_ACC];
+        fifo.extend_from_slice(&ACCEL);
+        fifo.push(HEADER_MODE_REGULAR | HEADER_MAG | HEADER_GYR | HEADER_ACC | HEADER_TAG_INT1);
+        fifo.extend_from_slice(&MAG);
+        fifo.extend_from_slice(&GYRO);
+        fifo.extend_from_slice(&ACCEL);
+
+        let mut frames = FifoFrames::new(&fifo);
+
+        let FifoFrame::Data(first) = frames.next().unwrap() else {
+            panic!("expected a data frame");
+        };
+        assert_eq!(xyz(first.accel), Some((1, -1, 0x4000)));
+        assert_eq!(first.gyro, None);
+        assert_eq!(first.mag, None);
+        assert!(!first.int1_tag);
+
+        let FifoFrame::Data(second) = frames.next().unwrap() else {
+            panic!("expected a data frame");
+        };
+        assert_eq!(second.mag.map(|mag| mag.bytes()), Some(MAG));
+        assert_eq!(xyz(second.gyro), Some((0x10, 0x20, -0x7FD0)));
+        assert_eq!(xyz(second.accel), Some((1, -1, 0x4000)));
+        assert!(second.int1_tag);
+        assert!(!second.int2_tag);
+
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.parsed(), 2);
+        assert!(frames.remaining().is_empty());
+        assert!(!frames.end_of_data());
+    }
+
+    #[test]
+    fn header_control_frames() {
+        let fifo = [
+            HEADER_SENSORTIME,
+            0x56,
+            0x34,
+            0x12,
+            HEADER_INPUT_CONFIG,
+            0x03,
+        ];
+
+        let frames: std::vec::Vec<_> = FifoFrames::new(&fifo).collect();
+        assert_eq!(
+            frames,
+            [
+                FifoFrame::SensorTime(SensorTime(0x12_3456)),
+                FifoFrame::ConfigChange(FifoConfigUpdate {
+                    changed: FifoConfigChange::ACCEL | FifoConfigChange::GYRO,
+                    config: None,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn header_ref_matches_copy() {
+        let mut fifo = std::vec![HEADER_MODE_REGULAR | HEADER_GYR | HEADER_ACC];
+        fifo.extend_from_slice(&GYRO);
+        fifo.extend_from_slice(&ACCEL);
+
+        let FifoFrameRef::Data(data) = FifoFrameRefs::new(&fifo).next().unwrap() else {
+            panic!("expected a data frame");
+        };
+        assert_eq!(data.gyro_bytes(), Some(&GYRO[..]));
+        assert_eq!(data.accel_bytes(), Some(&ACCEL[..]));
+        assert_eq!(data.mag_bytes(), None);
+        assert_eq!(
+            FifoFrames::new(&fifo).next(),
+            Some(FifoFrame::Data(data.to_data()))
+        );
+    }
+
+    #[test]
+    fn header_truncated_frame() {
+        let mut fifo = std::vec![HEADER_MODE_REGULAR | HEADER_ACC];
+        fifo.extend_from_slice(&ACCEL);
+        fifo.push(HEADER_MODE_REGULAR | HEADER_GYR | HEADER_ACC);
+        fifo.extend_from_slice(&GYRO);
+        fifo.extend_from_slice(&ACCEL[..3]);
+
+        let mut frames = FifoFrames::new(&fifo);
+        assert!(frames.next().is_some());
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.parsed(), 1);
+        assert_eq!(frames.remaining(), &fifo[7..]);
+        assert!(!frames.end_of_data());
+
+        // Partial control frames are left in place too
+        let mut frames = FifoFrames::new(&[HEADER_SENSORTIME, 0x00]);
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.remaining().len(), 2);
+    }
+
+    #[test]
+    fn header_invalid_header() {
+        let reserved = [
+            HEADER_MODE_REGULAR | HEADER_PARM_RESERVED | HEADER_ACC,
+            0,
+            0,
+        ];
+        assert_eq!(FifoFrames::new(&reserved).next(), None);
+
+        let no_sources = [HEADER_MODE_REGULAR, 0, 0];
+        assert_eq!(FifoFrames::new(&no_sources).next(), None);
+
+        let unknown_control = [0x4C, 0];
+        let mut frames = FifoFrames::new(&unknown_control);
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.remaining(), &unknown_control[..]);
+    }
+
+    #[test]
+    fn header_skip_frames() {
+        let mut fifo = std::vec![HEADER_SKIP, 3, HEADER_MODE_REGULAR | HEADER_ACC];
+        fifo.extend_from_slice(&ACCEL);
+        fifo.extend_from_slice(&[HEADER_SKIP, 250]);
+
+        let mut frames = FifoFrames::new(&fifo);
+        assert_eq!(frames.next(), Some(FifoFrame::Skipped(3)));
+        assert_eq!(frames.skipped(), 3);
+        assert!(matches!(frames.next(), Some(FifoFrame::Data(_))));
+        assert_eq!(frames.next(), Some(FifoFrame::Skipped(250)));
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.skipped(), 253);
+        assert_eq!(frames.parsed(), 3);
+
+        // A skip frame without its count byte is a partial frame
+        let mut frames = FifoFrames::new(&[HEADER_SKIP]);
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.skipped(), 0);
+        assert_eq!(frames.remaining(), &[HEADER_SKIP]);
+    }
+
+    #[test]
+    fn header_over_read_marker() {
+        let mut fifo = std::vec![HEADER_MODE_REGULAR | HEADER_ACC];
+        fifo.extend_from_slice(&ACCEL);
+        fifo.extend_from_slice(&[OVER_READ; 8]);
+
+        let mut frames = FifoFrames::new(&fifo);
+        assert!(frames.next().is_some());
+        assert_eq!(frames.next(), None);
+        assert!(frames.end_of_data());
+        assert_eq!(frames.parsed(), 1);
+        assert_eq!(frames.remaining(), &[OVER_READ; 8]);
+
+        // Reading an empty FIFO only returns the marker
+        let mut frames = FifoFrames::new(&[OVER_READ, OVER_READ]);
+        assert_eq!(frames.next(), None);
+        assert!(frames.end_of_data());
+        assert_eq!(frames.parsed(), 0);
+    }
+
+    #[test]
+    fn headerless_over_read_marker() {
+        let config = FifoConfig {
+            accel: true,
+            ..FifoConfig::default()
+        };
+
+        // 0x80 bytes inside real data don't end parsing, only a whole frame
+        // of them does
+        let mut fifo = std::vec![OVER_READ, OVER_READ, OVER_READ, OVER_READ, 0, 0];
+        fifo.extend_from_slice(&[OVER_READ; 6]);
+        fifo.extend_from_slice(&ACCEL);
+
+        let mut frames = HeaderlessFrames::new(&fifo, config);
+        assert_eq!(
+            xyz(frames.next().unwrap().accel),
+            Some((-0x7F80, -0x7F80, 0))
+        );
+        assert_eq!(frames.next(), None);
+        assert!(frames.end_of_data());
+        assert_eq!(frames.parsed(), 1);
+        assert_eq!(frames.remaining().len(), 12);
+    }
+
+    #[test]
+    fn headerless_frames() {
+        let config = FifoConfig {
+            gyro: true,
+            accel: true,
+            mode: FifoMode::Header,
+            ..FifoConfig::default()
+        };
+        let mut fifo = std::vec::Vec::new();
+        for _ in 0..2 {
+            fifo.extend_from_slice(&GYRO);
+            fifo.extend_from_slice(&ACCEL);
+        }
+
+        let mut frames = HeaderlessFrames::new(&fifo, config);
+        for _ in 0..2 {
+            let data = frames.next().unwrap();
+            assert_eq!(xyz(data.gyro), Some((0x10, 0x20, -0x7FD0)));
+            assert_eq!(xyz(data.accel), Some((1, -1, 0x4000)));
+            assert_eq!(data.mag, None);
+        }
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.parsed(), 2);
+        assert!(frames.remaining().is_empty());
+    }
+
+    #[test]
+    fn headerless_mag_frames() {
+        let config = FifoConfig {
+            mag: true,
+            accel: true,
+            ..FifoConfig::default()
+        };
+        let mut fifo = MAG.to_vec();
+        fifo.extend_from_slice(&ACCEL);
+
+        let data = HeaderlessFrames::new(&fifo, config).next().unwrap();
+        assert_eq!(data.mag.map(|mag| mag.bytes()), Some(MAG));
+        assert_eq!(data.gyro, None);
+        assert_eq!(xyz(data.accel), Some((1, -1, 0x4000)));
+    }
+
+    #[test]
+    fn headerless_truncated_frame() {
+        let config = FifoConfig {
+            accel: true,
+            ..FifoConfig::default()
+        };
+        let mut fifo = ACCEL.to_vec();
+        fifo.extend_from_slice(&ACCEL[..4]);
+
+        let mut frames = HeaderlessFrames::new(&fifo, config);
+        assert!(frames.next().is_some());
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.remaining(), &ACCEL[..4]);
+        assert!(!frames.end_of_data());
+    }
+
+    #[test]
+    fn headerless_no_sources() {
+        let mut frames = HeaderlessFrames::new(&ACCEL, FifoConfig::default());
+        assert_eq!(frames.next(), None);
+        assert_eq!(frames.remaining(), &ACCEL[..]);
+    }
+}
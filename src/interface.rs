@@ -0,0 +1,173 @@
+//! Bus interfaces the driver runs on
+//!
+//! All register, FIFO and interrupt logic goes through [`Interface`], so it
+//! is shared between I2C ([`I2cInterface`]) and SPI
+//! ([`SpiInterface`](crate::SpiInterface),
+//! [`Spi3Interface`](crate::Spi3Interface)).
+
+use hal::blocking::i2c::{Operation, Transactional, Write, WriteRead};
+
+/// Longest transfer the default [`Interface::write_registers`] sends, in
+/// bytes after the register address
+pub const WRITE_BURST_MAX: usize = 15;
+
+/// Register access over a bus
+pub trait Interface {
+    /// Bus error
+    type Error;
+
+    /// Write `bytes[1..]` to consecutive registers, starting at the register
+    /// address in `bytes[0]`
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Write `data` to consecutive registers starting at `register`.
+    ///
+    /// The default implementation copies the address and data into a
+    /// buffer for [`Interface::write`], splitting `data` into bursts of at
+    /// most [`WRITE_BURST_MAX`] bytes at consecutive addresses. Buses that
+    /// can send the address and data as separate parts of one transfer
+    /// override it without the copy or the split.
+    fn write_registers(&mut self, register: u8, data: &[u8]) -> Result<(), Self::Error> {
+        let mut buffer = [0u8; WRITE_BURST_MAX + 1];
+        let mut register = register;
+
+        for chunk in data.chunks(WRITE_BURST_MAX) {
+            buffer[0] = register;
+            buffer[1..=chunk.len()].copy_from_slice(chunk);
+            self.write(&buffer[..=chunk.len()])?;
+            register = register.wrapping_add(chunk.len() as u8);
+        }
+
+        Ok(())
+    }
+
+    /// Read `buffer.len()` bytes from consecutive registers, starting at
+    /// `register`
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+/// I2C bus and the device address the BMI160 answers on
+#[derive(Debug)]
+pub struct I2cInterface<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> I2cInterface<I2C> {
+    /// Talk to the BMI160 at the given 7-bit `address`
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        I2cInterface { i2c, address }
+    }
+}
+
+impl<I2C, E> Interface for I2cInterface<I2C>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E>,
+{
+    type Error = E;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), E> {
+        self.i2c.write(self.address, bytes)
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), E> {
+        self.i2c.write_read(self.address, &[register], buffer)
+    }
+}
+
+/// I2C bus with transaction support and the device address the BMI160
+/// answers on.
+///
+/// Register reads go out as a single address-write and data-read
+/// transaction, and burst writes send the register address and data back
+/// to back without copying them into one buffer, saving start and stop
+/// conditions on HALs whose `write_read` isn't a single transfer.
+#[derive(Debug)]
+pub struct TransactionalI2cInterface<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> TransactionalI2cInterface<I2C> {
+    /// Talk to the BMI160 at the given 7-bit `address`
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        TransactionalI2cInterface { i2c, address }
+    }
+}
+
+impl<I2C, E> Interface for TransactionalI2cInterface<I2C>
+where
+    I2C: Transactional<Error = E>,
+{
+    type Error = E;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), E> {
+        self.i2c.exec(self.address, &mut [Operation::Write(bytes)])
+    }
+
+    fn write_registers(&mut self, register: u8, data: &[u8]) -> Result<(), E> {
+        self.i2c.exec(
+            self.address,
+            &mut [Operation::Write(&[register]), Operation::Write(data)],
+        )
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), E> {
+        self.i2c.exec(
+            self.address,
+            &mut [Operation::Write(&[register]), Operation::Read(buffer)],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    /// Records every transfer passed to [`Interface::write`]
+    #[derive(Default)]
+    struct Bursts(Vec<Vec<u8>>);
+
+    impl Interface for Bursts {
+        type Error = ();
+
+        fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+            self.0.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, _register: u8, _buffer: &mut [u8]) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn short_burst_is_one_write() {
+        let mut bursts = Bursts::default();
+        bursts.write_registers(0x40, &[1, 2, 3]).unwrap();
+
+        assert_eq!(bursts.0, [[0x40, 1, 2, 3]]);
+    }
+
+    #[test]
+    fn long_burst_is_split_at_consecutive_registers() {
+        let data: Vec<u8> = (0..40).collect();
+        let mut bursts = Bursts::default();
+        bursts.write_registers(0x40, &data).unwrap();
+
+        let lens: Vec<(u8, usize)> = bursts.0.iter().map(|w| (w[0], w.len() - 1)).collect();
+        assert_eq!(lens, [(0x40, 15), (0x4F, 15), (0x5E, 10)]);
+
+        let written: Vec<u8> = bursts.0.iter().flat_map(|w| w[1..].to_vec()).collect();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn empty_burst_writes_nothing() {
+        let mut bursts = Bursts::default();
+        bursts.write_registers(0x40, &[]).unwrap();
+
+        assert!(bursts.0.is_empty());
+    }
+}
@@ -0,0 +1,1217 @@
+//! Interrupt configuration
+
+use crate::interface::Interface;
+use crate::orientation::{Face, Orientation};
+use crate::register::{
+    Cmd, InterruptEnable, InterruptFlags, InterruptLatch, PmuTriggerFlags, Register, StatusFlags,
+};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayUs;
+use hal::digital::v2::InputPin;
+
+/// INT_LATCH `int_latch` field
+const INT_LATCH_MASK: u8 = 0x0F;
+
+/// INT_LATCH `int1_input_en`; `int2_input_en` is the next bit up
+const INT_LATCH_INT1_INPUT_EN: u8 = 0b0001_0000;
+
+/// INT_OUT_CTRL `int1_edge_ctrl`, shifted left by 4 for INT2
+const INT_OUT_CTRL_EDGE: u8 = 0b0001;
+
+/// INT_OUT_CTRL `int1_lvl`, shifted left by 4 for INT2
+const INT_OUT_CTRL_LVL: u8 = 0b0010;
+
+/// INT_OUT_CTRL `int1_od`, shifted left by 4 for INT2
+const INT_OUT_CTRL_OD: u8 = 0b0100;
+
+/// INT_OUT_CTRL `int1_output_en`, shifted left by 4 for INT2
+const INT_OUT_CTRL_OUTPUT_EN: u8 = 0b1000;
+
+/// INT_DATA[0] `int_tap_src`
+const INT_DATA_TAP_SRC: u8 = 0b0000_1000;
+
+/// INT_DATA[0] `int_low_high_src`
+const INT_DATA_LOW_HIGH_SRC: u8 = 0b1000_0000;
+
+/// INT_DATA[1] `int_motion_src`
+const INT_DATA_MOTION_SRC: u8 = 0b1000_0000;
+
+/// Poll interval of [`Bmi160::wait_for_data_ready`], in microseconds
+const DATA_READY_POLL_US: u8 = 10;
+
+/// Range-independent interrupt thresholds requested in mg, re-applied by
+/// the driver whenever the accelerometer range changes
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct MgThresholds {
+    pub(crate) tap: Option<f32>,
+    pub(crate) any_motion: Option<f32>,
+    pub(crate) no_motion: Option<f32>,
+    pub(crate) high_g: Option<f32>,
+}
+
+/// Convert a threshold in mg to register steps of `step_mg`, rounding to
+/// nearest and saturating at `max`
+pub(crate) fn mg_to_steps(mg: f32, step_mg: f32, max: u8) -> u8 {
+    let steps = mg / step_mg + 0.5;
+
+    if steps <= 0.0 {
+        0
+    } else if steps >= f32::from(max) {
+        max
+    } else {
+        steps as u8
+    }
+}
+
+/// Interrupt output pin
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InterruptPin {
+    /// INT1
+    Int1,
+
+    /// INT2
+    Int2,
+}
+
+/// Interrupt signal that can be routed to the INT pins
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InterruptSource {
+    /// Low-g; shares its mapping with [`InterruptSource::StepDetector`]
+    LowG,
+
+    /// Step detector; shares its mapping with [`InterruptSource::LowG`]
+    StepDetector,
+
+    /// High-g
+    HighG,
+
+    /// Any-motion; also carries the significant motion interrupt
+    AnyMotion,
+
+    /// No-motion / slow-motion
+    NoMotion,
+
+    /// Double tap
+    DoubleTap,
+
+    /// Single tap
+    SingleTap,
+
+    /// Orientation change
+    Orientation,
+
+    /// Flat detection
+    Flat,
+
+    /// PMU trigger
+    PmuTrigger,
+
+    /// FIFO full
+    FifoFull,
+
+    /// FIFO watermark
+    FifoWatermark,
+
+    /// Data ready
+    DataReady,
+}
+
+impl InterruptSource {
+    /// Byte index within INT_MAP and bit mask for the INT1 mapping
+    fn int1_bit(self) -> (usize, u8) {
+        match self {
+            InterruptSource::LowG | InterruptSource::StepDetector => (0, 1 << 0),
+            InterruptSource::HighG => (0, 1 << 1),
+            InterruptSource::AnyMotion => (0, 1 << 2),
+            InterruptSource::NoMotion => (0, 1 << 3),
+            InterruptSource::DoubleTap => (0, 1 << 4),
+            InterruptSource::SingleTap => (0, 1 << 5),
+            InterruptSource::Orientation => (0, 1 << 6),
+            InterruptSource::Flat => (0, 1 << 7),
+            InterruptSource::PmuTrigger => (1, 1 << 4),
+            InterruptSource::FifoFull => (1, 1 << 5),
+            InterruptSource::FifoWatermark => (1, 1 << 6),
+            InterruptSource::DataReady => (1, 1 << 7),
+        }
+    }
+
+    /// Byte index within INT_MAP and bit mask for the INT2 mapping
+    fn int2_bit(self) -> (usize, u8) {
+        match self.int1_bit() {
+            (0, mask) => (2, mask),
+            (_, mask) => (1, mask >> 4),
+        }
+    }
+}
+
+/// Where an interrupt signal is routed
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum InterruptRoute {
+    /// Not mapped to any pin (default)
+    #[default]
+    None,
+
+    /// INT1 only
+    Int1,
+
+    /// INT2 only
+    Int2,
+
+    /// Both INT1 and INT2
+    Both,
+}
+
+impl InterruptPin {
+    /// Bit offset of this pin's nibble in INT_OUT_CTRL and INT_LATCH
+    fn shift(self) -> u8 {
+        match self {
+            InterruptPin::Int1 => 0,
+            InterruptPin::Int2 => 4,
+        }
+    }
+}
+
+/// Output driver of an interrupt pin
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PinDrive {
+    /// Push-pull (default)
+    #[default]
+    PushPull,
+
+    /// Open drain; needs an external pull-up, allows sharing the line
+    OpenDrain,
+}
+
+/// Active level of an interrupt pin
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PinLevel {
+    /// Active low (default)
+    #[default]
+    ActiveLow,
+
+    /// Active high
+    ActiveHigh,
+}
+
+/// Output behavior of an interrupt pin
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PinTrigger {
+    /// Level: the pin stays active while the interrupt is asserted (default)
+    #[default]
+    Level,
+
+    /// Edge: the pin pulses when the interrupt fires
+    Edge,
+}
+
+/// Electrical configuration of one interrupt pin (INT_OUT_CTRL, 0x53)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct IntPinConfig {
+    /// Enable the output driver. A disabled pin never signals interrupts.
+    pub output_enable: bool,
+
+    /// Push-pull or open drain
+    pub drive: PinDrive,
+
+    /// Active level
+    pub level: PinLevel,
+
+    /// Level or edge output
+    pub trigger: PinTrigger,
+}
+
+impl IntPinConfig {
+    /// Enabled push-pull, active-high, level output: what the one-call
+    /// interrupt setups configure
+    pub(crate) const ACTIVE_HIGH_OUTPUT: IntPinConfig = IntPinConfig {
+        output_enable: true,
+        drive: PinDrive::PushPull,
+        level: PinLevel::ActiveHigh,
+        trigger: PinTrigger::Level,
+    };
+
+    /// INT_OUT_CTRL nibble for this configuration, before shifting for the pin
+    pub(crate) fn bits(&self) -> u8 {
+        let mut bits = 0;
+        if self.trigger == PinTrigger::Edge {
+            bits |= INT_OUT_CTRL_EDGE;
+        }
+        if self.level == PinLevel::ActiveHigh {
+            bits |= INT_OUT_CTRL_LVL;
+        }
+        if self.drive == PinDrive::OpenDrain {
+            bits |= INT_OUT_CTRL_OD;
+        }
+        if self.output_enable {
+            bits |= INT_OUT_CTRL_OUTPUT_EN;
+        }
+        bits
+    }
+
+    /// Decode an unshifted INT_OUT_CTRL nibble
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        IntPinConfig {
+            output_enable: bits & INT_OUT_CTRL_OUTPUT_EN != 0,
+            drive: if bits & INT_OUT_CTRL_OD != 0 {
+                PinDrive::OpenDrain
+            } else {
+                PinDrive::PushPull
+            },
+            level: if bits & INT_OUT_CTRL_LVL != 0 {
+                PinLevel::ActiveHigh
+            } else {
+                PinLevel::ActiveLow
+            },
+            trigger: if bits & INT_OUT_CTRL_EDGE != 0 {
+                PinTrigger::Edge
+            } else {
+                PinTrigger::Level
+            },
+        }
+    }
+}
+
+impl InterruptPin {
+    /// INT_LATCH input enable bit for this pin
+    fn input_en(self) -> u8 {
+        match self {
+            InterruptPin::Int1 => INT_LATCH_INT1_INPUT_EN,
+            InterruptPin::Int2 => INT_LATCH_INT1_INPUT_EN << 1,
+        }
+    }
+}
+
+impl From<InterruptPin> for InterruptRoute {
+    fn from(pin: InterruptPin) -> Self {
+        match pin {
+            InterruptPin::Int1 => InterruptRoute::Int1,
+            InterruptPin::Int2 => InterruptRoute::Int2,
+        }
+    }
+}
+
+/// Data fed to an interrupt engine group
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum InterruptDataSource {
+    /// Data after the accelerometer's configured low-pass filter (default)
+    #[default]
+    Filtered,
+
+    /// Pre-filtered data, at the internal rate and without the configured
+    /// bandwidth filter; faster response, more noise
+    PreFiltered,
+}
+
+impl InterruptDataSource {
+    fn bit(self, mask: u8) -> u8 {
+        match self {
+            InterruptDataSource::Filtered => 0,
+            InterruptDataSource::PreFiltered => mask,
+        }
+    }
+
+    fn from_bit(bits: u8, mask: u8) -> Self {
+        if bits & mask != 0 {
+            InterruptDataSource::PreFiltered
+        } else {
+            InterruptDataSource::Filtered
+        }
+    }
+}
+
+/// Data sources of the interrupt engine groups, covering INT_DATA[0]
+/// (0x58) and INT_DATA[1] (0x59)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct InterruptDataConfig {
+    /// Source for single and double tap
+    pub tap: InterruptDataSource,
+
+    /// Source for low-g and high-g
+    pub low_high: InterruptDataSource,
+
+    /// Source for any-motion, no-motion and significant motion
+    pub motion: InterruptDataSource,
+}
+
+/// Complete interrupt setup, covering INT_EN, INT_OUT_CTRL, INT_LATCH and
+/// INT_MAP (0x50-0x57)
+///
+/// Stage the expected setup here, apply it with
+/// [`Bmi160::apply_interrupt_config`] and check it later with
+/// [`Bmi160::verify_interrupt_config`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct InterruptConfig {
+    /// Enabled interrupt engines
+    pub enabled: InterruptEnable,
+
+    /// Interrupt-to-pin mapping
+    pub map: InterruptMap,
+
+    /// INT1 electrical configuration
+    pub int1: IntPinConfig,
+
+    /// INT2 electrical configuration
+    pub int2: IntPinConfig,
+
+    /// Latching mode
+    pub latch: InterruptLatch,
+
+    /// INT1 input enable
+    pub int1_input: bool,
+
+    /// INT2 input enable
+    pub int2_input: bool,
+}
+
+impl InterruptConfig {
+    /// Register values for 0x50-0x57
+    pub(crate) fn bytes(&self) -> [u8; 8] {
+        let enabled = self.enabled.bits().to_le_bytes();
+        let mut latch = self.latch as u8;
+        if self.int1_input {
+            latch |= InterruptPin::Int1.input_en();
+        }
+        if self.int2_input {
+            latch |= InterruptPin::Int2.input_en();
+        }
+
+        [
+            enabled[0],
+            enabled[1],
+            enabled[2],
+            self.int1.bits() | self.int2.bits() << InterruptPin::Int2.shift(),
+            latch,
+            self.map.bytes[0],
+            self.map.bytes[1],
+            self.map.bytes[2],
+        ]
+    }
+
+    /// Decode register values for 0x50-0x57
+    pub(crate) fn from_bytes(bytes: [u8; 8]) -> Self {
+        InterruptConfig {
+            enabled: InterruptEnable::from_bits_truncate(u32::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], 0,
+            ])),
+            map: InterruptMap {
+                bytes: [bytes[5], bytes[6], bytes[7]],
+            },
+            int1: IntPinConfig::from_bits(bytes[3]),
+            int2: IntPinConfig::from_bits(bytes[3] >> InterruptPin::Int2.shift()),
+            latch: InterruptLatch::from_bits(bytes[4]),
+            int1_input: bytes[4] & InterruptPin::Int1.input_en() != 0,
+            int2_input: bytes[4] & InterruptPin::Int2.input_en() != 0,
+        }
+    }
+}
+
+/// An expected value that didn't match what was read back
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Mismatch<T> {
+    /// Staged value
+    pub expected: T,
+
+    /// Value read from the device
+    pub actual: T,
+}
+
+impl<T: PartialEq> Mismatch<T> {
+    fn check(expected: T, actual: T) -> Option<Self> {
+        if expected == actual {
+            None
+        } else {
+            Some(Mismatch { expected, actual })
+        }
+    }
+}
+
+/// Differences between a staged [`InterruptConfig`] and the device, one
+/// field per setting; `None` means the setting matched
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct InterruptConfigDiff {
+    /// INT_EN mismatch
+    pub enabled: Option<Mismatch<InterruptEnable>>,
+
+    /// INT_MAP mismatch
+    pub map: Option<Mismatch<InterruptMap>>,
+
+    /// INT_OUT_CTRL mismatch for INT1
+    pub int1: Option<Mismatch<IntPinConfig>>,
+
+    /// INT_OUT_CTRL mismatch for INT2
+    pub int2: Option<Mismatch<IntPinConfig>>,
+
+    /// INT_LATCH latch mode mismatch
+    pub latch: Option<Mismatch<InterruptLatch>>,
+
+    /// INT_LATCH INT1 input enable mismatch
+    pub int1_input: Option<Mismatch<bool>>,
+
+    /// INT_LATCH INT2 input enable mismatch
+    pub int2_input: Option<Mismatch<bool>>,
+}
+
+impl InterruptConfigDiff {
+    /// Compare a staged configuration against one read from the device
+    pub fn new(expected: &InterruptConfig, actual: &InterruptConfig) -> Self {
+        InterruptConfigDiff {
+            enabled: Mismatch::check(expected.enabled, actual.enabled),
+            map: Mismatch::check(expected.map, actual.map),
+            int1: Mismatch::check(expected.int1, actual.int1),
+            int2: Mismatch::check(expected.int2, actual.int2),
+            latch: Mismatch::check(expected.latch, actual.latch),
+            int1_input: Mismatch::check(expected.int1_input, actual.int1_input),
+            int2_input: Mismatch::check(expected.int2_input, actual.int2_input),
+        }
+    }
+
+    /// Whether everything matched
+    pub fn is_empty(&self) -> bool {
+        *self == InterruptConfigDiff::default()
+    }
+}
+
+/// Interrupt-to-pin mapping, covering INT_MAP_0..2 (0x55-0x57)
+///
+/// ```ignore
+/// let map = InterruptMap::new()
+///     .map(InterruptSource::DataReady, InterruptRoute::Int1)
+///     .map(InterruptSource::SingleTap, InterruptRoute::Int2);
+/// bmi160.set_interrupt_map(map)?;
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct InterruptMap {
+    bytes: [u8; 3],
+}
+
+impl InterruptMap {
+    /// Mapping with no interrupt routed to either pin
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route `source` to `route`, replacing any previous routing of it
+    pub fn map(mut self, source: InterruptSource, route: InterruptRoute) -> Self {
+        let (int1_index, int1_mask) = source.int1_bit();
+        let (int2_index, int2_mask) = source.int2_bit();
+        let (int1, int2) = match route {
+            InterruptRoute::None => (false, false),
+            InterruptRoute::Int1 => (true, false),
+            InterruptRoute::Int2 => (false, true),
+            InterruptRoute::Both => (true, true),
+        };
+
+        self.bytes[int1_index] &= !int1_mask;
+        self.bytes[int2_index] &= !int2_mask;
+        if int1 {
+            self.bytes[int1_index] |= int1_mask;
+        }
+        if int2 {
+            self.bytes[int2_index] |= int2_mask;
+        }
+        self
+    }
+
+    /// Where `source` is currently routed
+    pub fn route(&self, source: InterruptSource) -> InterruptRoute {
+        let (int1_index, int1_mask) = source.int1_bit();
+        let (int2_index, int2_mask) = source.int2_bit();
+
+        match (
+            self.bytes[int1_index] & int1_mask != 0,
+            self.bytes[int2_index] & int2_mask != 0,
+        ) {
+            (false, false) => InterruptRoute::None,
+            (true, false) => InterruptRoute::Int1,
+            (false, true) => InterruptRoute::Int2,
+            (true, true) => InterruptRoute::Both,
+        }
+    }
+
+    /// Raw INT_MAP_0..2 register values
+    pub fn bytes(&self) -> [u8; 3] {
+        self.bytes
+    }
+
+    /// Build a mapping from raw INT_MAP_0..2 register values
+    pub fn from_bytes(bytes: [u8; 3]) -> Self {
+        InterruptMap { bytes }
+    }
+}
+
+/// Axis and direction that first triggered a motion, tap or high-g
+/// interrupt (INT_STATUS_2/3)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FirstAxis {
+    /// X axis triggered the interrupt
+    pub x: bool,
+
+    /// Y axis triggered the interrupt
+    pub y: bool,
+
+    /// Z axis triggered the interrupt
+    pub z: bool,
+
+    /// The triggering slope or acceleration was negative
+    pub negative: bool,
+}
+
+/// Per-axis enable for interrupt engines that watch individual axes
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Axes {
+    /// X axis
+    pub x: bool,
+
+    /// Y axis
+    pub y: bool,
+
+    /// Z axis
+    pub z: bool,
+}
+
+impl Axes {
+    /// All three axes enabled
+    pub const ALL: Axes = Axes {
+        x: true,
+        y: true,
+        z: true,
+    };
+
+    /// No axis enabled
+    pub const NONE: Axes = Axes {
+        x: false,
+        y: false,
+        z: false,
+    };
+
+    /// Pack into bits 0-2 (X, Y, Z), as used throughout INT_EN
+    pub(crate) fn bits(&self) -> u8 {
+        u8::from(self.x) | u8::from(self.y) << 1 | u8::from(self.z) << 2
+    }
+
+    /// Unpack from bits 0-2
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        Axes {
+            x: bits & 0b001 != 0,
+            y: bits & 0b010 != 0,
+            z: bits & 0b100 != 0,
+        }
+    }
+}
+
+impl Default for Axes {
+    fn default() -> Self {
+        Axes::ALL
+    }
+}
+
+/// Accelerometer axis
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Axis {
+    /// X axis
+    X,
+
+    /// Y axis
+    Y,
+
+    /// Z axis
+    Z,
+}
+
+/// Direction of a motion along an axis
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Towards the positive end of the axis
+    Positive,
+
+    /// Towards the negative end of the axis
+    Negative,
+}
+
+impl FirstAxis {
+    /// The triggering axis. If the engine flagged several axes, X wins over
+    /// Y and Y over Z.
+    pub fn axis(&self) -> Option<Axis> {
+        if self.x {
+            Some(Axis::X)
+        } else if self.y {
+            Some(Axis::Y)
+        } else if self.z {
+            Some(Axis::Z)
+        } else {
+            None
+        }
+    }
+
+    /// Direction of the triggering slope or acceleration
+    pub fn direction(&self) -> Direction {
+        if self.negative {
+            Direction::Negative
+        } else {
+            Direction::Positive
+        }
+    }
+
+    /// Decode a `*_first_x/y/z` and `*_sign` nibble
+    fn from_bits(bits: u8) -> Self {
+        FirstAxis {
+            x: bits & 0b0001 != 0,
+            y: bits & 0b0010 != 0,
+            z: bits & 0b0100 != 0,
+            negative: bits & 0b1000 != 0,
+        }
+    }
+}
+
+/// Decoded INT_STATUS_0..3 (0x1C-0x1F), together with STATUS (0x1B)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InterruptStatus {
+    /// Interrupt engines that fired
+    pub fired: InterruptFlags,
+
+    /// STATUS flags read in the same burst, telling which sensors the
+    /// shared data ready interrupt is for
+    pub status: StatusFlags,
+
+    /// First axis and sign of the last any-motion interrupt
+    pub any_motion: FirstAxis,
+
+    /// First axis and sign of the last tap interrupt
+    pub tap: FirstAxis,
+
+    /// First axis and sign of the last high-g interrupt
+    pub high_g: FirstAxis,
+
+    /// Portrait/landscape orientation (`orient_1_0`)
+    pub orientation: Orientation,
+
+    /// Face up or down (`orient_2`)
+    pub face: Face,
+
+    /// The device is currently flat
+    pub flat: bool,
+}
+
+impl InterruptStatus {
+    /// Decode STATUS and the four INT_STATUS bytes
+    pub(crate) fn from_bytes(status: u8, bytes: [u8; 4]) -> Self {
+        InterruptStatus {
+            fired: InterruptFlags::from_bits_truncate(u16::from_le_bytes([bytes[0], bytes[1]])),
+            status: StatusFlags::from_bits_truncate(status),
+            any_motion: FirstAxis::from_bits(bytes[2]),
+            tap: FirstAxis::from_bits(bytes[2] >> 4),
+            high_g: FirstAxis::from_bits(bytes[3]),
+            orientation: Orientation::from_bits(bytes[3] >> 4),
+            face: Face::from_bits(bytes[3] >> 6),
+            flat: bytes[3] & 0b1000_0000 != 0,
+        }
+    }
+}
+
+impl InterruptStatus {
+    /// Whether the FIFO full interrupt fired
+    pub fn fifo_full(&self) -> bool {
+        self.fired.contains(InterruptFlags::FIFO_FULL)
+    }
+
+    /// Whether the FIFO watermark interrupt fired
+    pub fn fifo_watermark(&self) -> bool {
+        self.fired.contains(InterruptFlags::FIFO_WATERMARK)
+    }
+
+    /// Whether the data ready interrupt fired with new magnetometer data
+    /// (STATUS `drdy_mag`)
+    pub fn mag_data_ready(&self) -> bool {
+        self.fired.contains(InterruptFlags::DATA_READY)
+            && self.status.contains(StatusFlags::DRDY_MAG)
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Enable the given interrupt engines, leaving all others as they are.
+    pub fn enable_interrupts(&mut self, interrupts: InterruptEnable) -> Result<(), Error<E>> {
+        let current = self.get_enabled_interrupts()?;
+        self.set_enabled_interrupts(current | interrupts)
+    }
+
+    /// Disable the given interrupt engines, leaving all others as they are.
+    pub fn disable_interrupts(&mut self, interrupts: InterruptEnable) -> Result<(), Error<E>> {
+        let current = self.get_enabled_interrupts()?;
+        self.set_enabled_interrupts(current - interrupts)
+    }
+
+    /// Enable exactly the given interrupt engines and disable all others.
+    pub fn set_enabled_interrupts(&mut self, interrupts: InterruptEnable) -> Result<(), Error<E>> {
+        let bytes = interrupts.bits().to_le_bytes();
+        self.write_registers(Register::INT_EN, &bytes[..3])
+    }
+
+    /// Temporarily disable all interrupt engines.
+    ///
+    /// The current INT_EN is saved in the driver and restored by
+    /// [`Bmi160::unmask_interrupts`]; engine configuration, mapping and pin
+    /// settings are untouched. Masking again while already masked does
+    /// nothing, so the originally saved enables are kept.
+    pub fn mask_interrupts(&mut self) -> Result<(), Error<E>> {
+        if self.masked_interrupts.is_some() {
+            return Ok(());
+        }
+
+        let enabled = self.get_enabled_interrupts()?;
+        self.set_enabled_interrupts(InterruptEnable::empty())?;
+        self.masked_interrupts = Some(enabled);
+        Ok(())
+    }
+
+    /// Restore the interrupt enables saved by [`Bmi160::mask_interrupts`].
+    /// Does nothing if interrupts aren't masked.
+    pub fn unmask_interrupts(&mut self) -> Result<(), Error<E>> {
+        if let Some(enabled) = self.masked_interrupts {
+            self.set_enabled_interrupts(enabled)?;
+            self.masked_interrupts = None;
+        }
+
+        Ok(())
+    }
+
+    /// Whether interrupts are currently masked by
+    /// [`Bmi160::mask_interrupts`]
+    pub fn interrupts_masked(&self) -> bool {
+        self.masked_interrupts.is_some()
+    }
+
+    /// Rewrite every threshold set in mg for the current accelerometer
+    /// range, so a range change doesn't change detection sensitivity.
+    pub(crate) fn rescale_mg_thresholds(&mut self) -> Result<(), Error<E>> {
+        let thresholds = self.mg_thresholds;
+
+        if let Some(mg) = thresholds.tap {
+            self.set_tap_threshold_mg(mg)?;
+        }
+        if let Some(mg) = thresholds.any_motion {
+            self.set_any_motion_threshold_mg(mg)?;
+        }
+        if let Some(mg) = thresholds.no_motion {
+            self.set_no_motion_threshold_mg(mg)?;
+        }
+        if let Some(mg) = thresholds.high_g {
+            self.set_high_g_threshold_mg(mg)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read which interrupt engines are enabled (INT_EN_0..2).
+    pub fn get_enabled_interrupts(&mut self) -> Result<InterruptEnable, Error<E>> {
+        let mut buffer = [0u8; 3];
+        self.write_read_register(Register::INT_EN, &mut buffer)?;
+        Ok(InterruptEnable::from_bits_truncate(u32::from_le_bytes([
+            buffer[0], buffer[1], buffer[2], 0,
+        ])))
+    }
+
+    /// Write a complete interrupt setup (0x50-0x57).
+    ///
+    /// Written as a single burst.
+    pub fn apply_interrupt_config(&mut self, config: &InterruptConfig) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_EN, &config.bytes())
+    }
+
+    /// Read the complete interrupt setup (0x50-0x57) in a single burst.
+    pub fn read_interrupt_config(&mut self) -> Result<InterruptConfig, Error<E>> {
+        let mut bytes = [0u8; 8];
+        self.write_read_register(Register::INT_EN, &mut bytes)?;
+        Ok(InterruptConfig::from_bytes(bytes))
+    }
+
+    /// Read back the interrupt setup and compare it against `expected`.
+    ///
+    /// Catches dropped writes and unexpected resets in long-running
+    /// devices. An empty diff (see [`InterruptConfigDiff::is_empty`]) means
+    /// the device matches.
+    pub fn verify_interrupt_config(
+        &mut self,
+        expected: &InterruptConfig,
+    ) -> Result<InterruptConfigDiff, Error<E>> {
+        let actual = self.read_interrupt_config()?;
+        Ok(InterruptConfigDiff::new(expected, &actual))
+    }
+
+    /// Read and decode STATUS and all four INT_STATUS bytes in a single
+    /// burst.
+    pub fn get_interrupt_status(&mut self) -> Result<InterruptStatus, Error<E>> {
+        let mut bytes = [0u8; 5];
+        self.write_read_register(Register::STATUS, &mut bytes)?;
+        Ok(InterruptStatus::from_bytes(
+            bytes[0],
+            [bytes[1], bytes[2], bytes[3], bytes[4]],
+        ))
+    }
+
+    /// Select the data fed to each interrupt engine group (INT_DATA).
+    pub fn set_interrupt_data(&mut self, config: InterruptDataConfig) -> Result<(), Error<E>> {
+        self.write_registers(
+            Register::INT_DATA,
+            &[
+                config.tap.bit(INT_DATA_TAP_SRC) | config.low_high.bit(INT_DATA_LOW_HIGH_SRC),
+                config.motion.bit(INT_DATA_MOTION_SRC),
+            ],
+        )
+    }
+
+    /// Read the data source of each interrupt engine group (INT_DATA).
+    pub fn get_interrupt_data(&mut self) -> Result<InterruptDataConfig, Error<E>> {
+        let mut bytes = [0u8; 2];
+        self.write_read_register(Register::INT_DATA, &mut bytes)?;
+
+        Ok(InterruptDataConfig {
+            tap: InterruptDataSource::from_bit(bytes[0], INT_DATA_TAP_SRC),
+            low_high: InterruptDataSource::from_bit(bytes[0], INT_DATA_LOW_HIGH_SRC),
+            motion: InterruptDataSource::from_bit(bytes[1], INT_DATA_MOTION_SRC),
+        })
+    }
+
+    /// Write the complete interrupt-to-pin mapping (INT_MAP_0..2).
+    pub fn set_interrupt_map(&mut self, map: InterruptMap) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_MAP, &map.bytes)
+    }
+
+    /// Read the interrupt-to-pin mapping (INT_MAP_0..2).
+    pub fn get_interrupt_map(&mut self) -> Result<InterruptMap, Error<E>> {
+        let mut bytes = [0u8; 3];
+        self.write_read_register(Register::INT_MAP, &mut bytes)?;
+        Ok(InterruptMap { bytes })
+    }
+
+    /// Route a single interrupt source, leaving all other mappings as they
+    /// are.
+    pub fn map_interrupt(
+        &mut self,
+        source: InterruptSource,
+        route: InterruptRoute,
+    ) -> Result<(), Error<E>> {
+        let map = self.get_interrupt_map()?.map(source, route);
+        self.set_interrupt_map(map)
+    }
+
+    /// Configure the electrical behavior of one interrupt pin. The other
+    /// pin's configuration is left untouched.
+    pub fn set_int_pin_config(
+        &mut self,
+        pin: InterruptPin,
+        config: IntPinConfig,
+    ) -> Result<(), Error<E>> {
+        let shift = pin.shift();
+        self.modify_register(
+            Register::INT_OUT_CTRL,
+            0x0F << shift,
+            config.bits() << shift,
+        )
+    }
+
+    /// Read the electrical configuration of one interrupt pin.
+    pub fn get_int_pin_config(&mut self, pin: InterruptPin) -> Result<IntPinConfig, Error<E>> {
+        let bits = self.read_register(Register::INT_OUT_CTRL)?;
+        Ok(IntPinConfig::from_bits(bits >> pin.shift()))
+    }
+
+    /// Set the interrupt latching mode. Input enables are left untouched.
+    pub fn set_interrupt_latch(&mut self, latch: InterruptLatch) -> Result<(), Error<E>> {
+        self.modify_register(Register::INT_LATCH, INT_LATCH_MASK, latch as u8)
+    }
+
+    /// Read the interrupt latching mode.
+    pub fn get_interrupt_latch(&mut self) -> Result<InterruptLatch, Error<E>> {
+        let bits = self.read_register(Register::INT_LATCH)?;
+        Ok(InterruptLatch::from_bits(bits))
+    }
+
+    /// Clear all latched interrupts (`int_reset` command).
+    ///
+    /// Also resets the interrupt engines, so conditions still present are
+    /// re-evaluated from scratch. Needed after every event when the latch is
+    /// [`InterruptLatch::Latched`]; otherwise the pins stay asserted.
+    pub fn clear_latched_interrupts(&mut self) -> Result<(), Error<E>> {
+        self.send_command(Cmd::INT_RESET)
+    }
+
+    /// Enable or disable `pin` as an input. The output driver must be
+    /// disabled (see [`IntPinConfig::output_enable`]) for an input to work.
+    pub fn set_int_pin_input(&mut self, pin: InterruptPin, enabled: bool) -> Result<(), Error<E>> {
+        let bit = pin.input_en();
+        self.modify_register(Register::INT_LATCH, bit, if enabled { bit } else { 0 })
+    }
+
+    /// Whether `pin` is enabled as an input.
+    pub fn int_pin_input_enabled(&mut self, pin: InterruptPin) -> Result<bool, Error<E>> {
+        let bits = self.read_register(Register::INT_LATCH)?;
+        Ok(bits & pin.input_en() != 0)
+    }
+
+    /// Interrupt on `pin` whenever new sensor data is ready.
+    ///
+    /// Enables the data ready interrupt, maps it to `pin` and configures
+    /// that pin as an enabled push-pull, active-high, level output. The
+    /// interrupt latch is set to non-latched, which affects all interrupt
+    /// engines. Other interrupt enables and mappings are left untouched.
+    pub fn enable_data_ready_interrupt(&mut self, pin: InterruptPin) -> Result<(), Error<E>> {
+        self.set_int_pin_config(pin, IntPinConfig::ACTIVE_HIGH_OUTPUT)?;
+        self.set_interrupt_latch(InterruptLatch::None)?;
+        self.map_interrupt(InterruptSource::DataReady, pin.into())?;
+        self.enable_interrupts(InterruptEnable::DATA_READY)
+    }
+
+    /// Interrupt on `pin` when new magnetometer data is ready.
+    ///
+    /// The BMI160 has one data ready interrupt shared by all sensors, so
+    /// this sets it up like [`Bmi160::enable_data_ready_interrupt`]. With the
+    /// accelerometer and gyroscope suspended it only fires for the
+    /// magnetometer; otherwise check [`InterruptStatus::mag_data_ready`] or
+    /// look for [`Event::MagDataReady`](crate::Event::MagDataReady) to pick
+    /// out compass updates from the IMU stream.
+    pub fn enable_mag_data_ready_interrupt(&mut self, pin: InterruptPin) -> Result<(), Error<E>> {
+        self.enable_data_ready_interrupt(pin)
+    }
+
+    /// Write the automatic gyroscope sleep/wakeup triggers (PMU_TRIGGER).
+    pub fn set_pmu_trigger(&mut self, flags: PmuTriggerFlags) -> Result<(), Error<E>> {
+        self.write_register(Register::PMU_TRIGGER, flags.bits())
+    }
+
+    /// Read the automatic gyroscope sleep/wakeup triggers (PMU_TRIGGER).
+    pub fn get_pmu_trigger(&mut self) -> Result<PmuTriggerFlags, Error<E>> {
+        self.read_register(Register::PMU_TRIGGER)
+            .map(PmuTriggerFlags::from_bits_truncate)
+    }
+
+    /// Wake the gyroscope from fast start-up on an external signal on
+    /// `pin`.
+    ///
+    /// Disables `pin`'s output driver, enables it as an input and sets
+    /// `WAKE_ON_INT` in PMU_TRIGGER, so the gyroscope sleeps in fast
+    /// start-up and wakes on an edge driven by the host. Put the gyroscope
+    /// in [`GyroPowerMode::FastStartUp`](crate::GyroPowerMode::FastStartUp)
+    /// to arm it. Other PMU_TRIGGER bits are kept, except `SLEEP_SUSPEND`,
+    /// which is cleared.
+    pub fn enable_external_wakeup(&mut self, pin: InterruptPin) -> Result<(), Error<E>> {
+        let output_en = INT_OUT_CTRL_OUTPUT_EN << pin.shift();
+        self.modify_register(Register::INT_OUT_CTRL, output_en, 0)?;
+        self.set_int_pin_input(pin, true)?;
+
+        let trigger = self.get_pmu_trigger()?;
+        self.set_pmu_trigger(
+            (trigger - PmuTriggerFlags::SLEEP_SUSPEND) | PmuTriggerFlags::WAKE_ON_INT,
+        )
+    }
+
+    /// Block until `pin`, a host GPIO wired to INT1 or INT2, signals data
+    /// ready.
+    ///
+    /// Set up the interrupt first with
+    /// [`Bmi160::enable_data_ready_interrupt`], which makes the pin
+    /// [`PinLevel::ActiveHigh`]; pass the level the pin was configured
+    /// with. The pin is polled every 10 µs. With a `timeout_us`, returns
+    /// [`Error::Timeout`] if no data arrives in time; without one, waits
+    /// forever.
+    pub fn wait_for_data_ready<P, D>(
+        &mut self,
+        pin: &P,
+        level: PinLevel,
+        timeout_us: Option<u32>,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        P: InputPin,
+        D: DelayUs<u8>,
+    {
+        let mut waited = 0;
+
+        loop {
+            let active = match level {
+                PinLevel::ActiveHigh => pin.is_high(),
+                PinLevel::ActiveLow => pin.is_low(),
+            }
+            .map_err(|_| Error::Pin)?;
+
+            if active {
+                return Ok(());
+            }
+
+            if let Some(timeout) = timeout_us {
+                if waited >= timeout {
+                    return Err(Error::Timeout);
+                }
+            }
+
+            delay.delay_us(DATA_READY_POLL_US);
+            waited += u32::from(DATA_READY_POLL_US);
+        }
+    }
+
+    /// Interrupt when at least `frames` full frames are buffered in the
+    /// FIFO.
+    ///
+    /// Sets the FIFO watermark from the currently enabled FIFO sources (see
+    /// [`Bmi160::set_fifo_watermark_frames`]), enables the FIFO watermark
+    /// interrupt, maps it to `pin` and enables that pin's output driver.
+    /// Other interrupt enables and mappings are left untouched.
+    pub fn enable_fifo_watermark_interrupt(
+        &mut self,
+        frames: u16,
+        pin: InterruptPin,
+    ) -> Result<(), Error<E>> {
+        self.set_fifo_watermark_frames(frames)?;
+
+        let output_en = INT_OUT_CTRL_OUTPUT_EN << pin.shift();
+
+        self.map_interrupt(InterruptSource::FifoWatermark, pin.into())?;
+        self.modify_register(Register::INT_OUT_CTRL, output_en, output_en)?;
+        self.enable_interrupts(InterruptEnable::FIFO_WATERMARK)
+    }
+
+    /// Disable the FIFO watermark interrupt. The watermark level and
+    /// mapping are left untouched.
+    pub fn disable_fifo_watermark_interrupt(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::FIFO_WATERMARK)
+    }
+
+    /// Interrupt when the FIFO is full.
+    ///
+    /// Enables the FIFO full interrupt, maps it to `pin` and enables that
+    /// pin's output driver. Other interrupt enables and mappings are left
+    /// untouched, so it can be combined with the watermark interrupt on
+    /// the same or the other pin.
+    pub fn enable_fifo_full_interrupt(&mut self, pin: InterruptPin) -> Result<(), Error<E>> {
+        let output_en = INT_OUT_CTRL_OUTPUT_EN << pin.shift();
+
+        self.map_interrupt(InterruptSource::FifoFull, pin.into())?;
+        self.modify_register(Register::INT_OUT_CTRL, output_en, output_en)?;
+        self.enable_interrupts(InterruptEnable::FIFO_FULL)
+    }
+
+    /// Disable the FIFO full interrupt. The mapping is left untouched.
+    pub fn disable_fifo_full_interrupt(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::FIFO_FULL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AccelConfig;
+    use crate::mock::Mock;
+    use crate::register::AccelRange;
+
+    #[test]
+    fn interrupt_map_bits() {
+        let map = |source, route| InterruptMap::new().map(source, route).bytes();
+
+        // INT_MAP_0/2 hold the engine interrupts for INT1/INT2
+        assert_eq!(
+            map(InterruptSource::LowG, InterruptRoute::Int1),
+            [0x01, 0, 0]
+        );
+        assert_eq!(
+            map(InterruptSource::StepDetector, InterruptRoute::Int2),
+            [0, 0, 0x01]
+        );
+        assert_eq!(
+            map(InterruptSource::NoMotion, InterruptRoute::Both),
+            [0x08, 0, 0x08]
+        );
+        assert_eq!(
+            map(InterruptSource::Flat, InterruptRoute::Int2),
+            [0, 0, 0x80]
+        );
+
+        // INT_MAP_1 holds INT1 in the high nibble and INT2 in the low one
+        assert_eq!(
+            map(InterruptSource::DataReady, InterruptRoute::Int1),
+            [0, 0x80, 0]
+        );
+        assert_eq!(
+            map(InterruptSource::DataReady, InterruptRoute::Int2),
+            [0, 0x08, 0]
+        );
+        assert_eq!(
+            map(InterruptSource::FifoWatermark, InterruptRoute::Both),
+            [0, 0x44, 0]
+        );
+        assert_eq!(
+            map(InterruptSource::PmuTrigger, InterruptRoute::Int2),
+            [0, 0x01, 0]
+        );
+    }
+
+    #[test]
+    fn interrupt_map_reroute() {
+        let map = InterruptMap::new()
+            .map(InterruptSource::FifoFull, InterruptRoute::Both)
+            .map(InterruptSource::SingleTap, InterruptRoute::Int1)
+            .map(InterruptSource::FifoFull, InterruptRoute::Int2);
+
+        assert_eq!(map.bytes(), [0x20, 0x02, 0]);
+        assert_eq!(map.route(InterruptSource::FifoFull), InterruptRoute::Int2);
+        assert_eq!(map.route(InterruptSource::SingleTap), InterruptRoute::Int1);
+        assert_eq!(map.route(InterruptSource::DoubleTap), InterruptRoute::None);
+        assert_eq!(InterruptMap::from_bytes(map.bytes()), map);
+
+        let cleared = map.map(InterruptSource::FifoFull, InterruptRoute::None);
+        assert_eq!(cleared.bytes(), [0x20, 0, 0]);
+    }
+
+    #[test]
+    fn mg_to_steps_rounds_and_saturates() {
+        assert_eq!(mg_to_steps(100.0, 3.91, u8::MAX), 26);
+        assert_eq!(mg_to_steps(1.9, 3.91, u8::MAX), 0);
+        assert_eq!(mg_to_steps(2.0, 3.91, u8::MAX), 1);
+        assert_eq!(mg_to_steps(-50.0, 3.91, u8::MAX), 0);
+        assert_eq!(mg_to_steps(10_000.0, 3.91, u8::MAX), u8::MAX);
+        assert_eq!(mg_to_steps(1000.0, 125.0, 3), 3);
+    }
+
+    #[test]
+    fn mg_thresholds_follow_range() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.set_any_motion_threshold_mg(100.0).unwrap();
+        imu.set_high_g_threshold_mg(1000.0).unwrap();
+
+        // 3.91 mg and 7.81 mg per step at ±2 g
+        assert_eq!(imu.iface.reg(Register::INT_MOTION_1), 26);
+        assert_eq!(imu.iface.reg(Register::INT_LOWHIGH_4), 128);
+
+        imu.apply_accel_config(AccelConfig {
+            range: AccelRange::G8,
+            ..AccelConfig::default()
+        })
+        .unwrap();
+
+        // Steps are four times larger at ±8 g
+        assert_eq!(imu.iface.reg(Register::INT_MOTION_1), 6);
+        assert_eq!(imu.iface.reg(Register::INT_LOWHIGH_4), 32);
+    }
+
+    #[test]
+    fn raw_thresholds_are_not_rescaled() {
+        use crate::motion::NoMotionConfig;
+
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.set_no_motion_threshold_mg(100.0).unwrap();
+        imu.configure_no_motion(NoMotionConfig {
+            threshold: 7,
+            ..NoMotionConfig::default()
+        })
+        .unwrap();
+
+        imu.apply_accel_config(AccelConfig {
+            range: AccelRange::G16,
+            ..AccelConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(imu.get_no_motion_config().unwrap().threshold, 7);
+    }
+}
@@ -1,5 +1,5 @@
-//! Platform-agnostic ADXL343 accelerometer driver which uses I2C via
-//! [embedded-hal] and implements the [`Accelerometer` trait][trait]
+//! Platform-agnostic BMI160 inertial measurement unit driver which uses I2C
+//! or SPI via [embedded-hal] and implements the [`Accelerometer` trait][trait]
 //! from the `accelerometer` crate.
 //!
 //! <https://www.bosch-sensortec.com/bst/products/all_products/bmi160>
@@ -11,6 +11,7 @@
 //! > In full operation mode, with both the accelerometer and gyroscope enabled, the current
 //! > consumption is typically 950 μA, enabling always-on applications in battery driven devices.
 //! > It is available in a compact 14-pin 2.5 x 3.0 x 0.8 mm³ LGA package.
+//!
 //! [embedded-hal]: https://docs.rs/embedded-hal
 //! [trait]: https://docs.rs/accelerometer/latest/accelerometer/trait.Accelerometer.html
 
@@ -26,68 +27,510 @@
 )]
 #![forbid(unsafe_code)]
 
+#[cfg(test)]
+extern crate std;
+
 extern crate embedded_hal as hal;
 
+mod accel;
+#[cfg(feature = "async")]
+pub mod asynch;
+mod bmm150;
+mod calibration;
+mod config;
+#[cfg(feature = "embedded-hal-1")]
+pub mod eh1;
+mod event;
+mod fifo;
+mod fifo_frames;
+mod interface;
+mod interrupt;
+mod lowhigh;
+mod mag;
+#[cfg(test)]
+mod mock;
+mod motion;
+mod nine_dof;
+mod nvm;
+mod offset;
+mod ois;
+mod orientation;
+mod poll;
 mod register;
-use self::register::Register;
+mod selftest;
+mod shared;
+mod spi;
+mod step;
+mod tap;
+
+pub use self::bmm150::{
+    Bmm150, Bmm150PowerMode, Bmm150Preset, Bmm150Trim, MagSelfTest, Rhall, BMM150_ADDRESS,
+};
+pub use self::calibration::{AccelCorrection, BiasModel, CalibrationData, LinearBiasModel};
+pub use self::config::{
+    AccelConfig, Bmi160Config, ConfigError, GyroConfig, InterfaceConfig, PowerStatus, Preset,
+};
+pub use self::event::{Event, Events};
+pub use self::fifo::{
+    DownsamplingRatio, FifoConfig, FifoDownsampling, FifoMode, FifoStats, FIFO_MAX_FRAME_LEN,
+    FIFO_MAX_WATERMARK, FIFO_SIZE,
+};
+pub use self::fifo_frames::{
+    FifoConfigUpdate, FifoData, FifoDataRef, FifoFrame, FifoFrameRef, FifoFrameRefs, FifoFrames,
+    FifoMagData, HeaderlessFrames,
+};
+pub use self::interface::{I2cInterface, Interface, TransactionalI2cInterface, WRITE_BURST_MAX};
+pub use self::interrupt::{
+    Axes, Axis, Direction, FirstAxis, IntPinConfig, InterruptConfig, InterruptConfigDiff,
+    InterruptDataConfig, InterruptDataSource, InterruptMap, InterruptPin, InterruptRoute,
+    InterruptSource, InterruptStatus, Mismatch, PinDrive, PinLevel, PinTrigger,
+};
+pub use self::lowhigh::{HighGConfig, HighGEvent, LowGConfig, LowGMode, LowHighRegisters};
+pub use self::mag::{MagBus, SecondaryMag, MAG_READ_OFFSET_MAX};
+pub use self::motion::{
+    AnyMotionConfig, MotionRegisters, NoMotionConfig, NoMotionMode, SignificantMotionConfig,
+};
+pub use self::nine_dof::NineDof;
+pub use self::offset::{FocConfig, Offsets};
+pub use self::orientation::{Face, FlatConfig, FlatEvent, Orientation, OrientationConfig};
+pub use self::register::{
+    AccelBandwidth, AccelOdr, AccelPowerMode, AccelRange, Cmd, ConfFlags, FifoConfigChange,
+    FifoConfigFlags, FlatHold, FocAccelTarget, GyroBandwidth, GyroOdr, GyroPowerMode, GyroRange,
+    I2cWatchdog, InterruptEnable, InterruptFlags, InterruptLatch, MagBurst, MagOdr, MagPowerMode,
+    NvConfFlags, OrientBlocking, OrientMode, PmuTriggerFlags, Register, SecondaryInterface,
+    SigMotionProof, SigMotionSkip, SpiWireMode, StatusFlags, TapDuration, TapQuiet, TapShock,
+};
+pub use self::selftest::{AccelSelfTest, ACCEL_SELF_TEST_MIN_MG};
+pub use self::shared::{BusRef, SharedI2c};
+pub use self::spi::{Spi3Interface, SpiError, SpiHalfDuplex, SpiInterface};
+pub use self::step::{Pedometer, StepConfig, StepMode, STEP_TIME_READ_ATTEMPTS};
+pub use self::tap::{TapConfig, TapEvent, TapMode};
 
+use self::interrupt::MgThresholds;
+use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::i2c::{Write, WriteRead};
 
 /// BMI1160 I2C address.
 /// Assumes ALT address pin low
 pub const ADDRESS: u8 = 0x68;
-//pub const ADDRESS:u8 = 0x69;
+
+/// BMI160 I2C address with the SDO pin pulled high
+pub const ALT_ADDRESS: u8 = 0x69;
+
+/// CHIP_ID of the BMI160
+pub const CHIP_ID: u8 = 0xD1;
+
+/// Highest register address
+const LAST_REGISTER: u8 = 0x7F;
+
+/// Worst-case accelerometer power mode transition time in milliseconds
+const ACCEL_PMU_DELAY_MS: u8 = 4;
+
+/// Worst-case gyroscope start-up time from suspend in milliseconds
+const GYRO_PMU_DELAY_MS: u8 = 80;
+
+/// Magnetometer interface power mode transition time in milliseconds
+const MAG_PMU_DELAY_MS: u8 = 1;
+
+/// TEMPERATURE value of 0 corresponds to this temperature in °C
+const TEMPERATURE_OFFSET_C: f32 = 23.0;
+
+/// TEMPERATURE resolution in LSB per K
+const TEMPERATURE_LSB_PER_C: f32 = 512.0;
+
+/// TEMPERATURE value reported when no valid reading is available
+const TEMPERATURE_INVALID: i16 = i16::MIN;
+
+/// BMI160 errors
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error<E> {
+    /// Error from the underlying bus
+    Bus(E),
+
+    /// The requested configuration is rejected by the device
+    InvalidConfig(ConfigError),
+
+    /// A register read back a reserved value that can't be decoded
+    ReservedValue(Register, u8),
+
+    /// The device didn't complete an operation in time
+    Timeout,
+
+    /// Reading a host GPIO wired to an interrupt pin failed
+    Pin,
+
+    /// The device moved during an operation that requires it to be still
+    Moving,
+
+    /// No BMI160 answered on any of the probed addresses
+    NotFound,
+}
+
+impl<E> From<ConfigError> for Error<E> {
+    fn from(error: ConfigError) -> Self {
+        Error::InvalidConfig(error)
+    }
+}
 
 /// BMI160 driver
-pub struct Bmi160<I2C> {
-    /// Underlying I2C device
-    i2c: I2C,
+pub struct Bmi160<DI> {
+    /// Underlying bus interface
+    iface: DI,
+
+    /// Accelerometer range last applied or read back
+    accel_range: AccelRange,
+
+    /// Gyroscope range last applied or read back
+    gyro_range: GyroRange,
+
+    /// Longest read per transfer, see [`Bmi160::set_max_transfer_len`]
+    max_transfer_len: usize,
+
+    /// FIFO counters, see [`Bmi160::fifo_stats`]
+    fifo_stats: FifoStats,
+
+    /// INT_EN saved by [`Bmi160::mask_interrupts`]
+    masked_interrupts: Option<InterruptEnable>,
+
+    /// Interrupt thresholds set in mg, rescaled on range changes
+    mg_thresholds: MgThresholds,
+
+    /// Software gyroscope bias in dps, see [`Bmi160::estimate_gyro_bias`]
+    gyro_bias: [f32; 3],
+
+    /// Software accelerometer correction, see
+    /// [`Bmi160::set_accel_correction`]
+    accel_correction: Option<AccelCorrection>,
+
+    /// BMM150 initialized by [`Bmi160::setup_bmm150`]
+    bmm150: Option<Bmm150>,
+}
+
+impl<DI> Bmi160<DI> {
+    /// Driver state matching the power-on reset state of the device
+    fn with_defaults(iface: DI) -> Self {
+        Bmi160 {
+            iface,
+            accel_range: AccelRange::G2,
+            gyro_range: GyroRange::Dps2000,
+            max_transfer_len: usize::MAX,
+            fifo_stats: FifoStats::default(),
+            masked_interrupts: None,
+            mg_thresholds: MgThresholds::default(),
+            gyro_bias: [0.0; 3],
+            accel_correction: None,
+            bmm150: None,
+        }
+    }
 }
 
-impl<I2C, E> Bmi160<I2C>
+impl<I2C, E> Bmi160<I2cInterface<I2C>>
 where
     I2C: WriteRead<Error = E> + Write<Error = E>,
 {
-    /// Create a new BMI160 driver from the given I2C peripheral
+    /// Create a new BMI160 driver from the given I2C peripheral, at
+    /// [`ADDRESS`]
+    pub fn new(i2c: I2C) -> Result<Self, Error<E>> {
+        Self::from_interface(I2cInterface::new(i2c, ADDRESS))
+    }
+
+    /// Create a new BMI160 driver at whichever of [`ADDRESS`] and
+    /// [`ALT_ADDRESS`] answers with the BMI160 [`CHIP_ID`], trying them in
+    /// that order.
     ///
-    /// Default
-    pub fn new(i2c: I2C) -> Result<Self, E> {
-        let bmi160 = Bmi160 { i2c };
-        Ok(bmi160)
+    /// Bus errors while probing, such as the NACK from an empty address,
+    /// move on to the next address. Fails with [`Error::NotFound`] if
+    /// neither address holds a BMI160.
+    pub fn probe(mut i2c: I2C) -> Result<Self, Error<E>> {
+        for &address in &[ADDRESS, ALT_ADDRESS] {
+            let mut chip_id = [0u8];
+            let found = i2c
+                .write_read(address, &[Register::CHIP_ID.addr()], &mut chip_id)
+                .is_ok();
+
+            if found && chip_id[0] == CHIP_ID {
+                return Self::from_interface(I2cInterface::new(i2c, address));
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Create a new BMI160 driver on the given bus interface
+    pub fn from_interface(iface: DI) -> Result<Self, Error<E>> {
+        Ok(Self::with_defaults(iface))
     }
 
     /// Get the chip ID
-    pub fn get_chip_id(&mut self) -> Result<u8, E> {
-        let input = [Register::CHIP_ID.addr()];
+    pub fn get_chip_id(&mut self) -> Result<u8, Error<E>> {
         let mut output = [0u8];
-        self.i2c.write_read(ADDRESS, &input, &mut output)?;
+        self.iface
+            .read(Register::CHIP_ID.addr(), &mut output)
+            .map_err(Error::Bus)?;
         Ok(output[0])
     }
 
     /// Read The Data (Mag, Gyro, RHALL, Accel) from the Data Register
-    pub fn read_data(&mut self) -> Result<Data, E> {
-        let mut buffer = [0u8, 20];
-        self.i2c.write_read(ADDRESS, &[Register::CMD.addr()], &mut buffer)?;
+    pub fn read_data(&mut self) -> Result<Data, Error<E>> {
+        let mut buffer = [0u8; 20];
+        self.write_read_register(Register::DATA, &mut buffer)?;
         Ok(Data::new_from_buffer(&mut buffer))
     }
 
+    /// Set the accelerometer power mode and wait for the transition to complete
+    pub fn set_accel_power_mode<D>(
+        &mut self,
+        mode: AccelPowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let cmd = match mode {
+            AccelPowerMode::Suspend => Cmd::ACC_SET_PMU_MODE_SUSPEND,
+            AccelPowerMode::Normal => Cmd::ACC_SET_PMU_MODE_NORMAL,
+            AccelPowerMode::LowPower => Cmd::ACC_SET_PMU_MODE_LOW_POWER,
+        };
+        self.send_command(cmd)?;
+        delay.delay_ms(ACCEL_PMU_DELAY_MS);
+        Ok(())
+    }
+
+    /// Set the gyroscope power mode and wait for the transition to complete
+    pub fn set_gyro_power_mode<D>(
+        &mut self,
+        mode: GyroPowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let cmd = match mode {
+            GyroPowerMode::Suspend => Cmd::GYR_SET_PMU_MODE_SUSPEND,
+            GyroPowerMode::Normal => Cmd::GYR_SET_PMU_MODE_NORMAL,
+            GyroPowerMode::FastStartUp => Cmd::GYR_SET_PMU_MODE_FAST_STARTUP,
+        };
+        self.send_command(cmd)?;
+        delay.delay_ms(GYRO_PMU_DELAY_MS);
+        Ok(())
+    }
+
+    /// Set the magnetometer interface power mode and wait for the transition
+    /// to complete
+    pub fn set_mag_power_mode<D>(
+        &mut self,
+        mode: MagPowerMode,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let cmd = match mode {
+            MagPowerMode::Suspend => Cmd::MAG_IF_SET_PMU_MODE_SUSPEND,
+            MagPowerMode::Normal => Cmd::MAG_IF_SET_PMU_MODE_NORMAL,
+            MagPowerMode::LowPower => Cmd::MAG_IF_SET_PMU_MODE_LOW_POWER,
+        };
+        self.send_command(cmd)?;
+        delay.delay_ms(MAG_PMU_DELAY_MS);
+        Ok(())
+    }
+
+    /// Accelerometer range last applied or read back by the driver.
+    ///
+    /// This is a cached value and doesn't touch the bus.
+    pub fn accel_range(&self) -> AccelRange {
+        self.accel_range
+    }
+
+    /// Gyroscope range last applied or read back by the driver.
+    ///
+    /// This is a cached value and doesn't touch the bus.
+    pub fn gyro_range(&self) -> GyroRange {
+        self.gyro_range
+    }
+
+    /// Read the 24-bit SENSORTIME counter
+    pub fn read_sensortime(&mut self) -> Result<SensorTime, Error<E>> {
+        let mut buffer = [0u8; 3];
+        self.write_read_register(Register::SENSORTIME, &mut buffer)?;
+        Ok(SensorTime::from_le_bytes(buffer))
+    }
+
+    /// Read the die temperature in °C.
+    ///
+    /// Returns `None` while the temperature sensor has no valid reading,
+    /// e.g. with both the accelerometer and gyroscope suspended.
+    pub fn read_temperature(&mut self) -> Result<Option<f32>, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.write_read_register(Register::TEMPERATURE, &mut buffer)?;
+        Ok(temperature_from_bytes(buffer))
+    }
+
+    /// Read the STATUS register flags
+    pub fn get_status(&mut self) -> Result<StatusFlags, Error<E>> {
+        self.read_register(Register::STATUS)
+            .map(StatusFlags::from_bits_truncate)
+    }
+
     /// Resets and restarts the device.
-    pub fn soft_reset(&mut self) -> Result<(), E> {
+    pub fn soft_reset(&mut self) -> Result<(), Error<E>> {
         Ok(())
     }
 
     /// Write to the given register
     // TODO: make this an internal API after enough functionality is wrapped
-    pub fn write_register(&mut self, register: Register, value: u8) -> Result<(), E> {
+    pub fn write_register(&mut self, register: Register, value: u8) -> Result<(), Error<E>> {
         debug_assert!(!register.read_only(), "can't write to read-only register");
-        self.i2c.write(ADDRESS, &[register.addr(), value])?;
-        Ok(())
+        self.iface
+            .write(&[register.addr(), value])
+            .map_err(Error::Bus)
     }
 
-    /// Write to a given register, then read the result
+    /// Read a single register
+    pub(crate) fn read_register(&mut self, register: Register) -> Result<u8, Error<E>> {
+        let mut buffer = [0u8];
+        self.write_read_register(register, &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    /// Read-modify-write a register: clear the bits in `mask`, then set
+    /// the bits in `value`
+    pub(crate) fn modify_register(
+        &mut self,
+        register: Register,
+        mask: u8,
+        value: u8,
+    ) -> Result<(), Error<E>> {
+        let current = self.read_register(register)?;
+        self.write_register(register, (current & !mask) | value)
+    }
+
+    /// Write a command to the CMD register
+    pub(crate) fn send_command(&mut self, cmd: Cmd) -> Result<(), Error<E>> {
+        self.write_register(Register::CMD, cmd as u8)
+    }
+
+    /// Burst write consecutive registers starting at the given register
+    pub(crate) fn write_registers(
+        &mut self,
+        register: Register,
+        data: &[u8],
+    ) -> Result<(), Error<E>> {
+        self.iface
+            .write_registers(register.addr(), data)
+            .map_err(Error::Bus)
+    }
+
+    /// Write to a given register, then read the result.
+    ///
+    /// Reads longer than [`Bmi160::max_transfer_len`] are split into
+    /// several transfers, each continuing at the next register address
+    /// (FIFO_DATA is read repeatedly instead). Fails with
+    /// [`ConfigError::ReadPastLastRegister`] if the read would run past
+    /// register 0x7F.
     // TODO: make this an internal API after enough functionality is wrapped
-    pub fn write_read_register(&mut self, register: Register, buffer: &mut [u8]) -> Result<(), E> {
-        self.i2c.write_read(ADDRESS, &[register.addr()], buffer)
+    pub fn write_read_register(
+        &mut self,
+        register: Register,
+        buffer: &mut [u8],
+    ) -> Result<(), Error<E>> {
+        let start = register.addr();
+        if register != Register::FIFO_DATA
+            && usize::from(start) + buffer.len() > usize::from(LAST_REGISTER) + 1
+        {
+            return Err(ConfigError::ReadPastLastRegister.into());
+        }
+
+        if buffer.len() <= self.max_transfer_len {
+            return self.iface.read(start, buffer).map_err(Error::Bus);
+        }
+
+        let mut addr = start;
+        for chunk in buffer.chunks_mut(self.max_transfer_len) {
+            self.iface.read(addr, chunk).map_err(Error::Bus)?;
+
+            if register != Register::FIFO_DATA {
+                // Can't overflow: the whole read ends at or before 0x7F
+                addr += chunk.len() as u8;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Limit every read to `bytes` per bus transfer.
+    ///
+    /// Needed on buses or DMA engines that can't move more than a fixed
+    /// number of bytes at once (often 255 bytes on I2C). Burst reads such
+    /// as DATA and [`Bmi160::read_9dof`] are split at register boundaries,
+    /// which gives up the data consistency of a single burst; FIFO reads
+    /// are split at frame boundaries, see [`Bmi160::read_fifo`]. `bytes`
+    /// must be at least [`FIFO_MAX_FRAME_LEN`] so every FIFO burst can
+    /// hold a complete frame.
+    ///
+    /// Only reads are split by this limit. The default
+    /// [`Interface::write_registers`] sends at most [`WRITE_BURST_MAX`]
+    /// bytes plus the register address per transfer, which is shorter than
+    /// any accepted `bytes`.
+    pub fn set_max_transfer_len(&mut self, bytes: usize) -> Result<(), Error<E>> {
+        if bytes < FIFO_MAX_FRAME_LEN {
+            return Err(ConfigError::TransferTooSmall.into());
+        }
+
+        self.max_transfer_len = bytes;
+        Ok(())
+    }
+
+    /// Longest read issued in a single bus transfer, unlimited by default
+    pub fn max_transfer_len(&self) -> usize {
+        self.max_transfer_len
+    }
+}
+
+/// Convert the TEMPERATURE register bytes to °C, `None` if invalid
+pub(crate) fn temperature_from_bytes(bytes: [u8; 2]) -> Option<f32> {
+    let raw = i16::from_le_bytes(bytes);
+
+    if raw == TEMPERATURE_INVALID {
+        return None;
+    }
+
+    Some(TEMPERATURE_OFFSET_C + f32::from(raw) / TEMPERATURE_LSB_PER_C)
+}
+
+/// Value of the 24-bit sensortime counter, which ticks every 39.0625 µs
+/// and wraps roughly every 655 seconds.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SensorTime(pub u32);
+
+impl SensorTime {
+    /// Counter mask; the counter is 24 bits wide
+    const MASK: u32 = 0x00FF_FFFF;
+
+    /// Build from the three little-endian SENSORTIME bytes
+    pub(crate) fn from_le_bytes(bytes: [u8; 3]) -> Self {
+        SensorTime(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
+    /// Raw counter ticks
+    pub fn ticks(self) -> u32 {
+        self.0
+    }
+
+    /// Counter value converted to microseconds
+    pub fn as_micros(self) -> u64 {
+        u64::from(self.0) * 625 / 16
+    }
+
+    /// Microseconds elapsed since `earlier`, accounting for one wrap of the
+    /// 24-bit counter
+    pub fn micros_since(self, earlier: SensorTime) -> u64 {
+        SensorTime(self.0.wrapping_sub(earlier.0) & Self::MASK).as_micros()
     }
 }
 
@@ -116,7 +559,45 @@ pub struct DataXYZRaw {
     z_msb: u8,
 }
 
-/// The Raw Data structure returned from reading the 
+impl DataXYZRaw {
+    /// Build from six bytes laid out as X LSB, X MSB, Y LSB, ... Z MSB
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        DataXYZRaw {
+            x_lsb: bytes[0],
+            x_msb: bytes[1],
+            y_lsb: bytes[2],
+            y_msb: bytes[3],
+            z_lsb: bytes[4],
+            z_msb: bytes[5],
+        }
+    }
+
+    /// X axis as a signed 16-bit value
+    pub fn x(&self) -> i16 {
+        i16::from_le_bytes([self.x_lsb, self.x_msb])
+    }
+
+    /// Y axis as a signed 16-bit value
+    pub fn y(&self) -> i16 {
+        i16::from_le_bytes([self.y_lsb, self.y_msb])
+    }
+
+    /// Z axis as a signed 16-bit value
+    pub fn z(&self) -> i16 {
+        i16::from_le_bytes([self.z_lsb, self.z_msb])
+    }
+
+    /// All three axes divided by the given sensitivity (LSB per unit)
+    pub fn scaled(&self, lsb_per_unit: f32) -> [f32; 3] {
+        [
+            f32::from(self.x()) / lsb_per_unit,
+            f32::from(self.y()) / lsb_per_unit,
+            f32::from(self.z()) / lsb_per_unit,
+        ]
+    }
+}
+
+/// The Raw Data structure returned from reading the
 /// data register.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(C)]
@@ -138,11 +619,16 @@ pub struct Data {
 }
 
 impl Data {
+    /// Decode the RHALL bytes
+    pub fn rhall(&self) -> Rhall {
+        Rhall::from_bytes(self.rhall_lsb, self.rhall_msb)
+    }
+
     /// Returns a new Data struct from the data buffer returned
     /// from the Data register.
     pub fn new_from_buffer(buffer: &mut [u8]) -> Self {
         Data {
-            mag:  DataXYZRaw {
+            mag: DataXYZRaw {
                 x_lsb: buffer[0],
                 x_msb: buffer[1],
                 y_lsb: buffer[2],
@@ -170,8 +656,48 @@ impl Data {
                 y_msb: buffer[17],
                 z_lsb: buffer[18],
                 z_msb: buffer[19],
-            }, 
+            },
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Mock;
+
+    #[test]
+    fn chunked_reads_continue_at_next_register() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.set_max_transfer_len(FIFO_MAX_FRAME_LEN).unwrap();
+        imu.iface.reads.clear();
+
+        let mut buffer = [0u8; 50];
+        imu.write_read_register(Register::DATA, &mut buffer)
+            .unwrap();
+
+        let data = Register::DATA.addr();
+        assert_eq!(
+            imu.iface.reads,
+            [(data, 21), (data + 21, 21), (data + 42, 8)]
+        );
+    }
+
+    #[test]
+    fn reads_past_last_register_are_rejected() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.iface.reads.clear();
+
+        let mut buffer = [0u8; 9];
+        assert!(matches!(
+            imu.write_read_register(Register::STEP_CNT, &mut buffer),
+            Err(Error::InvalidConfig(ConfigError::ReadPastLastRegister))
+        ));
+        assert!(imu.iface.reads.is_empty());
+
+        let mut buffer = [0u8; 8];
+        assert!(imu
+            .write_read_register(Register::STEP_CNT, &mut buffer)
+            .is_ok());
+    }
+}
@@ -0,0 +1,536 @@
+//! Low-g and high-g detection (INT_LOWHIGH)
+
+use crate::interface::Interface;
+use crate::interrupt::{
+    mg_to_steps, Axes, Axis, Direction, IntPinConfig, InterruptPin, InterruptSource,
+    InterruptStatus,
+};
+use crate::register::{AccelRange, InterruptEnable, InterruptFlags, InterruptLatch, Register};
+use crate::{Bmi160, Error};
+
+/// INT_LOWHIGH[2] `int_high_hy`, 2 bits starting at bit 6
+const INT_LOWHIGH_HIGH_HY_SHIFT: u8 = 6;
+
+/// INT_LOWHIGH[2] `int_high_hy`
+const INT_LOWHIGH_HIGH_HY_MASK: u8 = 0b1100_0000;
+
+/// INT_LOWHIGH[2] `int_low_hy`
+const INT_LOWHIGH_LOW_HY_MASK: u8 = 0b0000_0011;
+
+/// INT_LOWHIGH[2] `int_low_mode`
+const INT_LOWHIGH_LOW_MODE: u8 = 0b0000_0100;
+
+/// Low-g threshold step in mg, independent of the accelerometer range
+const LOW_G_TH_MG: f32 = 7.81;
+
+/// Low-g hysteresis step in mg, independent of the accelerometer range
+const LOW_G_HY_MG: f32 = 125.0;
+
+/// High-g threshold step at the ±2 g range, in mg
+const HIGH_G_TH_MG_2G: f32 = 7.81;
+
+/// High-g hysteresis step at the ±2 g range, in mg
+const HIGH_G_HY_MG_2G: f32 = 125.0;
+
+/// Low-g and high-g delay step, in ms
+const LOWHIGH_DUR_STEP_MS: f32 = 2.5;
+
+/// How the low-g engine combines the axes (INT_LOWHIGH `int_low_mode`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LowGMode {
+    /// Compare each axis against the threshold (default)
+    #[default]
+    SingleAxis,
+
+    /// Compare `|x| + |y| + |z|` against the threshold; the usual choice
+    /// for free fall
+    AxisSumming,
+}
+
+/// Low-g (free fall) detection configuration
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LowGConfig {
+    /// Raw threshold (`int_low_th`), 7.81 mg per step at every range
+    pub threshold: u8,
+
+    /// Raw delay (`int_low_dur`); the interrupt fires after
+    /// `(duration + 1) * 2.5 ms` below threshold
+    pub duration: u8,
+
+    /// Raw hysteresis (`int_low_hy`, 0-3), 125 mg per step
+    pub hysteresis: u8,
+
+    /// Single-axis or axis-summing comparison
+    pub mode: LowGMode,
+}
+
+impl Default for LowGConfig {
+    /// Power-on reset values
+    fn default() -> Self {
+        LowGConfig {
+            threshold: 0x30,
+            duration: 0x07,
+            hysteresis: 1,
+            mode: LowGMode::default(),
+        }
+    }
+}
+
+impl LowGConfig {
+    /// Axis-summing configuration firing once acceleration stays below
+    /// `threshold_mg` for at least `min_duration_ms`. Both values are
+    /// rounded to the nearest step and saturate at the register limits.
+    pub fn free_fall(threshold_mg: f32, min_duration_ms: f32) -> Self {
+        LowGConfig {
+            threshold: mg_to_steps(threshold_mg, LOW_G_TH_MG, u8::MAX),
+            // The register counts `duration + 1` steps
+            duration: mg_to_steps(
+                min_duration_ms - LOWHIGH_DUR_STEP_MS,
+                LOWHIGH_DUR_STEP_MS,
+                u8::MAX,
+            ),
+            hysteresis: 1,
+            mode: LowGMode::AxisSumming,
+        }
+    }
+
+    /// Threshold in mg
+    pub fn threshold_mg(&self) -> f32 {
+        f32::from(self.threshold) * LOW_G_TH_MG
+    }
+
+    /// Hysteresis in mg
+    pub fn hysteresis_mg(&self) -> f32 {
+        f32::from(self.hysteresis) * LOW_G_HY_MG
+    }
+
+    /// Delay before the interrupt fires, in ms
+    pub fn duration_ms(&self) -> f32 {
+        (f32::from(self.duration) + 1.0) * LOWHIGH_DUR_STEP_MS
+    }
+}
+
+/// High-g threshold step in mg for the given range
+fn high_g_threshold_step_mg(range: AccelRange) -> f32 {
+    HIGH_G_TH_MG_2G * range.g() / 2.0
+}
+
+/// High-g (shock) detection configuration
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HighGConfig {
+    /// Raw threshold (`int_high_th`). One step is 7.81 mg at ±2 g and
+    /// scales with the accelerometer range.
+    pub threshold: u8,
+
+    /// Raw delay (`int_high_dur`); the interrupt fires after
+    /// `(duration + 1) * 2.5 ms` above threshold
+    pub duration: u8,
+
+    /// Raw hysteresis (`int_high_hy`, 0-3). One step is 125 mg at ±2 g and
+    /// scales with the accelerometer range.
+    pub hysteresis: u8,
+
+    /// Axes to watch
+    pub axes: Axes,
+}
+
+impl Default for HighGConfig {
+    /// Power-on reset values, all axes enabled
+    fn default() -> Self {
+        HighGConfig {
+            threshold: 0xC0,
+            duration: 0x0B,
+            hysteresis: 2,
+            axes: Axes::ALL,
+        }
+    }
+}
+
+impl HighGConfig {
+    /// Threshold in mg for the given accelerometer range
+    pub fn threshold_mg(&self, range: AccelRange) -> f32 {
+        f32::from(self.threshold) * high_g_threshold_step_mg(range)
+    }
+
+    /// Set the threshold in mg for the given accelerometer range, rounded
+    /// to the nearest step
+    pub fn with_threshold_mg(mut self, mg: f32, range: AccelRange) -> Self {
+        self.threshold = mg_to_steps(mg, high_g_threshold_step_mg(range), u8::MAX);
+        self
+    }
+
+    /// Hysteresis in mg for the given accelerometer range
+    pub fn hysteresis_mg(&self, range: AccelRange) -> f32 {
+        f32::from(self.hysteresis) * HIGH_G_HY_MG_2G * range.g() / 2.0
+    }
+
+    /// Delay before the interrupt fires, in ms
+    pub fn duration_ms(&self) -> f32 {
+        (f32::from(self.duration) + 1.0) * LOWHIGH_DUR_STEP_MS
+    }
+}
+
+/// A detected high-g event
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HighGEvent {
+    /// Axis that first exceeded the threshold, `None` if the engine didn't
+    /// flag one
+    pub axis: Option<Axis>,
+
+    /// Sign of the acceleration on `axis`
+    pub direction: Direction,
+}
+
+impl HighGEvent {
+    /// Decode the high-g event in `status`, if the high-g interrupt fired
+    pub fn from_status(status: &InterruptStatus) -> Option<Self> {
+        if !status.fired.contains(InterruptFlags::HIGH_G) {
+            return None;
+        }
+
+        Some(HighGEvent {
+            axis: status.high_g.axis(),
+            direction: status.high_g.direction(),
+        })
+    }
+}
+
+/// Staged contents of the five INT_LOWHIGH registers (0x5A-0x5E).
+///
+/// Low-g and high-g share these registers; INT_LOWHIGH[2] holds fields of
+/// both. Stage all changes here and commit them with
+/// [`Bmi160::write_lowhigh_registers`], a single burst write, so the
+/// engines never see a half-updated configuration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LowHighRegisters {
+    bytes: [u8; 5],
+}
+
+impl Default for LowHighRegisters {
+    /// Power-on reset values
+    fn default() -> Self {
+        LowHighRegisters {
+            bytes: [0x07, 0x30, 0x81, 0x0B, 0xC0],
+        }
+    }
+}
+
+impl LowHighRegisters {
+    /// Power-on reset values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage a low-g configuration.
+    pub fn low_g(mut self, config: &LowGConfig) -> Self {
+        let mode = match config.mode {
+            LowGMode::SingleAxis => 0,
+            LowGMode::AxisSumming => INT_LOWHIGH_LOW_MODE,
+        };
+
+        self.bytes[0] = config.duration;
+        self.bytes[1] = config.threshold;
+        self.bytes[2] = (self.bytes[2] & !(INT_LOWHIGH_LOW_HY_MASK | INT_LOWHIGH_LOW_MODE))
+            | (config.hysteresis & INT_LOWHIGH_LOW_HY_MASK)
+            | mode;
+        self
+    }
+
+    /// Stage a high-g configuration. `config.axes` is not part of
+    /// INT_LOWHIGH.
+    pub fn high_g(mut self, config: &HighGConfig) -> Self {
+        self.bytes[2] = (self.bytes[2] & !INT_LOWHIGH_HIGH_HY_MASK)
+            | (config.hysteresis << INT_LOWHIGH_HIGH_HY_SHIFT) & INT_LOWHIGH_HIGH_HY_MASK;
+        self.bytes[3] = config.duration;
+        self.bytes[4] = config.threshold;
+        self
+    }
+
+    /// Raw INT_LOWHIGH[0..4] values
+    pub fn bytes(&self) -> [u8; 5] {
+        self.bytes
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Commit staged INT_LOWHIGH contents with a single burst write.
+    ///
+    /// The high-g threshold is taken as a raw value, so a threshold
+    /// previously set in mg is no longer rescaled on range changes.
+    pub fn write_lowhigh_registers(&mut self, registers: LowHighRegisters) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_LOWHIGH, &registers.bytes)?;
+        self.mg_thresholds.high_g = None;
+        Ok(())
+    }
+
+    /// Set the high-g threshold in mg.
+    ///
+    /// Converted using the driver's cached accelerometer range and
+    /// rewritten whenever [`Bmi160::apply_accel_config`] changes the range,
+    /// so the sensitivity stays the same.
+    pub fn set_high_g_threshold_mg(&mut self, mg: f32) -> Result<(), Error<E>> {
+        let steps = mg_to_steps(mg, high_g_threshold_step_mg(self.accel_range), u8::MAX);
+        self.write_register(Register::INT_LOWHIGH_4, steps)?;
+        self.mg_thresholds.high_g = Some(mg);
+        Ok(())
+    }
+
+    /// Read the current INT_LOWHIGH contents, to stage changes on top of.
+    pub fn read_lowhigh_registers(&mut self) -> Result<LowHighRegisters, Error<E>> {
+        let mut bytes = [0u8; 5];
+        self.write_read_register(Register::INT_LOWHIGH, &mut bytes)?;
+        Ok(LowHighRegisters { bytes })
+    }
+
+    /// Configure and enable high-g detection.
+    ///
+    /// Writes the threshold, delay and hysteresis and enables exactly the
+    /// axes in `config.axes`. Route the interrupt with
+    /// [`Bmi160::map_interrupt`] and
+    /// [`InterruptSource::HighG`](crate::InterruptSource::HighG).
+    pub fn configure_high_g(&mut self, config: HighGConfig) -> Result<(), Error<E>> {
+        let registers = self.read_lowhigh_registers()?.high_g(&config);
+        self.write_registers(Register::INT_LOWHIGH, &registers.bytes)?;
+        self.mg_thresholds.high_g = None;
+
+        let current = self.get_enabled_interrupts()?;
+        let axes = InterruptEnable::from_bits_truncate(u32::from(config.axes.bits()) << 8);
+        self.set_enabled_interrupts((current - InterruptEnable::HIGH_G) | axes)
+    }
+
+    /// Read the high-g configuration and enabled axes.
+    pub fn get_high_g_config(&mut self) -> Result<HighGConfig, Error<E>> {
+        let mut lowhigh = [0u8; 3];
+        self.write_read_register(Register::INT_LOWHIGH_2, &mut lowhigh)?;
+        let enabled = self.get_enabled_interrupts()?;
+
+        Ok(HighGConfig {
+            threshold: lowhigh[2],
+            duration: lowhigh[1],
+            hysteresis: lowhigh[0] >> INT_LOWHIGH_HIGH_HY_SHIFT,
+            axes: Axes::from_bits((enabled.bits() >> 8) as u8),
+        })
+    }
+
+    /// Configure and enable low-g detection.
+    ///
+    /// Route the interrupt with [`Bmi160::map_interrupt`] and
+    /// [`InterruptSource::LowG`].
+    pub fn configure_low_g(&mut self, config: LowGConfig) -> Result<(), Error<E>> {
+        let registers = self.read_lowhigh_registers()?.low_g(&config);
+        self.write_registers(Register::INT_LOWHIGH, &registers.bytes)?;
+        self.enable_interrupts(InterruptEnable::LOW_G)
+    }
+
+    /// Read the low-g configuration.
+    pub fn get_low_g_config(&mut self) -> Result<LowGConfig, Error<E>> {
+        let lowhigh = self.read_lowhigh_registers()?.bytes;
+
+        Ok(LowGConfig {
+            threshold: lowhigh[1],
+            duration: lowhigh[0],
+            hysteresis: lowhigh[2] & INT_LOWHIGH_LOW_HY_MASK,
+            mode: if lowhigh[2] & INT_LOWHIGH_LOW_MODE != 0 {
+                LowGMode::AxisSumming
+            } else {
+                LowGMode::SingleAxis
+            },
+        })
+    }
+
+    /// Disable low-g detection.
+    pub fn disable_low_g(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::LOW_G)
+    }
+
+    /// Interrupt on `pin` when the device is in free fall.
+    ///
+    /// Configures the low-g engine in axis-summing mode with the given
+    /// threshold (typically 300-500 mg) and minimum duration (typically
+    /// 20-100 ms), latches interrupts for 80 ms so short falls aren't
+    /// missed, maps low-g to `pin` and makes it an enabled push-pull,
+    /// active-high output. The latch setting affects all interrupt engines.
+    pub fn enable_freefall_detection(
+        &mut self,
+        threshold_mg: f32,
+        min_duration_ms: f32,
+        pin: InterruptPin,
+    ) -> Result<(), Error<E>> {
+        self.set_int_pin_config(pin, IntPinConfig::ACTIVE_HIGH_OUTPUT)?;
+        self.set_interrupt_latch(InterruptLatch::Ms80)?;
+        self.map_interrupt(InterruptSource::LowG, pin.into())?;
+        self.configure_low_g(LowGConfig::free_fall(threshold_mg, min_duration_ms))
+    }
+
+    /// Disable high-g detection on all axes.
+    pub fn disable_high_g(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::HIGH_G)
+    }
+
+    /// Read INT_STATUS and decode the high-g event, if the high-g
+    /// interrupt fired.
+    pub fn high_g_event(&mut self) -> Result<Option<HighGEvent>, Error<E>> {
+        let status = self.get_interrupt_status()?;
+        Ok(HighGEvent::from_status(&status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AccelConfig;
+    use crate::mock::Mock;
+
+    const LOW_G: LowGConfig = LowGConfig {
+        threshold: 0x40,
+        duration: 0x10,
+        hysteresis: 2,
+        mode: LowGMode::AxisSumming,
+    };
+
+    const HIGH_G: HighGConfig = HighGConfig {
+        threshold: 0x80,
+        duration: 0x20,
+        hysteresis: 3,
+        axes: Axes::ALL,
+    };
+
+    #[test]
+    fn staged_fields_share_byte_2() {
+        let low_then_high = LowHighRegisters::new().low_g(&LOW_G).high_g(&HIGH_G);
+        let high_then_low = LowHighRegisters::new().high_g(&HIGH_G).low_g(&LOW_G);
+
+        assert_eq!(low_then_high.bytes(), [0x10, 0x40, 0b1100_0110, 0x20, 0x80]);
+        assert_eq!(high_then_low, low_then_high);
+    }
+
+    #[test]
+    fn staging_one_engine_keeps_the_other() {
+        // Power-on INT_LOWHIGH[2]: high-g hysteresis 2, low-g hysteresis 1
+        let low = LowHighRegisters::new().low_g(&LOW_G).bytes();
+        assert_eq!(low[2], 0b1000_0110);
+        assert_eq!(low[3..], [0x0B, 0xC0]);
+
+        let high = LowHighRegisters::new().high_g(&HIGH_G).bytes();
+        assert_eq!(high[2], 0b1100_0001);
+        assert_eq!(high[..2], [0x07, 0x30]);
+    }
+
+    #[test]
+    fn configs_round_trip() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.configure_low_g(LOW_G).unwrap();
+        imu.configure_high_g(HIGH_G).unwrap();
+
+        assert_eq!(imu.iface.reg(Register::INT_LOWHIGH_2), 0b1100_0110);
+        assert_eq!(imu.get_low_g_config().unwrap(), LOW_G);
+        assert_eq!(imu.get_high_g_config().unwrap(), HIGH_G);
+        assert_eq!(
+            imu.read_lowhigh_registers().unwrap(),
+            LowHighRegisters::new().low_g(&LOW_G).high_g(&HIGH_G)
+        );
+    }
+
+    /// Set a high-g threshold in mg, run `f`, then switch to ±8 g and
+    /// return the raw threshold
+    fn threshold_after_range_change<F>(f: F) -> u8
+    where
+        F: FnOnce(&mut Bmi160<Mock>),
+    {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.set_high_g_threshold_mg(500.0).unwrap();
+        f(&mut imu);
+
+        imu.apply_accel_config(AccelConfig {
+            range: AccelRange::G8,
+            ..AccelConfig::default()
+        })
+        .unwrap();
+        imu.iface.reg(Register::INT_LOWHIGH_4)
+    }
+
+    #[test]
+    fn mg_threshold_rescaled_on_range_change() {
+        // 500 mg is 64 steps of 7.81 mg at ±2 g and 16 steps at ±8 g
+        assert_eq!(threshold_after_range_change(|_| {}), 16);
+    }
+
+    #[test]
+    fn raw_writes_drop_mg_threshold() {
+        let registers = LowHighRegisters::new().high_g(&HIGH_G);
+        let raw = threshold_after_range_change(|imu| {
+            imu.write_lowhigh_registers(registers).unwrap();
+        });
+        assert_eq!(raw, HIGH_G.threshold);
+
+        let raw = threshold_after_range_change(|imu| imu.configure_high_g(HIGH_G).unwrap());
+        assert_eq!(raw, HIGH_G.threshold);
+    }
+
+    #[test]
+    fn high_g_axes() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.enable_interrupts(InterruptEnable::LOW_G).unwrap();
+        let axes = Axes {
+            x: true,
+            y: false,
+            z: true,
+        };
+        imu.configure_high_g(HighGConfig { axes, ..HIGH_G })
+            .unwrap();
+
+        let int_en_1 = imu.iface.regs[Register::INT_EN as usize + 1];
+        assert_eq!(int_en_1 & 0b111, 0b101);
+        // Low-g (INT_EN_1 bit 3) is left alone
+        assert_ne!(int_en_1 & 0b1000, 0);
+        assert_eq!(imu.get_high_g_config().unwrap().axes, axes);
+
+        imu.disable_high_g().unwrap();
+        assert_eq!(imu.get_high_g_config().unwrap().axes, Axes::NONE);
+    }
+
+    #[test]
+    fn high_g_event_from_status() {
+        // INT_STATUS_1 `high_g_int`; INT_STATUS_3 `high_first_y` and
+        // `high_sign`
+        let status = InterruptStatus::from_bytes(0, [0, 0b0000_0100, 0, 0b0000_1010]);
+        assert_eq!(
+            HighGEvent::from_status(&status),
+            Some(HighGEvent {
+                axis: Some(Axis::Y),
+                direction: Direction::Negative,
+            })
+        );
+
+        let status = InterruptStatus::from_bytes(0, [0, 0b0000_0100, 0, 0b0000_0100]);
+        assert_eq!(
+            HighGEvent::from_status(&status),
+            Some(HighGEvent {
+                axis: Some(Axis::Z),
+                direction: Direction::Positive,
+            })
+        );
+
+        // First axis bits without the interrupt flag
+        let status = InterruptStatus::from_bytes(0, [0, 0, 0, 0b0000_0001]);
+        assert_eq!(HighGEvent::from_status(&status), None);
+    }
+
+    #[test]
+    fn free_fall_steps() {
+        let config = LowGConfig::free_fall(400.0, 100.0);
+        assert_eq!(config.threshold, 51);
+        assert_eq!(config.duration, 39);
+        assert_eq!(config.hysteresis, 1);
+        assert_eq!(config.mode, LowGMode::AxisSumming);
+
+        let shortest = LowGConfig::free_fall(0.0, 0.0);
+        assert_eq!((shortest.threshold, shortest.duration), (0, 0));
+
+        let longest = LowGConfig::free_fall(5000.0, 1000.0);
+        assert_eq!((longest.threshold, longest.duration), (255, 255));
+    }
+}
@@ -0,0 +1,471 @@
+//! Secondary magnetometer interface (MAG_IF)
+
+use crate::config::{ConfigError, InterfaceConfig};
+use crate::interface::Interface;
+use crate::register::{MagBurst, MagOdr, MagPowerMode, Register, SecondaryInterface, StatusFlags};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+/// MAG_IF[1] `mag_manual_en`
+const MAG_IF_MANUAL_EN: u8 = 0b1000_0000;
+
+/// MAG_IF[1] `mag_rd_burst`
+const MAG_IF_RD_BURST_MASK: u8 = 0b0000_0011;
+
+/// MAG_IF[1] `mag_offset`, 4 bits starting at bit 2
+const MAG_IF_OFFSET_SHIFT: u8 = 2;
+
+/// Largest `mag_offset` value
+pub const MAG_READ_OFFSET_MAX: u8 = 0b1111;
+
+/// MAG_CONF `mag_odr`
+const MAG_CONF_ODR_MASK: u8 = 0b0000_1111;
+
+/// How many times STATUS is polled for `mag_man_op` to clear before giving
+/// up. One indirect access takes a few hundred microseconds, well under
+/// this many bus transfers.
+const MAG_MAN_OP_POLLS: u32 = 100;
+
+/// Time in milliseconds for a read loop transfer already in flight to
+/// finish after `mag_manual_en` is set
+const MAG_SETUP_SETTLE_MS: u8 = 2;
+
+/// Indirect access to the registers of a magnetometer behind MAG_IF, handed
+/// to [`SecondaryMag::init`]
+pub trait MagBus {
+    /// Bus error
+    type Error;
+
+    /// Read one magnetometer register
+    fn read_register(&mut self, addr: u8) -> Result<u8, Self::Error>;
+
+    /// Write one magnetometer register
+    fn write_register(&mut self, addr: u8, value: u8) -> Result<(), Self::Error>;
+
+    /// Read consecutive magnetometer registers
+    fn read_registers(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(addr + i as u8)?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the given number of milliseconds
+    fn delay_ms(&mut self, ms: u8);
+}
+
+/// A magnetometer attached to the BMI160 secondary interface.
+///
+/// Describes everything [`Bmi160::setup_mag`] and the read loop need to
+/// drive the part, so magnetometers other than the BMM150 (e.g. AKM
+/// AK09911/AK09912) can be supported outside this crate.
+pub trait SecondaryMag {
+    /// 7-bit I2C address on the secondary interface
+    fn address(&self) -> u8;
+
+    /// Bring the magnetometer up through indirect register access: leave
+    /// suspend, read any calibration data, configure measurement settings
+    fn init<B: MagBus>(&mut self, bus: &mut B) -> Result<(), B::Error>;
+
+    /// Register and value the read loop writes before every read, e.g. to
+    /// trigger a single measurement; `None` if not needed
+    fn trigger(&self) -> Option<(u8, u8)>;
+
+    /// First data register read by the read loop
+    fn data_register(&self) -> u8;
+
+    /// Number of data bytes read per cycle
+    fn burst(&self) -> MagBurst;
+
+    /// Convert the bytes read per cycle to µT, `None` if the sample is
+    /// invalid
+    fn compensate(&self, raw: &[u8]) -> Option<[f32; 3]>;
+}
+
+/// [`MagBus`] over MAG_IF manual mode
+struct MagIf<'a, DI, D> {
+    bmi160: &'a mut Bmi160<DI>,
+    delay: &'a mut D,
+}
+
+impl<'a, DI, D, E> MagBus for MagIf<'a, DI, D>
+where
+    DI: Interface<Error = E>,
+    D: DelayMs<u8>,
+{
+    type Error = Error<E>;
+
+    fn read_register(&mut self, addr: u8) -> Result<u8, Self::Error> {
+        self.bmi160.mag_read_register(addr)
+    }
+
+    fn write_register(&mut self, addr: u8, value: u8) -> Result<(), Self::Error> {
+        self.bmi160.mag_write_register(addr, value)
+    }
+
+    fn read_registers(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bmi160.mag_read_registers(addr, buffer)
+    }
+
+    fn delay_ms(&mut self, ms: u8) {
+        self.delay.delay_ms(ms);
+    }
+}
+
+/// Number of bytes read per cycle for a burst setting
+fn burst_len(burst: MagBurst) -> usize {
+    match burst {
+        MagBurst::Bytes1 => 1,
+        MagBurst::Bytes2 => 2,
+        MagBurst::Bytes6 => 6,
+        MagBurst::Bytes8 => 8,
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Initialize a magnetometer on the secondary interface.
+    ///
+    /// Powers the magnetometer interface, selects it in IF_CONF, sets the
+    /// magnetometer address and runs [`SecondaryMag::init`] in manual mode.
+    /// Then the read loop is set up with the trigger write and data
+    /// register of `mag`. The interface is left in manual mode; call
+    /// [`Bmi160::start_mag_data_mode`] with [`SecondaryMag::data_register`]
+    /// and [`SecondaryMag::burst`] to start the loop.
+    pub fn setup_mag<M, D>(&mut self, mag: &mut M, delay: &mut D) -> Result<(), Error<E>>
+    where
+        M: SecondaryMag,
+        D: DelayMs<u8>,
+    {
+        let interface = self.get_interface_config()?;
+        self.apply_interface_config(InterfaceConfig {
+            secondary: SecondaryInterface::Magnetometer,
+            ..interface
+        })?;
+
+        self.enter_mag_setup_mode(delay)?;
+        self.write_register(Register::MAG_IF, mag.address() << 1)?;
+
+        mag.init(&mut MagIf {
+            bmi160: self,
+            delay,
+        })?;
+
+        if let Some((addr, value)) = mag.trigger() {
+            self.mag_write_register(addr, value)?;
+        }
+        self.mag_read_register(mag.data_register())?;
+
+        Ok(())
+    }
+
+    /// Read the latest sample of `mag` from DATA_MAG and compensate it
+    /// with [`SecondaryMag::compensate`].
+    ///
+    /// Returns `None` if the sample is invalid.
+    pub fn read_mag_compensated<M>(&mut self, mag: &M) -> Result<Option<[f32; 3]>, Error<E>>
+    where
+        M: SecondaryMag,
+    {
+        let mut raw = [0u8; 8];
+        let raw = &mut raw[..burst_len(mag.burst())];
+        self.write_read_register(Register::DATA, raw)?;
+        Ok(mag.compensate(raw))
+    }
+
+    /// Set the rate of the magnetometer read loop (MAG_CONF), independent
+    /// of the accelerometer and gyroscope rates
+    pub fn set_mag_odr(&mut self, odr: MagOdr) -> Result<(), Error<E>> {
+        self.modify_register(Register::MAG_CONF, MAG_CONF_ODR_MASK, odr as u8)
+    }
+
+    /// Read the rate of the magnetometer read loop
+    pub fn get_mag_odr(&mut self) -> Result<MagOdr, Error<E>> {
+        let mag_conf = self.read_register(Register::MAG_CONF)?;
+        MagOdr::from_bits(mag_conf).ok_or(Error::ReservedValue(Register::MAG_CONF, mag_conf))
+    }
+
+    /// Set the trigger-to-read offset of the read loop (`mag_offset`), in
+    /// units of 2.5 ms.
+    ///
+    /// Shifts when the magnetometer is read relative to the accelerometer
+    /// and gyroscope sampling, e.g. to phase-align the three for sensor
+    /// fusion. Fails with [`ConfigError::MagReadOffsetTooLarge`] above
+    /// [`MAG_READ_OFFSET_MAX`].
+    pub fn set_mag_read_offset(&mut self, offset: u8) -> Result<(), Error<E>> {
+        if offset > MAG_READ_OFFSET_MAX {
+            return Err(ConfigError::MagReadOffsetTooLarge.into());
+        }
+
+        self.modify_register(
+            Register::MAG_IF_1,
+            MAG_READ_OFFSET_MAX << MAG_IF_OFFSET_SHIFT,
+            offset << MAG_IF_OFFSET_SHIFT,
+        )
+    }
+
+    /// Read the trigger-to-read offset of the read loop, in units of 2.5 ms
+    pub fn get_mag_read_offset(&mut self) -> Result<u8, Error<E>> {
+        Ok((self.read_register(Register::MAG_IF_1)? >> MAG_IF_OFFSET_SHIFT) & MAG_READ_OFFSET_MAX)
+    }
+
+    /// Enable or disable manual (setup) mode on the magnetometer interface
+    /// (`mag_manual_en`).
+    ///
+    /// Manual mode is required for [`Bmi160::mag_read_register`] and
+    /// [`Bmi160::mag_write_register`] and stops the automatic read loop.
+    /// This only flips the bit; prefer [`Bmi160::enter_mag_setup_mode`] and
+    /// [`Bmi160::enter_mag_data_mode`], which wait for transfers in flight.
+    pub fn set_mag_manual_mode(&mut self, enabled: bool) -> Result<(), Error<E>> {
+        let value = if enabled { MAG_IF_MANUAL_EN } else { 0 };
+        self.modify_register(Register::MAG_IF_1, MAG_IF_MANUAL_EN, value)
+    }
+
+    /// Is the magnetometer interface in manual (setup) mode?
+    pub fn mag_manual_mode(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.read_register(Register::MAG_IF_1)? & MAG_IF_MANUAL_EN != 0)
+    }
+
+    /// Stop the automatic read loop and switch to setup (manual) mode.
+    ///
+    /// Powers the magnetometer interface, which indirect accesses need.
+    /// Then, unless the interface is already in setup mode, sets
+    /// `mag_manual_en` and waits for a read loop transfer still in flight
+    /// to finish, so the following indirect accesses don't collide with it.
+    /// Fails with [`Error::Timeout`] if a manual operation never completes.
+    pub fn enter_mag_setup_mode<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.set_mag_power_mode(MagPowerMode::Normal, delay)?;
+
+        if self.mag_manual_mode()? {
+            return Ok(());
+        }
+
+        self.set_mag_manual_mode(true)?;
+        delay.delay_ms(MAG_SETUP_SETTLE_MS);
+        self.wait_mag_manual_op()
+    }
+
+    /// Leave setup mode and resume the automatic read loop with the
+    /// current read address and burst length.
+    ///
+    /// Waits for the last indirect access to finish before clearing
+    /// `mag_manual_en`, since clearing it mid-transfer leaves the
+    /// magnetometer in an undefined state.
+    pub fn enter_mag_data_mode(&mut self) -> Result<(), Error<E>> {
+        self.wait_mag_manual_op()?;
+        self.set_mag_manual_mode(false)
+    }
+
+    /// Run `f` with the magnetometer interface in setup mode, then restore
+    /// the previous mode.
+    ///
+    /// Use this to change magnetometer settings at runtime, e.g. the
+    /// repetitions of a [`Bmm150`](crate::Bmm150), without stopping the
+    /// read loop for good. The indirect accesses made by `f` overwrite
+    /// MAG_IF_1..4, so the read loop's read address, burst length and write
+    /// address and data are saved first and restored before it resumes. The
+    /// read loop is resumed even if `f` fails, and the error of `f` takes
+    /// precedence.
+    pub fn reconfigure_mag<D, F, T>(&mut self, delay: &mut D, f: F) -> Result<T, Error<E>>
+    where
+        D: DelayMs<u8>,
+        F: FnOnce(&mut Self) -> Result<T, Error<E>>,
+    {
+        let data_mode = !self.mag_manual_mode()?;
+        self.enter_mag_setup_mode(delay)?;
+
+        let mut mag_if = [0u8; 4];
+        self.write_read_register(Register::MAG_IF_1, &mut mag_if)?;
+
+        let result = f(self);
+
+        if data_mode {
+            let resumed = self.resume_mag_read_loop(mag_if);
+            return result.and_then(|value| resumed.map(|_| value));
+        }
+
+        result
+    }
+
+    /// Write back MAG_IF_1..4 as saved by [`Bmi160::reconfigure_mag`] and
+    /// leave setup mode
+    fn resume_mag_read_loop(&mut self, mag_if: [u8; 4]) -> Result<(), Error<E>> {
+        // Setting the write address triggers a write and the read address a
+        // read, the same accesses the loop makes on every cycle
+        self.write_register(Register::MAG_IF_4, mag_if[3])?;
+        self.write_register(Register::MAG_IF_3, mag_if[2])?;
+        self.wait_mag_manual_op()?;
+        self.write_register(Register::MAG_IF_2, mag_if[1])?;
+        self.wait_mag_manual_op()?;
+
+        self.write_register(Register::MAG_IF_1, mag_if[0] | MAG_IF_MANUAL_EN)?;
+        self.enter_mag_data_mode()
+    }
+
+    /// Start the automatic read loop (data mode).
+    ///
+    /// Sets the magnetometer register the loop reads from and the burst
+    /// length, then leaves setup mode with [`Bmi160::enter_mag_data_mode`].
+    /// From then on the BMI160 reads `burst` bytes from `read_addr` at the
+    /// MAG_CONF rate into DATA_MAG (and the FIFO if enabled), first writing
+    /// the MAG_IF write data to the write address if one was set up in
+    /// manual mode. Changing the read address under a running loop corrupts
+    /// it, so this fails with [`ConfigError::MagNotInSetupMode`] unless the
+    /// interface is in setup mode.
+    pub fn start_mag_data_mode(&mut self, read_addr: u8, burst: MagBurst) -> Result<(), Error<E>> {
+        self.ensure_mag_setup_mode()?;
+        let mag_if_1 = self.read_register(Register::MAG_IF_1)?;
+        self.write_registers(
+            Register::MAG_IF_1,
+            &[(mag_if_1 & !MAG_IF_RD_BURST_MASK) | burst as u8, read_addr],
+        )?;
+        self.enter_mag_data_mode()
+    }
+
+    /// Read the burst length of the read loop
+    pub fn get_mag_burst(&mut self) -> Result<MagBurst, Error<E>> {
+        self.read_register(Register::MAG_IF_1)
+            .map(MagBurst::from_bits)
+    }
+
+    /// Read a register of the attached magnetometer through MAG_IF.
+    ///
+    /// The magnetometer interface must be powered and in manual mode. Sets
+    /// the read address, waits for STATUS `mag_man_op` to clear and returns
+    /// the first byte the BMI160 copied into the DATA_MAG registers. Fails
+    /// with [`Error::Timeout`] if the transfer doesn't finish and with
+    /// [`ConfigError::MagNotInSetupMode`] while the read loop is running.
+    pub fn mag_read_register(&mut self, addr: u8) -> Result<u8, Error<E>> {
+        self.ensure_mag_setup_mode()?;
+        self.write_register(Register::MAG_IF_2, addr)?;
+        self.wait_mag_manual_op()?;
+        self.read_register(Register::DATA)
+    }
+
+    /// Write a register of the attached magnetometer through MAG_IF.
+    ///
+    /// The magnetometer interface must be powered and in manual mode. The
+    /// write is triggered by setting the write address and is complete once
+    /// STATUS `mag_man_op` clears; fails with [`Error::Timeout`] otherwise,
+    /// and with [`ConfigError::MagNotInSetupMode`] while the read loop is
+    /// running.
+    pub fn mag_write_register(&mut self, addr: u8, value: u8) -> Result<(), Error<E>> {
+        self.ensure_mag_setup_mode()?;
+        self.write_register(Register::MAG_IF_4, value)?;
+        self.write_register(Register::MAG_IF_3, addr)?;
+        self.wait_mag_manual_op()
+    }
+
+    /// Read consecutive registers of the attached magnetometer in manual
+    /// mode, eight at a time. Leaves the burst length at 8 bytes.
+    pub(crate) fn mag_read_registers(
+        &mut self,
+        addr: u8,
+        buffer: &mut [u8],
+    ) -> Result<(), Error<E>> {
+        self.ensure_mag_setup_mode()?;
+        self.modify_register(
+            Register::MAG_IF_1,
+            MAG_IF_RD_BURST_MASK,
+            MagBurst::Bytes8 as u8,
+        )?;
+
+        for (i, chunk) in buffer.chunks_mut(8).enumerate() {
+            self.write_register(Register::MAG_IF_2, addr + 8 * i as u8)?;
+            self.wait_mag_manual_op()?;
+            self.write_read_register(Register::DATA, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Fail unless `mag_manual_en` is set
+    fn ensure_mag_setup_mode(&mut self) -> Result<(), Error<E>> {
+        if self.mag_manual_mode()? {
+            Ok(())
+        } else {
+            Err(ConfigError::MagNotInSetupMode.into())
+        }
+    }
+
+    /// Poll STATUS until `mag_man_op` clears
+    fn wait_mag_manual_op(&mut self) -> Result<(), Error<E>> {
+        for _ in 0..MAG_MAN_OP_POLLS {
+            if !self.get_status()?.contains(StatusFlags::MAG_MAN_OP) {
+                return Ok(());
+            }
+        }
+
+        Err(Error::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Mock, NoDelay};
+
+    /// BMM150 read loop: 8 bytes from DATAX_L, writing forced mode to
+    /// OP_MODE before every read
+    const READ_LOOP: [u8; 4] = [MagBurst::Bytes8 as u8, 0x42, 0x4C, 0x02];
+
+    #[test]
+    fn reconfigure_mag_restores_read_loop() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.iface.regs[Register::MAG_IF_1 as usize..][..4].copy_from_slice(&READ_LOOP);
+
+        let value = imu
+            .reconfigure_mag(&mut NoDelay, |imu| {
+                assert!(imu.mag_manual_mode()?);
+                imu.mag_write_register(0x51, 0x04)?;
+                let mut trim = [0u8; 10];
+                imu.mag_read_registers(0x5D, &mut trim)?;
+                imu.mag_read_register(0x40)
+            })
+            .unwrap();
+        assert_eq!(value, imu.iface.reg(Register::DATA));
+
+        assert_eq!(
+            imu.iface.regs[Register::MAG_IF_1 as usize..][..4],
+            READ_LOOP
+        );
+        assert!(!imu.mag_manual_mode().unwrap());
+    }
+
+    #[test]
+    fn reconfigure_mag_restores_read_loop_on_error() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.iface.regs[Register::MAG_IF_1 as usize..][..4].copy_from_slice(&READ_LOOP);
+
+        let result: Result<(), _> = imu.reconfigure_mag(&mut NoDelay, |imu| {
+            imu.mag_write_register(0x51, 0x04)?;
+            Err(Error::Timeout)
+        });
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        assert_eq!(
+            imu.iface.regs[Register::MAG_IF_1 as usize..][..4],
+            READ_LOOP
+        );
+    }
+
+    #[test]
+    fn reconfigure_mag_in_setup_mode_keeps_changes() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.iface.regs[Register::MAG_IF_1 as usize..][..4].copy_from_slice(&READ_LOOP);
+        imu.set_mag_manual_mode(true).unwrap();
+
+        imu.reconfigure_mag(&mut NoDelay, |imu| imu.mag_write_register(0x51, 0x04))
+            .unwrap();
+
+        assert!(imu.mag_manual_mode().unwrap());
+        assert_eq!(imu.iface.reg(Register::MAG_IF_3), 0x51);
+        assert_eq!(imu.iface.reg(Register::MAG_IF_4), 0x04);
+    }
+}
@@ -0,0 +1,232 @@
+//! Register-level BMI160 model for unit tests
+
+use crate::interface::Interface;
+use crate::register::{Cmd, ConfFlags, Register, StatusFlags};
+use hal::blocking::delay::{DelayMs, DelayUs};
+use hal::digital::v2::InputPin;
+use std::vec::Vec;
+
+/// ACC_CONF `acc_us`
+const ACC_US: u8 = 0b1000_0000;
+
+/// First accelerometer data register (ACC_X LSB)
+const ACC_DATA: usize = 0x12;
+
+/// INT_EN_1 `int_fwm_en` and INT_STATUS_1 `fwm_int`
+const FWM: u8 = 0b0100_0000;
+
+/// PMU_STATUS `acc_pmu_status` values
+const ACC_PMU_NORMAL: u8 = 0b01;
+const ACC_PMU_LOW_POWER: u8 = 0b10;
+
+/// Simulated device behind the [`Interface`] trait.
+///
+/// Registers are plain memory. Commands update PMU_STATUS and STATUS the
+/// way the device does, FIFO_DATA streams from `fifo`, and ACC_CONF is
+/// checked against the accelerometer power mode like ERR_REG does.
+pub(crate) struct Mock {
+    pub regs: [u8; 128],
+    pub fifo: Vec<u8>,
+    /// FIFO frame size. A frame only partially read stays in the FIFO and
+    /// is sent again in full on the next read, like on the device; `0`
+    /// consumes every byte read.
+    pub fifo_frame_len: usize,
+    /// FIFO contents once the next `fifo_flush` command arrives, standing
+    /// in for data sampled after the flush
+    pub fifo_after_flush: Vec<u8>,
+    pub writes: Vec<(u8, u8)>,
+    pub reads: Vec<(u8, usize)>,
+    /// ACC_CONF and the accelerometer power mode were ever incompatible
+    pub invalid_acc_conf: bool,
+    /// OFFSET registers loaded by `start_foc`
+    pub foc_offsets: [u8; 7],
+    /// Number of `prog_nvm` commands accepted
+    pub nvm_writes: usize,
+    /// Accelerometer output in LSB during a positive self-test deflection;
+    /// the negative deflection reads back negated
+    pub self_test_deflection: [i16; 3],
+}
+
+impl Mock {
+    pub fn new() -> Self {
+        let mut regs = [0u8; 128];
+        regs[Register::CHIP_ID as usize] = crate::CHIP_ID;
+        regs[Register::STATUS as usize] = StatusFlags::NVM_RDY.bits();
+        regs[Register::ACC_CONF as usize] = 0x28;
+        regs[Register::ACC_RANGE as usize] = 0x03;
+        regs[Register::GYR_CONF as usize] = 0x28;
+        regs[Register::MAG_CONF as usize] = 0x0B;
+
+        Mock {
+            regs,
+            fifo: Vec::new(),
+            fifo_frame_len: 0,
+            fifo_after_flush: Vec::new(),
+            writes: Vec::new(),
+            reads: Vec::new(),
+            invalid_acc_conf: false,
+            foc_offsets: [0; 7],
+            nvm_writes: 0,
+            self_test_deflection: [0; 3],
+        }
+    }
+
+    pub fn reg(&self, register: Register) -> u8 {
+        self.regs[register as usize]
+    }
+
+    pub fn set_reg(&mut self, register: Register, value: u8) {
+        self.regs[register as usize] = value;
+    }
+
+    /// Accelerometer power mode bits from PMU_STATUS
+    pub fn acc_pmu(&self) -> u8 {
+        (self.reg(Register::PMU_STATUS) >> 4) & 0b11
+    }
+
+    fn set_pmu(&mut self, shift: u8, mode: u8) {
+        let pmu = &mut self.regs[Register::PMU_STATUS as usize];
+        *pmu = (*pmu & !(0b11 << shift)) | mode << shift;
+    }
+
+    fn check_acc_conf(&mut self) {
+        let undersampling = self.reg(Register::ACC_CONF) & ACC_US != 0;
+        match self.acc_pmu() {
+            ACC_PMU_NORMAL if undersampling => self.invalid_acc_conf = true,
+            ACC_PMU_LOW_POWER if !undersampling => self.invalid_acc_conf = true,
+            _ => {}
+        }
+    }
+
+    fn self_test(&mut self, value: u8) {
+        if value & 0b0001 == 0 {
+            return;
+        }
+
+        let sign = if value & 0b0100 != 0 { 1 } else { -1 };
+        for (axis, &lsb) in self.self_test_deflection.iter().enumerate() {
+            let start = ACC_DATA + 2 * axis;
+            self.regs[start..start + 2].copy_from_slice(&(sign * lsb).to_le_bytes());
+        }
+    }
+
+    fn command(&mut self, cmd: u8) {
+        match cmd {
+            c if c == Cmd::START_OFC as u8 => {
+                let offsets = self.foc_offsets;
+                self.regs[Register::OFFSET as usize..Register::OFFSET as usize + 6]
+                    .copy_from_slice(&offsets[..6]);
+                let offset_6 = &mut self.regs[Register::OFFSET_6 as usize];
+                *offset_6 = (*offset_6 & 0b1100_0000) | (offsets[6] & 0b0011_1111);
+                self.regs[Register::STATUS as usize] |= StatusFlags::FOC_RDY.bits();
+            }
+            c if c == Cmd::PROG_NVM as u8
+                && self.reg(Register::CONF) & ConfFlags::NVM_PROG_EN.bits() != 0 =>
+            {
+                self.nvm_writes += 1;
+            }
+            c if c == Cmd::FIFO_FLUSH as u8 => {
+                self.fifo = core::mem::take(&mut self.fifo_after_flush);
+            }
+            c if c == Cmd::STEP_CNT_CLR as u8 => {
+                self.set_reg(Register::STEP_CNT, 0);
+                self.regs[Register::STEP_CNT as usize + 1] = 0;
+            }
+            0b0001_0000..=0b0001_0010 => {
+                self.set_pmu(4, cmd & 0b11);
+                self.check_acc_conf();
+            }
+            0b0001_0100..=0b0001_0111 => self.set_pmu(2, cmd & 0b11),
+            0b0001_1000..=0b0001_1010 => self.set_pmu(0, cmd & 0b11),
+            _ => {}
+        }
+    }
+}
+
+impl Interface for Mock {
+    type Error = ();
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        let start = bytes[0];
+
+        for (i, &value) in bytes[1..].iter().enumerate() {
+            let addr = start + i as u8;
+            self.writes.push((addr, value));
+
+            if addr == Register::CMD as u8 {
+                self.command(value);
+            } else {
+                self.regs[usize::from(addr)] = value;
+                if addr == Register::ACC_CONF as u8 {
+                    self.check_acc_conf();
+                } else if addr == Register::SELF_TEST as u8 {
+                    self.self_test(value);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), ()> {
+        self.reads.push((register, buffer.len()));
+
+        if register == Register::FIFO_DATA as u8 {
+            for (i, byte) in buffer.iter_mut().enumerate() {
+                *byte = self.fifo.get(i).copied().unwrap_or(0x80);
+            }
+
+            let mut consumed = buffer.len().min(self.fifo.len());
+            if self.fifo_frame_len != 0 {
+                consumed -= consumed % self.fifo_frame_len;
+            }
+            self.fifo.drain(..consumed);
+            return Ok(());
+        }
+
+        if register == Register::FIFO_LENGTH as u8 {
+            let len = self.fifo.len() as u16;
+            self.regs[Register::FIFO_LENGTH as usize..][..2].copy_from_slice(&len.to_le_bytes());
+        }
+
+        let watermark = usize::from(self.reg(Register::FIFO_CONFIG)) * 4;
+        let fwm =
+            self.regs[Register::INT_EN as usize + 1] & FWM != 0 && self.fifo.len() >= watermark;
+        let int_status_1 = &mut self.regs[Register::INT_STATUS as usize + 1];
+        *int_status_1 = if fwm {
+            *int_status_1 | FWM
+        } else {
+            *int_status_1 & !FWM
+        };
+
+        let start = usize::from(register);
+        buffer.copy_from_slice(&self.regs[start..start + buffer.len()]);
+        Ok(())
+    }
+}
+
+/// Delay that returns immediately
+pub(crate) struct NoDelay;
+
+impl DelayMs<u8> for NoDelay {
+    fn delay_ms(&mut self, _ms: u8) {}
+}
+
+impl DelayUs<u8> for NoDelay {
+    fn delay_us(&mut self, _us: u8) {}
+}
+
+/// Host GPIO stuck at one level
+pub(crate) struct Pin(pub bool);
+
+impl InputPin for Pin {
+    type Error = ();
+
+    fn is_high(&self) -> Result<bool, ()> {
+        Ok(self.0)
+    }
+
+    fn is_low(&self) -> Result<bool, ()> {
+        Ok(!self.0)
+    }
+}
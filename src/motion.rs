@@ -0,0 +1,589 @@
+//! Motion detection engines (INT_MOTION)
+
+use crate::interface::Interface;
+use crate::interrupt::{mg_to_steps, Axes};
+use crate::register::{AccelRange, InterruptEnable, Register, SigMotionProof, SigMotionSkip};
+use crate::{Bmi160, Error};
+
+/// INT_MOTION[0] `int_anym_dur`
+const INT_MOTION_ANYM_DUR_MASK: u8 = 0b0000_0011;
+
+/// INT_MOTION[0] `int_slo_no_mot_dur`, 6 bits starting at bit 2
+const INT_MOTION_SLO_NO_MOT_DUR_SHIFT: u8 = 2;
+
+/// INT_MOTION[3] `int_no_mot_sel`
+const INT_MOTION_NO_MOT_SEL: u8 = 0b0000_0001;
+
+/// INT_MOTION[3] `int_sig_mot_sel`
+const INT_MOTION_SIG_MOT_SEL: u8 = 0b0000_0010;
+
+/// INT_MOTION[3] `int_sig_mot_skip`, 2 bits starting at bit 2
+const INT_MOTION_SIG_MOT_SKIP_SHIFT: u8 = 2;
+
+/// INT_MOTION[3] `int_sig_mot_proof`, 2 bits starting at bit 4
+const INT_MOTION_SIG_MOT_PROOF_SHIFT: u8 = 4;
+
+/// INT_MOTION[3] bits owned by significant motion
+const INT_MOTION_SIG_MOT_MASK: u8 = 0b0011_1110;
+
+/// Largest any-motion duration, in samples
+const ANY_MOTION_MAX_DURATION: u8 = 4;
+
+/// Motion threshold step in mg per g of accelerometer full scale
+const MOTION_TH_MG_PER_G: f32 = 1000.0 / 512.0;
+
+/// Motion threshold step in mg for the given range
+pub(crate) fn motion_threshold_step_mg(range: AccelRange) -> f32 {
+    MOTION_TH_MG_PER_G * range.g()
+}
+
+/// Any-motion (slope) detection configuration
+///
+/// The interrupt fires once the slope between successive samples exceeds
+/// `threshold` on an enabled axis for `duration` consecutive samples.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AnyMotionConfig {
+    /// Raw slope threshold (`int_anymo_th`). One step is 3.91 mg at ±2 g
+    /// and scales with the accelerometer range.
+    pub threshold: u8,
+
+    /// Consecutive samples above threshold needed to fire, 1-4
+    pub duration: u8,
+
+    /// Axes to watch
+    pub axes: Axes,
+}
+
+impl Default for AnyMotionConfig {
+    /// Power-on reset values, all axes enabled
+    fn default() -> Self {
+        AnyMotionConfig {
+            threshold: 0x14,
+            duration: 1,
+            axes: Axes::ALL,
+        }
+    }
+}
+
+impl AnyMotionConfig {
+    /// Threshold in mg for the given accelerometer range
+    pub fn threshold_mg(&self, range: AccelRange) -> f32 {
+        f32::from(self.threshold) * motion_threshold_step_mg(range)
+    }
+
+    /// Set the threshold in mg for the given accelerometer range, rounded
+    /// to the nearest step
+    pub fn with_threshold_mg(mut self, mg: f32, range: AccelRange) -> Self {
+        self.threshold = mg_to_steps(mg, motion_threshold_step_mg(range), u8::MAX);
+        self
+    }
+}
+
+/// Significant motion detection configuration
+///
+/// Significant motion reuses the any-motion engine: after a first motion
+/// above `threshold`, it waits out `skip` and fires if motion is seen again
+/// within `proof`. Typical for wake-on-real-movement use cases that must
+/// ignore a single bump.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SignificantMotionConfig {
+    /// Raw slope threshold (`int_anymo_th`), shared with any-motion. One
+    /// step is 3.91 mg at ±2 g and scales with the accelerometer range.
+    pub threshold: u8,
+
+    /// Skip time
+    pub skip: SigMotionSkip,
+
+    /// Proof time
+    pub proof: SigMotionProof,
+}
+
+impl Default for SignificantMotionConfig {
+    /// Power-on reset values
+    fn default() -> Self {
+        SignificantMotionConfig {
+            threshold: 0x14,
+            skip: SigMotionSkip::default(),
+            proof: SigMotionProof::default(),
+        }
+    }
+}
+
+impl SignificantMotionConfig {
+    /// Threshold in mg for the given accelerometer range
+    pub fn threshold_mg(&self, range: AccelRange) -> f32 {
+        f32::from(self.threshold) * motion_threshold_step_mg(range)
+    }
+
+    /// Set the threshold in mg for the given accelerometer range, rounded
+    /// to the nearest step
+    pub fn with_threshold_mg(mut self, mg: f32, range: AccelRange) -> Self {
+        self.threshold = mg_to_steps(mg, motion_threshold_step_mg(range), u8::MAX);
+        self
+    }
+}
+
+/// Mode of the no-motion / slow-motion engine (INT_MOTION `int_no_mot_sel`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum NoMotionMode {
+    /// Slow-motion: fire while the slope stays above threshold for
+    /// `duration + 1` consecutive samples (default)
+    #[default]
+    SlowMotion,
+
+    /// No-motion: fire once the slope stays below threshold for the
+    /// duration given by the non-linear `duration` encoding
+    NoMotion,
+}
+
+/// No-motion / slow-motion detection configuration
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct NoMotionConfig {
+    /// No-motion or slow-motion
+    pub mode: NoMotionMode,
+
+    /// Raw slope threshold (`int_slo_no_mot_th`). One step is 3.91 mg at
+    /// ±2 g and scales with the accelerometer range.
+    pub threshold: u8,
+
+    /// Raw duration (`int_slo_no_mot_dur`, 6 bits). In slow-motion mode
+    /// only bits 1:0 are used, as a sample count minus one. In no-motion
+    /// mode see [`NoMotionConfig::duration_secs`].
+    pub duration: u8,
+
+    /// Axes to watch
+    pub axes: Axes,
+}
+
+impl Default for NoMotionConfig {
+    /// Power-on reset values, all axes enabled
+    fn default() -> Self {
+        NoMotionConfig {
+            mode: NoMotionMode::default(),
+            threshold: 0x14,
+            duration: 0,
+            axes: Axes::ALL,
+        }
+    }
+}
+
+impl NoMotionConfig {
+    /// No-motion configuration firing after at least `secs` seconds below
+    /// `threshold`. The duration is rounded up to the next encodable step
+    /// and saturates at 430.08 s.
+    pub fn no_motion(threshold: u8, secs: f32, axes: Axes) -> Self {
+        // Compare in the 10 ms units the encoding is exact in, with half a
+        // unit of slack, so durations taken from the datasheet table aren't
+        // rounded up a step by float error
+        let target = secs * 100.0;
+        let duration = (0..64)
+            .find(|&raw| f32::from(no_motion_duration_10ms(raw)) + 0.5 >= target)
+            .unwrap_or(63);
+
+        NoMotionConfig {
+            mode: NoMotionMode::NoMotion,
+            threshold,
+            duration,
+            axes,
+        }
+    }
+
+    /// Threshold in mg for the given accelerometer range
+    pub fn threshold_mg(&self, range: AccelRange) -> f32 {
+        f32::from(self.threshold) * motion_threshold_step_mg(range)
+    }
+
+    /// Set the threshold in mg for the given accelerometer range, rounded
+    /// to the nearest step
+    pub fn with_threshold_mg(mut self, mg: f32, range: AccelRange) -> Self {
+        self.threshold = mg_to_steps(mg, motion_threshold_step_mg(range), u8::MAX);
+        self
+    }
+
+    /// No-motion delay in seconds, `None` in slow-motion mode
+    pub fn duration_secs(&self) -> Option<f32> {
+        match self.mode {
+            NoMotionMode::NoMotion => Some(no_motion_duration_secs(self.duration)),
+            NoMotionMode::SlowMotion => None,
+        }
+    }
+
+    /// Consecutive samples needed in slow-motion mode, `None` in no-motion
+    /// mode
+    pub fn duration_samples(&self) -> Option<u8> {
+        match self.mode {
+            NoMotionMode::SlowMotion => Some((self.duration & 0b11) + 1),
+            NoMotionMode::NoMotion => None,
+        }
+    }
+}
+
+/// Decode the no-motion `int_slo_no_mot_dur` field into seconds
+fn no_motion_duration_secs(raw: u8) -> f32 {
+    f32::from(no_motion_duration_10ms(raw)) / 100.0
+}
+
+/// Decode the no-motion `int_slo_no_mot_dur` field into units of 10 ms
+fn no_motion_duration_10ms(raw: u8) -> u16 {
+    let raw = u16::from(raw & 0b11_1111);
+
+    if raw & 0b10_0000 != 0 {
+        // 112.64 s to 430.08 s in 10.24 s steps
+        ((raw & 0b1_1111) + 11) * 1024
+    } else if raw & 0b01_0000 != 0 {
+        // 25.6 s to 102.4 s in 5.12 s steps
+        ((raw & 0b1111) + 5) * 512
+    } else {
+        // 1.28 s to 20.48 s in 1.28 s steps
+        (raw + 1) * 128
+    }
+}
+
+/// Staged contents of the four INT_MOTION registers (0x5F-0x62).
+///
+/// Any-motion, no-motion and significant motion share these registers, and
+/// several fields straddle engine boundaries. Stage all changes here and
+/// commit them with [`Bmi160::write_motion_registers`], a single burst
+/// write, so the engines never see a half-updated configuration.
+///
+/// ```ignore
+/// let regs = bmi160
+///     .read_motion_registers()?
+///     .any_motion(&any_motion)
+///     .no_motion(&no_motion);
+/// bmi160.write_motion_registers(regs)?;
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MotionRegisters {
+    bytes: [u8; 4],
+}
+
+impl Default for MotionRegisters {
+    /// Power-on reset values
+    fn default() -> Self {
+        MotionRegisters {
+            bytes: [0x00, 0x14, 0x14, 0x14],
+        }
+    }
+}
+
+impl MotionRegisters {
+    /// Power-on reset values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage an any-motion configuration, selecting any-motion over
+    /// significant motion. `config.axes` is not part of INT_MOTION.
+    pub fn any_motion(mut self, config: &AnyMotionConfig) -> Self {
+        let duration = config.duration.clamp(1, ANY_MOTION_MAX_DURATION) - 1;
+
+        self.bytes[0] = (self.bytes[0] & !INT_MOTION_ANYM_DUR_MASK) | duration;
+        self.bytes[1] = config.threshold;
+        self.bytes[3] &= !INT_MOTION_SIG_MOT_SEL;
+        self
+    }
+
+    /// Stage a no-motion / slow-motion configuration. `config.axes` is not
+    /// part of INT_MOTION.
+    pub fn no_motion(mut self, config: &NoMotionConfig) -> Self {
+        let select = match config.mode {
+            NoMotionMode::NoMotion => INT_MOTION_NO_MOT_SEL,
+            NoMotionMode::SlowMotion => 0,
+        };
+
+        self.bytes[0] = (self.bytes[0] & INT_MOTION_ANYM_DUR_MASK)
+            | config.duration << INT_MOTION_SLO_NO_MOT_DUR_SHIFT;
+        self.bytes[2] = config.threshold;
+        self.bytes[3] = (self.bytes[3] & !INT_MOTION_NO_MOT_SEL) | select;
+        self
+    }
+
+    /// Stage a significant motion configuration, selecting significant
+    /// motion over any-motion.
+    pub fn significant_motion(mut self, config: &SignificantMotionConfig) -> Self {
+        let bits = INT_MOTION_SIG_MOT_SEL
+            | (config.skip as u8) << INT_MOTION_SIG_MOT_SKIP_SHIFT
+            | (config.proof as u8) << INT_MOTION_SIG_MOT_PROOF_SHIFT;
+
+        self.bytes[1] = config.threshold;
+        self.bytes[3] = (self.bytes[3] & !INT_MOTION_SIG_MOT_MASK) | bits;
+        self
+    }
+
+    /// Raw INT_MOTION[0..3] values
+    pub fn bytes(&self) -> [u8; 4] {
+        self.bytes
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Commit staged INT_MOTION contents with a single burst write.
+    ///
+    /// The any-motion and no-motion thresholds are taken as raw values, so
+    /// thresholds previously set in mg are no longer rescaled on range
+    /// changes.
+    pub fn write_motion_registers(&mut self, registers: MotionRegisters) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_MOTION, &registers.bytes)?;
+        self.mg_thresholds.any_motion = None;
+        self.mg_thresholds.no_motion = None;
+        Ok(())
+    }
+
+    /// Set the any-motion (and significant motion) threshold in mg.
+    ///
+    /// Converted using the driver's cached accelerometer range and
+    /// rewritten whenever [`Bmi160::apply_accel_config`] changes the range,
+    /// so the sensitivity stays the same.
+    pub fn set_any_motion_threshold_mg(&mut self, mg: f32) -> Result<(), Error<E>> {
+        let steps = mg_to_steps(mg, motion_threshold_step_mg(self.accel_range), u8::MAX);
+        self.write_register(Register::INT_MOTION_1, steps)?;
+        self.mg_thresholds.any_motion = Some(mg);
+        Ok(())
+    }
+
+    /// Set the no-motion / slow-motion threshold in mg.
+    ///
+    /// Converted using the driver's cached accelerometer range and
+    /// rewritten whenever [`Bmi160::apply_accel_config`] changes the range,
+    /// so the sensitivity stays the same.
+    pub fn set_no_motion_threshold_mg(&mut self, mg: f32) -> Result<(), Error<E>> {
+        let steps = mg_to_steps(mg, motion_threshold_step_mg(self.accel_range), u8::MAX);
+        self.write_register(Register::INT_MOTION_2, steps)?;
+        self.mg_thresholds.no_motion = Some(mg);
+        Ok(())
+    }
+
+    /// Read the current INT_MOTION contents, to stage changes on top of.
+    pub fn read_motion_registers(&mut self) -> Result<MotionRegisters, Error<E>> {
+        let mut bytes = [0u8; 4];
+        self.write_read_register(Register::INT_MOTION, &mut bytes)?;
+        Ok(MotionRegisters { bytes })
+    }
+
+    /// Configure and enable any-motion detection.
+    ///
+    /// Selects any-motion over significant motion, writes the threshold and
+    /// duration, and enables exactly the axes in `config.axes`. Route the
+    /// interrupt with [`Bmi160::map_interrupt`] and
+    /// [`InterruptSource::AnyMotion`](crate::InterruptSource::AnyMotion).
+    pub fn configure_any_motion(&mut self, config: AnyMotionConfig) -> Result<(), Error<E>> {
+        let registers = self.read_motion_registers()?.any_motion(&config);
+        self.write_registers(Register::INT_MOTION, &registers.bytes)?;
+        self.mg_thresholds.any_motion = None;
+
+        let current = self.get_enabled_interrupts()?;
+        let axes = InterruptEnable::from_bits_truncate(u32::from(config.axes.bits()));
+        self.set_enabled_interrupts((current - InterruptEnable::ANY_MOTION) | axes)
+    }
+
+    /// Read the any-motion configuration and enabled axes.
+    pub fn get_any_motion_config(&mut self) -> Result<AnyMotionConfig, Error<E>> {
+        let mut motion = [0u8; 2];
+        self.write_read_register(Register::INT_MOTION, &mut motion)?;
+        let enabled = self.get_enabled_interrupts()?;
+
+        Ok(AnyMotionConfig {
+            threshold: motion[1],
+            duration: (motion[0] & INT_MOTION_ANYM_DUR_MASK) + 1,
+            axes: Axes::from_bits(enabled.bits() as u8),
+        })
+    }
+
+    /// Configure and enable no-motion or slow-motion detection.
+    ///
+    /// Writes the mode, threshold and duration, and enables exactly the
+    /// axes in `config.axes`. Route the interrupt with
+    /// [`Bmi160::map_interrupt`] and
+    /// [`InterruptSource::NoMotion`](crate::InterruptSource::NoMotion).
+    pub fn configure_no_motion(&mut self, config: NoMotionConfig) -> Result<(), Error<E>> {
+        let registers = self.read_motion_registers()?.no_motion(&config);
+        self.write_registers(Register::INT_MOTION, &registers.bytes)?;
+        self.mg_thresholds.no_motion = None;
+
+        let current = self.get_enabled_interrupts()?;
+        let axes = InterruptEnable::from_bits_truncate(u32::from(config.axes.bits()) << 16);
+        self.set_enabled_interrupts((current - InterruptEnable::NO_MOTION) | axes)
+    }
+
+    /// Read the no-motion / slow-motion configuration and enabled axes.
+    pub fn get_no_motion_config(&mut self) -> Result<NoMotionConfig, Error<E>> {
+        let mut motion = [0u8; 4];
+        self.write_read_register(Register::INT_MOTION, &mut motion)?;
+        let enabled = self.get_enabled_interrupts()?;
+
+        Ok(NoMotionConfig {
+            mode: if motion[3] & INT_MOTION_NO_MOT_SEL != 0 {
+                NoMotionMode::NoMotion
+            } else {
+                NoMotionMode::SlowMotion
+            },
+            threshold: motion[2],
+            duration: motion[0] >> INT_MOTION_SLO_NO_MOT_DUR_SHIFT,
+            axes: Axes::from_bits((enabled.bits() >> 16) as u8),
+        })
+    }
+
+    /// Disable no-motion / slow-motion detection on all axes.
+    pub fn disable_no_motion(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::NO_MOTION)
+    }
+
+    /// Configure and enable significant motion detection.
+    ///
+    /// Selects significant motion over any-motion, writes the threshold,
+    /// skip and proof times, and enables the any-motion engine on all axes.
+    /// Significant motion is signalled on the any-motion interrupt mapping,
+    /// see [`InterruptSource::AnyMotion`](crate::InterruptSource::AnyMotion).
+    pub fn configure_significant_motion(
+        &mut self,
+        config: SignificantMotionConfig,
+    ) -> Result<(), Error<E>> {
+        let registers = self.read_motion_registers()?.significant_motion(&config);
+        self.write_registers(Register::INT_MOTION, &registers.bytes)?;
+        self.mg_thresholds.any_motion = None;
+        self.enable_interrupts(InterruptEnable::ANY_MOTION)
+    }
+
+    /// Read the significant motion configuration. Returns `None` if the
+    /// any-motion engine is in any-motion rather than significant motion
+    /// mode.
+    pub fn get_significant_motion_config(
+        &mut self,
+    ) -> Result<Option<SignificantMotionConfig>, Error<E>> {
+        let mut motion = [0u8; 3];
+        self.write_read_register(Register::INT_MOTION_1, &mut motion)?;
+
+        if motion[2] & INT_MOTION_SIG_MOT_SEL == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(SignificantMotionConfig {
+            threshold: motion[0],
+            skip: SigMotionSkip::from_bits(motion[2] >> INT_MOTION_SIG_MOT_SKIP_SHIFT),
+            proof: SigMotionProof::from_bits(motion[2] >> INT_MOTION_SIG_MOT_PROOF_SHIFT),
+        }))
+    }
+
+    /// Disable any-motion detection on all axes.
+    pub fn disable_any_motion(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::ANY_MOTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Mock;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn no_motion_duration_decode() {
+        for &(raw, secs) in &[
+            (0, 1.28),
+            (15, 20.48),
+            (16, 25.6),
+            (31, 102.4),
+            (32, 112.64),
+            (63, 430.08),
+        ] {
+            assert_close(no_motion_duration_secs(raw), secs);
+        }
+    }
+
+    #[test]
+    fn no_motion_duration_encode() {
+        // Rounds up to the next encodable duration
+        let config = NoMotionConfig::no_motion(0x14, 5.0, Axes::ALL);
+        assert_eq!(config.duration, 3);
+        assert_close(config.duration_secs().unwrap(), 5.12);
+
+        assert_eq!(
+            NoMotionConfig::no_motion(0x14, 20.48, Axes::ALL).duration,
+            15
+        );
+        assert_eq!(
+            NoMotionConfig::no_motion(0x14, 21.0, Axes::ALL).duration,
+            16
+        );
+        assert_eq!(
+            NoMotionConfig::no_motion(0x14, 103.0, Axes::ALL).duration,
+            32
+        );
+        assert_eq!(
+            NoMotionConfig::no_motion(0x14, 1000.0, Axes::ALL).duration,
+            63
+        );
+
+        // Every encoding survives a round trip through seconds
+        for raw in 0..64 {
+            let secs = no_motion_duration_secs(raw);
+            assert_eq!(NoMotionConfig::no_motion(0, secs, Axes::ALL).duration, raw);
+        }
+    }
+
+    #[test]
+    fn slow_motion_duration() {
+        let config = NoMotionConfig {
+            duration: 0b11_1110,
+            ..NoMotionConfig::default()
+        };
+
+        assert_eq!(config.duration_samples(), Some(3));
+        assert_eq!(config.duration_secs(), None);
+    }
+
+    #[test]
+    fn no_motion_registers_keep_any_motion() {
+        let any_motion = AnyMotionConfig {
+            threshold: 0x20,
+            duration: 3,
+            axes: Axes::ALL,
+        };
+        let no_motion = NoMotionConfig::no_motion(0x30, 25.6, Axes::ALL);
+
+        let registers = MotionRegisters::new()
+            .any_motion(&any_motion)
+            .no_motion(&no_motion);
+
+        assert_eq!(
+            registers.bytes[0],
+            16 << INT_MOTION_SLO_NO_MOT_DUR_SHIFT | 2
+        );
+        assert_eq!(registers.bytes[1], 0x20);
+        assert_eq!(registers.bytes[2], 0x30);
+        assert_eq!(
+            registers.bytes[3] & INT_MOTION_NO_MOT_SEL,
+            INT_MOTION_NO_MOT_SEL
+        );
+    }
+
+    #[test]
+    fn configure_no_motion_round_trip() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        let config = NoMotionConfig::no_motion(
+            0x30,
+            60.0,
+            Axes {
+                x: true,
+                y: false,
+                z: true,
+            },
+        );
+
+        imu.configure_no_motion(config).unwrap();
+
+        assert_eq!(imu.get_no_motion_config().unwrap(), config);
+    }
+}
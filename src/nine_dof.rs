@@ -0,0 +1,90 @@
+//! Combined accelerometer, gyroscope and magnetometer reads in SI units
+
+use crate::interface::Interface;
+use crate::mag::SecondaryMag;
+use crate::register::Register;
+use crate::{temperature_from_bytes, Bmi160, Data, Error, SensorTime};
+use core::f32::consts::PI;
+
+/// Standard gravity in m/s² per g
+const STANDARD_GRAVITY: f32 = 9.806_65;
+
+/// Bytes from DATA (0x04) through TEMPERATURE (0x21)
+const NINE_DOF_LEN: usize = 30;
+
+/// Offset of SENSORTIME in the burst
+const SENSORTIME_OFFSET: usize = 20;
+
+/// Offset of TEMPERATURE in the burst
+const TEMPERATURE_OFFSET: usize = 28;
+
+/// One accelerometer, gyroscope and magnetometer sample in SI units, as
+/// returned by [`Bmi160::read_9dof`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NineDof {
+    /// Acceleration in m/s²
+    pub accel: [f32; 3],
+
+    /// Angular rate in rad/s
+    pub gyro: [f32; 3],
+
+    /// Magnetic field in µT, `None` without a BMM150 set up or if the
+    /// sample is invalid
+    pub mag: Option<[f32; 3]>,
+
+    /// Die temperature in °C, `None` while the sensor has no valid reading
+    pub temperature_c: Option<f32>,
+
+    /// Sensortime of the read
+    pub time: SensorTime,
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Read accelerometer, gyroscope, magnetometer, sensortime and
+    /// temperature in one burst (0x04-0x21) and convert them in one pass.
+    ///
+    /// The accelerometer and gyroscope are scaled at the cached ranges,
+    /// with the software accelerometer correction and gyroscope bias
+    /// applied like [`Bmi160::read_accel_g`] and [`Bmi160::read_gyro_dps`].
+    /// The magnetometer is compensated with the BMM150 set up by
+    /// [`Bmi160::setup_bmm150`]. The units suit AHRS filters directly.
+    pub fn read_9dof(&mut self) -> Result<NineDof, Error<E>> {
+        let mut buffer = [0u8; NINE_DOF_LEN];
+        self.write_read_register(Register::DATA, &mut buffer)?;
+
+        let time = [
+            buffer[SENSORTIME_OFFSET],
+            buffer[SENSORTIME_OFFSET + 1],
+            buffer[SENSORTIME_OFFSET + 2],
+        ];
+        let temperature = [buffer[TEMPERATURE_OFFSET], buffer[TEMPERATURE_OFFSET + 1]];
+        let mag = match self.bmm150 {
+            Some(bmm150) => bmm150.compensate(&buffer[..8]),
+            None => None,
+        };
+        let data = Data::new_from_buffer(&mut buffer);
+
+        let g = data.accel.scaled(self.accel_range.lsb_per_g());
+        let g = match self.accel_correction {
+            Some(correction) => correction.apply(g),
+            None => g,
+        };
+        let dps = data.gyro.scaled(self.gyro_range.lsb_per_dps());
+        let rad = |i: usize| (dps[i] - self.gyro_bias[i]) * PI / 180.0;
+
+        Ok(NineDof {
+            accel: [
+                g[0] * STANDARD_GRAVITY,
+                g[1] * STANDARD_GRAVITY,
+                g[2] * STANDARD_GRAVITY,
+            ],
+            gyro: [rad(0), rad(1), rad(2)],
+            mag,
+            temperature_c: temperature_from_bytes(temperature),
+            time: SensorTime::from_le_bytes(time),
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Non-volatile memory control (CONF) and NVM-backed settings (NV_CONF)
+
+use crate::interface::Interface;
+use crate::register::{Cmd, ConfFlags, I2cWatchdog, NvConfFlags, Register, StatusFlags};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+/// Upper bound on an NVM write in milliseconds
+const NVM_TIMEOUT_MS: u32 = 50;
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Read the CONF register flags
+    pub fn get_conf(&mut self) -> Result<ConfFlags, Error<E>> {
+        self.read_register(Register::CONF)
+            .map(ConfFlags::from_bits_truncate)
+    }
+
+    /// Is NVM programming currently enabled (`nvm_prog_en`)?
+    pub fn nvm_programming_enabled(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.get_conf()?.contains(ConfFlags::NVM_PROG_EN))
+    }
+
+    /// Enable or disable NVM programming (`nvm_prog_en`).
+    ///
+    /// Only the `nvm_prog_en` bit is changed; the remaining CONF bits are
+    /// read back and written unchanged. NVM supports a limited number of
+    /// write cycles, so leave this disabled except while programming.
+    pub fn set_nvm_programming(&mut self, enabled: bool) -> Result<(), Error<E>> {
+        let mut conf = self.read_register(Register::CONF)?;
+
+        if enabled {
+            conf |= ConfFlags::NVM_PROG_EN.bits();
+        } else {
+            conf &= !ConfFlags::NVM_PROG_EN.bits();
+        }
+
+        self.write_register(Register::CONF, conf)
+    }
+
+    /// Persist NV_CONF and the OFFSET registers into NVM.
+    ///
+    /// NVM only survives a limited number of write cycles, so call this
+    /// once per calibration rather than on every boot. The flow is:
+    ///
+    /// 1. Wait for STATUS `nvm_rdy`, so no write is started while a
+    ///    previous one is still running
+    /// 2. Set `nvm_prog_en` and issue `prog_nvm`
+    /// 3. Wait for `nvm_rdy` again
+    /// 4. Clear `nvm_prog_en`
+    ///
+    /// Each wait polls once per millisecond and fails with
+    /// [`Error::Timeout`] after 50 ms. `nvm_prog_en` is cleared even if the
+    /// write times out, so a failed call never leaves programming enabled.
+    pub fn program_nvm<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.wait_nvm_ready(delay)?;
+
+        self.set_nvm_programming(true)?;
+        let result = self
+            .send_command(Cmd::PROG_NVM)
+            .and_then(|_| self.wait_nvm_ready(delay));
+        let disabled = self.set_nvm_programming(false);
+
+        result.and(disabled)
+    }
+
+    /// Poll STATUS `nvm_rdy` until set
+    fn wait_nvm_ready<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let mut waited = 0;
+        while !self.get_status()?.contains(StatusFlags::NVM_RDY) {
+            if waited >= NVM_TIMEOUT_MS {
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_ms(1);
+            waited += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Read the NV_CONF register flags
+    pub fn get_nv_conf(&mut self) -> Result<NvConfFlags, Error<E>> {
+        self.read_register(Register::NV_CONF)
+            .map(NvConfFlags::from_bits_truncate)
+    }
+
+    /// Get the current I2C watchdog setting
+    pub fn get_i2c_watchdog(&mut self) -> Result<I2cWatchdog, Error<E>> {
+        let flags = self.get_nv_conf()?;
+
+        Ok(if !flags.contains(NvConfFlags::I2C_WDT_EN) {
+            I2cWatchdog::Disabled
+        } else if flags.contains(NvConfFlags::I2C_WDT_SEL) {
+            I2cWatchdog::Ms50
+        } else {
+            I2cWatchdog::Ms1
+        })
+    }
+
+    /// Configure the I2C watchdog (`i2c_wdt_en`/`i2c_wdt_sel`).
+    ///
+    /// The other NV_CONF bits are preserved. The setting is lost on reset
+    /// unless it is subsequently programmed into NVM.
+    pub fn set_i2c_watchdog(&mut self, watchdog: I2cWatchdog) -> Result<(), Error<E>> {
+        let mut nv_conf = self.read_register(Register::NV_CONF)?;
+        nv_conf &= !(NvConfFlags::I2C_WDT_EN | NvConfFlags::I2C_WDT_SEL).bits();
+
+        nv_conf |= match watchdog {
+            I2cWatchdog::Disabled => NvConfFlags::empty(),
+            I2cWatchdog::Ms1 => NvConfFlags::I2C_WDT_EN,
+            I2cWatchdog::Ms50 => NvConfFlags::I2C_WDT_EN | NvConfFlags::I2C_WDT_SEL,
+        }
+        .bits();
+
+        self.write_register(Register::NV_CONF, nv_conf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Mock, NoDelay};
+
+    #[test]
+    fn program_nvm_enables_programming_only_while_writing() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.program_nvm(&mut NoDelay).unwrap();
+
+        assert_eq!(imu.iface.nvm_writes, 1);
+        assert!(!imu.nvm_programming_enabled().unwrap());
+    }
+
+    #[test]
+    fn program_nvm_waits_for_ready() {
+        let mut mock = Mock::new();
+        mock.set_reg(Register::STATUS, 0);
+        let mut imu = Bmi160::from_interface(mock).unwrap();
+
+        assert!(matches!(imu.program_nvm(&mut NoDelay), Err(Error::Timeout)));
+        assert_eq!(imu.iface.nvm_writes, 0);
+        assert!(!imu.nvm_programming_enabled().unwrap());
+    }
+}
@@ -0,0 +1,392 @@
+//! Fast offset compensation (FOC_CONF) and the OFFSET registers
+
+use crate::interface::Interface;
+use crate::register::{Cmd, FocAccelTarget, Register, StatusFlags};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+/// FOC_CONF `foc_gyr_en`
+const FOC_CONF_GYR_EN: u8 = 0b0100_0000;
+
+/// OFFSET[6] gyroscope offset MSBs, 2 bits per axis
+const OFFSET_GYR_MSB_MASK: u8 = 0b0011_1111;
+
+/// OFFSET[6] `acc_off_en`
+const OFFSET_ACC_OFF_EN: u8 = 0b0100_0000;
+
+/// OFFSET[6] `gyr_off_en`
+const OFFSET_GYR_OFF_EN: u8 = 0b1000_0000;
+
+/// Accelerometer offset resolution in mg per LSB
+const ACCEL_OFFSET_MG_PER_LSB: f32 = 3.9;
+
+/// Gyroscope offset resolution in dps per LSB
+const GYRO_OFFSET_DPS_PER_LSB: f32 = 0.061;
+
+/// FOC_CONF `foc_acc_x`, 2 bits starting at bit 4
+const FOC_CONF_ACC_X_SHIFT: u8 = 4;
+
+/// FOC_CONF `foc_acc_y`, 2 bits starting at bit 2
+const FOC_CONF_ACC_Y_SHIFT: u8 = 2;
+
+/// FOC_CONF `foc_acc_z`, 2 bits starting at bit 0
+const FOC_CONF_ACC_Z_SHIFT: u8 = 0;
+
+/// Upper bound on a FOC run in milliseconds. The datasheet specifies
+/// 250 ms; allow some margin for the host delay.
+const FOC_TIMEOUT_MS: u32 = 300;
+
+/// Fast offset compensation settings (FOC_CONF)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FocConfig {
+    /// X axis target
+    pub accel_x: FocAccelTarget,
+
+    /// Y axis target
+    pub accel_y: FocAccelTarget,
+
+    /// Z axis target
+    pub accel_z: FocAccelTarget,
+
+    /// Null the gyroscope bias (`foc_gyr_en`). The device must be at rest.
+    pub gyro: bool,
+}
+
+impl FocConfig {
+    /// Device lying flat, face up: X and Y at 0 g, Z at +1 g
+    pub fn flat() -> Self {
+        FocConfig {
+            accel_x: FocAccelTarget::Zero,
+            accel_y: FocAccelTarget::Zero,
+            accel_z: FocAccelTarget::PlusOneG,
+            gyro: false,
+        }
+    }
+
+    /// Also compensate the gyroscope bias
+    pub fn with_gyro(mut self) -> Self {
+        self.gyro = true;
+        self
+    }
+
+    /// Encode as a FOC_CONF byte
+    pub(crate) fn bits(&self) -> u8 {
+        (self.accel_x as u8) << FOC_CONF_ACC_X_SHIFT
+            | (self.accel_y as u8) << FOC_CONF_ACC_Y_SHIFT
+            | (self.accel_z as u8) << FOC_CONF_ACC_Z_SHIFT
+            | if self.gyro { FOC_CONF_GYR_EN } else { 0 }
+    }
+
+    /// Decode a FOC_CONF byte
+    fn from_bits(bits: u8) -> Self {
+        FocConfig {
+            accel_x: FocAccelTarget::from_bits(bits >> FOC_CONF_ACC_X_SHIFT),
+            accel_y: FocAccelTarget::from_bits(bits >> FOC_CONF_ACC_Y_SHIFT),
+            accel_z: FocAccelTarget::from_bits(bits >> FOC_CONF_ACC_Z_SHIFT),
+            gyro: bits & FOC_CONF_GYR_EN != 0,
+        }
+    }
+}
+
+/// Round to the nearest integer and clamp to `min..=max`
+fn round_clamp(value: f32, min: i16, max: i16) -> i16 {
+    let rounded = if value < 0.0 {
+        value - 0.5
+    } else {
+        value + 0.5
+    };
+
+    if rounded <= f32::from(min) {
+        min
+    } else if rounded >= f32::from(max) {
+        max
+    } else {
+        rounded as i16
+    }
+}
+
+/// Contents of the OFFSET registers (0x71-0x77)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Offsets {
+    /// Accelerometer offsets (`off_acc_x/y/z`), 8-bit two's complement
+    pub accel: [i8; 3],
+
+    /// Gyroscope offsets (`off_gyr_x/y/z`), 10-bit two's complement
+    /// assembled from 0x74-0x76 and the MSBs in 0x77
+    pub gyro: [i16; 3],
+}
+
+impl Offsets {
+    /// Decode the seven OFFSET bytes
+    pub(crate) fn from_bytes(bytes: [u8; 7]) -> Self {
+        let gyro = |i: usize| {
+            let msb = u16::from(bytes[6] >> (2 * i)) & 0b11;
+            let raw = msb << 8 | u16::from(bytes[3 + i]);
+            // Sign-extend from 10 bits
+            ((raw << 6) as i16) >> 6
+        };
+
+        Offsets {
+            accel: [bytes[0] as i8, bytes[1] as i8, bytes[2] as i8],
+            gyro: [gyro(0), gyro(1), gyro(2)],
+        }
+    }
+
+    /// Offsets from physical units, rounded to the nearest LSB and clamped
+    /// to the register range (±499.2 mg and ±31.2 dps)
+    pub fn from_units(accel_mg: [f32; 3], gyro_dps: [f32; 3]) -> Self {
+        let accel = |mg: f32| round_clamp(mg / ACCEL_OFFSET_MG_PER_LSB, -128, 127) as i8;
+        let gyro = |dps: f32| round_clamp(dps / GYRO_OFFSET_DPS_PER_LSB, -512, 511);
+
+        Offsets {
+            accel: [accel(accel_mg[0]), accel(accel_mg[1]), accel(accel_mg[2])],
+            gyro: [gyro(gyro_dps[0]), gyro(gyro_dps[1]), gyro(gyro_dps[2])],
+        }
+    }
+
+    /// Encode the six offset bytes (0x71-0x76) and the gyroscope MSBs of
+    /// 0x77
+    fn bytes(&self) -> ([u8; 6], u8) {
+        let mut bytes = [0u8; 6];
+        let mut msbs = 0;
+
+        for i in 0..3 {
+            bytes[i] = self.accel[i] as u8;
+            bytes[3 + i] = self.gyro[i] as u8;
+            msbs |= ((self.gyro[i] >> 8) as u8 & 0b11) << (2 * i);
+        }
+
+        (bytes, msbs)
+    }
+
+    /// Accelerometer offsets in mg (3.9 mg per LSB)
+    pub fn accel_mg(&self) -> [f32; 3] {
+        let mg = |raw: i8| f32::from(raw) * ACCEL_OFFSET_MG_PER_LSB;
+        [mg(self.accel[0]), mg(self.accel[1]), mg(self.accel[2])]
+    }
+
+    /// Gyroscope offsets in dps (0.061 dps per LSB)
+    pub fn gyro_dps(&self) -> [f32; 3] {
+        let dps = |raw: i16| f32::from(raw) * GYRO_OFFSET_DPS_PER_LSB;
+        [dps(self.gyro[0]), dps(self.gyro[1]), dps(self.gyro[2])]
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Run fast offset compensation and return the resulting offsets.
+    ///
+    /// Writes FOC_CONF, issues `start_foc` and polls STATUS `foc_rdy` once
+    /// per millisecond, giving up with [`Error::Timeout`] after 300 ms. The
+    /// accelerometer must be in normal mode and the device held still in
+    /// the orientation the targets describe; gyroscope compensation also
+    /// needs the gyroscope in normal mode. Use [`Offsets::gyro_dps`] to log
+    /// the resulting gyroscope bias. The offsets are written to the
+    /// OFFSET registers but only applied once offset compensation is
+    /// enabled with [`Bmi160::enable_offset_compensation`].
+    pub fn run_foc<D>(&mut self, config: FocConfig, delay: &mut D) -> Result<Offsets, Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.start_foc(config)?;
+
+        let mut waited = 0;
+        while !self.get_status()?.contains(StatusFlags::FOC_RDY) {
+            if waited >= FOC_TIMEOUT_MS {
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_ms(1);
+            waited += 1;
+        }
+
+        self.read_offsets()
+    }
+
+    /// Write FOC_CONF and issue `start_foc` without waiting for the result.
+    ///
+    /// Poll for completion with [`Bmi160::try_foc_result`]; the same
+    /// requirements as for [`Bmi160::run_foc`] apply.
+    pub fn start_foc(&mut self, config: FocConfig) -> Result<(), Error<E>> {
+        self.write_register(Register::FOC_CONF, config.bits())?;
+        self.send_command(Cmd::START_OFC)
+    }
+
+    /// Read the current FOC_CONF settings
+    pub fn get_foc_config(&mut self) -> Result<FocConfig, Error<E>> {
+        self.read_register(Register::FOC_CONF)
+            .map(FocConfig::from_bits)
+    }
+
+    /// Read the raw OFFSET registers in a single burst
+    pub fn read_offsets(&mut self) -> Result<Offsets, Error<E>> {
+        let mut bytes = [0u8; 7];
+        self.write_read_register(Register::OFFSET, &mut bytes)?;
+        Ok(Offsets::from_bytes(bytes))
+    }
+
+    /// Write the OFFSET registers.
+    ///
+    /// The offset compensation enables in 0x77 are preserved. The values
+    /// take effect immediately if compensation is enabled.
+    pub fn write_offsets(&mut self, offsets: Offsets) -> Result<(), Error<E>> {
+        let (bytes, msbs) = offsets.bytes();
+        self.write_registers(Register::OFFSET, &bytes)?;
+        self.modify_register(Register::OFFSET_6, OFFSET_GYR_MSB_MASK, msbs)
+    }
+
+    /// Enable or disable applying the stored offsets to the accelerometer
+    /// (`acc_off_en`) and gyroscope (`gyr_off_en`) data.
+    ///
+    /// The offset values themselves are left unchanged.
+    pub fn enable_offset_compensation(&mut self, accel: bool, gyro: bool) -> Result<(), Error<E>> {
+        let mut value = 0;
+
+        if accel {
+            value |= OFFSET_ACC_OFF_EN;
+        }
+
+        if gyro {
+            value |= OFFSET_GYR_OFF_EN;
+        }
+
+        self.modify_register(
+            Register::OFFSET_6,
+            OFFSET_ACC_OFF_EN | OFFSET_GYR_OFF_EN,
+            value,
+        )
+    }
+
+    /// Is offset compensation enabled for the accelerometer and gyroscope,
+    /// in that order?
+    pub fn offset_compensation_enabled(&mut self) -> Result<(bool, bool), Error<E>> {
+        let value = self.read_register(Register::OFFSET_6)?;
+        Ok((
+            value & OFFSET_ACC_OFF_EN != 0,
+            value & OFFSET_GYR_OFF_EN != 0,
+        ))
+    }
+
+    /// Read the OFFSET registers as accelerometer offsets in mg and
+    /// gyroscope offsets in dps
+    pub fn read_offsets_units(&mut self) -> Result<([f32; 3], [f32; 3]), Error<E>> {
+        let offsets = self.read_offsets()?;
+        Ok((offsets.accel_mg(), offsets.gyro_dps()))
+    }
+
+    /// Write accelerometer offsets in mg and gyroscope offsets in dps,
+    /// rounded to the nearest LSB and clamped to the register range
+    pub fn write_offsets_units(
+        &mut self,
+        accel_mg: [f32; 3],
+        gyro_dps: [f32; 3],
+    ) -> Result<(), Error<E>> {
+        self.write_offsets(Offsets::from_units(accel_mg, gyro_dps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Mock, NoDelay};
+
+    #[test]
+    fn gyro_offsets_sign_extend_from_10_bits() {
+        // x = 0x1FF (+511), y = 0x200 (-512), z = 0x3FF (-1)
+        let offsets = Offsets::from_bytes([0x7F, 0x80, 0xFF, 0xFF, 0x00, 0xFF, 0b11_10_01]);
+
+        assert_eq!(offsets.accel, [127, -128, -1]);
+        assert_eq!(offsets.gyro, [511, -512, -1]);
+    }
+
+    #[test]
+    fn offsets_round_trip() {
+        let offsets = Offsets {
+            accel: [-5, 0, 100],
+            gyro: [-300, 257, 0],
+        };
+
+        let (bytes, msbs) = offsets.bytes();
+        let mut raw = [0u8; 7];
+        raw[..6].copy_from_slice(&bytes);
+        raw[6] = msbs | OFFSET_ACC_OFF_EN | OFFSET_GYR_OFF_EN;
+
+        assert_eq!(msbs & !OFFSET_GYR_MSB_MASK, 0);
+        assert_eq!(Offsets::from_bytes(raw), offsets);
+    }
+
+    #[test]
+    fn offsets_from_units_round_and_clamp() {
+        let offsets = Offsets::from_units([7.8, -5.9, 1000.0], [0.0305, -0.0306, -40.0]);
+
+        assert_eq!(offsets.accel, [2, -2, 127]);
+        assert_eq!(offsets.gyro, [1, -1, -512]);
+        assert_eq!(offsets.accel_mg()[0], 7.8);
+    }
+
+    #[test]
+    fn foc_config_bits() {
+        let config = FocConfig::flat().with_gyro();
+
+        assert_eq!(config.bits(), 0b0111_1101);
+        assert_eq!(FocConfig::from_bits(config.bits()), config);
+    }
+
+    #[test]
+    fn run_foc_returns_offsets() {
+        let mut mock = Mock::new();
+        mock.foc_offsets = [0xFE, 0x01, 0x00, 0x10, 0x00, 0xFF, 0b11_00_01];
+        let mut imu = Bmi160::from_interface(mock).unwrap();
+
+        let offsets = imu
+            .run_foc(FocConfig::flat().with_gyro(), &mut NoDelay)
+            .unwrap();
+
+        assert_eq!(offsets.accel, [-2, 1, 0]);
+        assert_eq!(offsets.gyro, [0x110, 0, -1]);
+        assert_eq!(imu.get_foc_config().unwrap(), FocConfig::flat().with_gyro());
+    }
+
+    #[test]
+    fn run_foc_times_out() {
+        struct NoFoc(Mock);
+
+        impl Interface for NoFoc {
+            type Error = ();
+
+            fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+                if bytes == [Register::CMD as u8, Cmd::START_OFC as u8] {
+                    return Ok(());
+                }
+                self.0.write(bytes)
+            }
+
+            fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), ()> {
+                self.0.read(register, buffer)
+            }
+        }
+
+        let mut imu = Bmi160::from_interface(NoFoc(Mock::new())).unwrap();
+        assert!(matches!(
+            imu.run_foc(FocConfig::flat(), &mut NoDelay),
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn write_offsets_keeps_enables() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.enable_offset_compensation(true, true).unwrap();
+
+        let offsets = Offsets {
+            accel: [1, -1, 2],
+            gyro: [-512, 511, 3],
+        };
+        imu.write_offsets(offsets).unwrap();
+
+        assert_eq!(imu.read_offsets().unwrap(), offsets);
+        assert_eq!(imu.offset_compensation_enabled().unwrap(), (true, true));
+    }
+}
@@ -0,0 +1,58 @@
+//! Optical image stabilization (OIS) output on the secondary interface
+
+use crate::config::InterfaceConfig;
+use crate::interface::Interface;
+use crate::register::{GyroPowerMode, MagPowerMode, SecondaryInterface};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Serve gyroscope data to an external OIS controller on the secondary
+    /// interface (IF_CONF `if_mode`).
+    ///
+    /// The secondary interface can't drive a magnetometer at the same
+    /// time, so the magnetometer interface is suspended and a BMM150 set up
+    /// with [`Bmi160::setup_bmm150`] is forgotten. The gyroscope is put in
+    /// normal mode, since the OIS controller reads its data directly; set
+    /// its range and rate with [`Bmi160::apply_gyro_config`]. The SPI wire
+    /// mode in IF_CONF is preserved.
+    ///
+    /// In OIS mode the primary interface is fixed to I2C.
+    pub fn enable_ois<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        self.set_mag_power_mode(MagPowerMode::Suspend, delay)?;
+        self.bmm150 = None;
+
+        let interface = self.get_interface_config()?;
+        self.apply_interface_config(InterfaceConfig {
+            secondary: SecondaryInterface::Ois,
+            ..interface
+        })?;
+
+        self.set_gyro_power_mode(GyroPowerMode::Normal, delay)
+    }
+
+    /// Turn the secondary interface off again if it is serving OIS. The
+    /// gyroscope is left running.
+    pub fn disable_ois(&mut self) -> Result<(), Error<E>> {
+        let interface = self.get_interface_config()?;
+        if interface.secondary != SecondaryInterface::Ois {
+            return Ok(());
+        }
+
+        self.apply_interface_config(InterfaceConfig {
+            secondary: SecondaryInterface::Off,
+            ..interface
+        })
+    }
+
+    /// Is the secondary interface serving OIS?
+    pub fn ois_enabled(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.get_interface_config()?.secondary == SecondaryInterface::Ois)
+    }
+}
@@ -0,0 +1,341 @@
+//! Orientation and flat detection (INT_ORIENT, INT_FLAT)
+
+use crate::interface::Interface;
+use crate::interrupt::{InterruptRoute, InterruptSource, InterruptStatus};
+use crate::register::{
+    FlatHold, InterruptEnable, InterruptFlags, OrientBlocking, OrientMode, Register,
+};
+use crate::{Bmi160, Error};
+
+/// INT_ORIENT[0] `int_orient_blocking`, 2 bits starting at bit 2
+const INT_ORIENT_BLOCKING_SHIFT: u8 = 2;
+
+/// INT_ORIENT[0] `int_orient_hyst`, 4 bits starting at bit 4
+const INT_ORIENT_HYST_SHIFT: u8 = 4;
+
+/// INT_ORIENT[1] `int_orient_theta`
+const INT_ORIENT_THETA_MASK: u8 = 0b0011_1111;
+
+/// INT_ORIENT[1] `int_orient_ud_en`
+const INT_ORIENT_UD_EN: u8 = 0b0100_0000;
+
+/// INT_ORIENT[1] `int_orient_axes_ex`
+const INT_ORIENT_AXES_EX: u8 = 0b1000_0000;
+
+/// INT_FLAT[0] `int_flat_theta`
+const INT_FLAT_THETA_MASK: u8 = 0b0011_1111;
+
+/// INT_FLAT[1] `int_flat_hy`
+const INT_FLAT_HY_MASK: u8 = 0b0000_0111;
+
+/// INT_FLAT[1] `int_flat_hold`, 2 bits starting at bit 4
+const INT_FLAT_HOLD_SHIFT: u8 = 4;
+
+/// Portrait/landscape orientation (INT_STATUS_3 `orient_1_0`)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Orientation {
+    /// Portrait, upright
+    PortraitUp,
+
+    /// Portrait, upside down
+    PortraitDown,
+
+    /// Landscape, rotated left
+    LandscapeLeft,
+
+    /// Landscape, rotated right
+    LandscapeRight,
+}
+
+impl Orientation {
+    /// Decode the `orient_1_0` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Orientation::PortraitUp,
+            0b01 => Orientation::PortraitDown,
+            0b10 => Orientation::LandscapeLeft,
+            _ => Orientation::LandscapeRight,
+        }
+    }
+}
+
+/// Which way the Z axis points (INT_STATUS_3 `orient_2`)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Face {
+    /// Z axis points upwards
+    Up,
+
+    /// Z axis points downwards
+    Down,
+}
+
+impl Face {
+    /// Decode the `orient_2` bit
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        if bits & 1 != 0 {
+            Face::Down
+        } else {
+            Face::Up
+        }
+    }
+}
+
+/// Orientation detection configuration, covering INT_ORIENT[0] (0x65) and
+/// INT_ORIENT[1] (0x66)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct OrientationConfig {
+    /// Threshold mode
+    pub mode: OrientMode,
+
+    /// Blocking conditions
+    pub blocking: OrientBlocking,
+
+    /// Raw hysteresis (`int_orient_hyst`, 0-15), 62.5 mg per step
+    pub hysteresis: u8,
+
+    /// Raw blocking angle (`int_orient_theta`, 0-63). The maximum tilt is
+    /// `atan(sqrt(theta) / 8)`.
+    pub theta: u8,
+
+    /// Also report face up/down changes
+    pub up_down: bool,
+
+    /// Swap the X and Z axes, for devices mounted upright
+    pub axes_exchange: bool,
+}
+
+impl Default for OrientationConfig {
+    /// Power-on reset values
+    fn default() -> Self {
+        OrientationConfig {
+            mode: OrientMode::default(),
+            blocking: OrientBlocking::default(),
+            hysteresis: 1,
+            theta: 8,
+            up_down: true,
+            axes_exchange: false,
+        }
+    }
+}
+
+impl OrientationConfig {
+    /// INT_ORIENT[0..1] register values
+    pub(crate) fn bytes(&self) -> [u8; 2] {
+        let orient0 = self.mode as u8
+            | (self.blocking as u8) << INT_ORIENT_BLOCKING_SHIFT
+            | (self.hysteresis & 0x0F) << INT_ORIENT_HYST_SHIFT;
+
+        let mut orient1 = self.theta & INT_ORIENT_THETA_MASK;
+        if self.up_down {
+            orient1 |= INT_ORIENT_UD_EN;
+        }
+        if self.axes_exchange {
+            orient1 |= INT_ORIENT_AXES_EX;
+        }
+
+        [orient0, orient1]
+    }
+
+    /// Decode INT_ORIENT[0..1] register values
+    pub(crate) fn from_bytes(bytes: [u8; 2]) -> Self {
+        OrientationConfig {
+            mode: OrientMode::from_bits(bytes[0]),
+            blocking: OrientBlocking::from_bits(bytes[0] >> INT_ORIENT_BLOCKING_SHIFT),
+            hysteresis: bytes[0] >> INT_ORIENT_HYST_SHIFT,
+            theta: bytes[1] & INT_ORIENT_THETA_MASK,
+            up_down: bytes[1] & INT_ORIENT_UD_EN != 0,
+            axes_exchange: bytes[1] & INT_ORIENT_AXES_EX != 0,
+        }
+    }
+}
+
+/// Flat detection configuration, covering INT_FLAT[0] (0x67) and
+/// INT_FLAT[1] (0x68)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FlatConfig {
+    /// Raw maximum tilt (`int_flat_theta`, 0-63). The device counts as flat
+    /// while tilted less than `atan(sqrt(theta) / 8)`.
+    pub theta: u8,
+
+    /// Raw hysteresis (`int_flat_hy`, 0-7) on `theta`
+    pub hysteresis: u8,
+
+    /// Time the device must stay flat before the interrupt fires
+    pub hold: FlatHold,
+}
+
+impl Default for FlatConfig {
+    /// Power-on reset values
+    fn default() -> Self {
+        FlatConfig {
+            theta: 8,
+            hysteresis: 4,
+            hold: FlatHold::default(),
+        }
+    }
+}
+
+impl FlatConfig {
+    /// INT_FLAT[0..1] register values
+    pub(crate) fn bytes(&self) -> [u8; 2] {
+        [
+            self.theta & INT_FLAT_THETA_MASK,
+            (self.hysteresis & INT_FLAT_HY_MASK) | (self.hold as u8) << INT_FLAT_HOLD_SHIFT,
+        ]
+    }
+
+    /// Decode INT_FLAT[0..1] register values
+    pub(crate) fn from_bytes(bytes: [u8; 2]) -> Self {
+        FlatConfig {
+            theta: bytes[0] & INT_FLAT_THETA_MASK,
+            hysteresis: bytes[1] & INT_FLAT_HY_MASK,
+            hold: FlatHold::from_bits(bytes[1] >> INT_FLAT_HOLD_SHIFT),
+        }
+    }
+}
+
+/// A flat detection event
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FlatEvent {
+    /// The device was put down flat
+    Flat,
+
+    /// The device was lifted out of the flat position
+    NotFlat,
+}
+
+impl FlatEvent {
+    /// Decode the flat event in `status`, if the flat interrupt fired
+    pub fn from_status(status: &InterruptStatus) -> Option<Self> {
+        if !status.fired.contains(InterruptFlags::FLAT) {
+            None
+        } else if status.flat {
+            Some(FlatEvent::Flat)
+        } else {
+            Some(FlatEvent::NotFlat)
+        }
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Write the orientation detection configuration (INT_ORIENT).
+    pub fn configure_orientation(&mut self, config: OrientationConfig) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_ORIENT, &config.bytes())
+    }
+
+    /// Read the orientation detection configuration (INT_ORIENT).
+    pub fn get_orientation_config(&mut self) -> Result<OrientationConfig, Error<E>> {
+        let mut bytes = [0u8; 2];
+        self.write_read_register(Register::INT_ORIENT, &mut bytes)?;
+        Ok(OrientationConfig::from_bytes(bytes))
+    }
+
+    /// Enable the orientation interrupt and route it to `route`.
+    pub fn enable_orientation(&mut self, route: InterruptRoute) -> Result<(), Error<E>> {
+        self.map_interrupt(InterruptSource::Orientation, route)?;
+        self.enable_interrupts(InterruptEnable::ORIENTATION)
+    }
+
+    /// Current orientation as last evaluated by the orientation engine.
+    ///
+    /// The engine must be enabled (see [`Bmi160::enable_orientation`]);
+    /// the face is only updated when `up_down` is set in the configuration.
+    pub fn orientation(&mut self) -> Result<(Orientation, Face), Error<E>> {
+        let status = self.get_interrupt_status()?;
+        Ok((status.orientation, status.face))
+    }
+
+    /// Disable the orientation interrupt.
+    pub fn disable_orientation(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::ORIENTATION)
+    }
+
+    /// Write the flat detection configuration (INT_FLAT).
+    pub fn configure_flat(&mut self, config: FlatConfig) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_FLAT, &config.bytes())
+    }
+
+    /// Read the flat detection configuration (INT_FLAT).
+    pub fn get_flat_config(&mut self) -> Result<FlatConfig, Error<E>> {
+        let mut bytes = [0u8; 2];
+        self.write_read_register(Register::INT_FLAT, &mut bytes)?;
+        Ok(FlatConfig::from_bytes(bytes))
+    }
+
+    /// Enable the flat interrupt and route it to `route`.
+    ///
+    /// The interrupt fires both when the device becomes flat and when it
+    /// stops being flat; see [`FlatEvent`].
+    pub fn enable_flat(&mut self, route: InterruptRoute) -> Result<(), Error<E>> {
+        self.map_interrupt(InterruptSource::Flat, route)?;
+        self.enable_interrupts(InterruptEnable::FLAT)
+    }
+
+    /// Disable the flat interrupt.
+    pub fn disable_flat(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::FLAT)
+    }
+
+    /// Whether the flat engine currently considers the device flat.
+    pub fn is_flat(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.get_interrupt_status()?.flat)
+    }
+
+    /// Read INT_STATUS and decode the flat event, if the flat interrupt
+    /// fired.
+    pub fn flat_event(&mut self) -> Result<Option<FlatEvent>, Error<E>> {
+        let status = self.get_interrupt_status()?;
+        Ok(FlatEvent::from_status(&status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation_power_on_bytes() {
+        assert_eq!(
+            OrientationConfig::from_bytes([0x18, 0x48]),
+            OrientationConfig::default()
+        );
+        assert_eq!(OrientationConfig::default().bytes(), [0x18, 0x48]);
+    }
+
+    #[test]
+    fn orientation_round_trip() {
+        let config = OrientationConfig {
+            mode: OrientMode::LowAsymmetrical,
+            blocking: OrientBlocking::ThetaFast,
+            hysteresis: 0x0F,
+            theta: 0x3F,
+            up_down: false,
+            axes_exchange: true,
+        };
+        assert_eq!(config.bytes(), [0b1111_1110, 0b1011_1111]);
+        assert_eq!(OrientationConfig::from_bytes(config.bytes()), config);
+    }
+
+    #[test]
+    fn flat_power_on_bytes() {
+        assert_eq!(FlatConfig::from_bytes([0x08, 0x14]), FlatConfig::default());
+        assert_eq!(FlatConfig::default().bytes(), [0x08, 0x14]);
+    }
+
+    #[test]
+    fn flat_round_trip() {
+        let config = FlatConfig {
+            theta: 0x3F,
+            hysteresis: 7,
+            hold: FlatHold::Ms2560,
+        };
+        assert_eq!(config.bytes(), [0x3F, 0b0011_0111]);
+        assert_eq!(FlatConfig::from_bytes(config.bytes()), config);
+
+        // Reserved bits are dropped
+        assert_eq!(FlatConfig::from_bytes([0xC8, 0xDC]), FlatConfig::default());
+    }
+}
@@ -0,0 +1,59 @@
+//! Non-blocking reads for cooperative main loops
+//!
+//! Each method checks STATUS once and returns [`nb::Error::WouldBlock`]
+//! until the data is ready, so callers can poll with [`nb::block!`] or
+//! interleave other work instead of spinning inside the driver.
+
+use crate::interface::Interface;
+use crate::offset::Offsets;
+use crate::register::StatusFlags;
+use crate::{Bmi160, Data, Error};
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Read the DATA registers once new accelerometer or gyroscope data is
+    /// ready (STATUS `drdy_acc` or `drdy_gyr`)
+    pub fn try_read_data(&mut self) -> nb::Result<Data, Error<E>> {
+        self.would_block_unless(StatusFlags::DRDY_ACC | StatusFlags::DRDY_GYR)?;
+        Ok(self.read_data()?)
+    }
+
+    /// Read the accelerometer in g once new data is ready (`drdy_acc`).
+    /// See [`Bmi160::read_accel_g`].
+    pub fn try_read_accel_g(&mut self) -> nb::Result<[f32; 3], Error<E>> {
+        self.would_block_unless(StatusFlags::DRDY_ACC)?;
+        Ok(self.read_accel_g()?)
+    }
+
+    /// Read the gyroscope in dps once new data is ready (`drdy_gyr`). See
+    /// [`Bmi160::read_gyro_dps`].
+    pub fn try_read_gyro_dps(&mut self) -> nb::Result<[f32; 3], Error<E>> {
+        self.would_block_unless(StatusFlags::DRDY_GYR)?;
+        Ok(self.read_gyro_dps()?)
+    }
+
+    /// Read the magnetometer in µT once new data is ready (`drdy_mag`). See
+    /// [`Bmi160::read_mag_scaled`].
+    pub fn try_read_mag_scaled(&mut self) -> nb::Result<Option<[f32; 3]>, Error<E>> {
+        self.would_block_unless(StatusFlags::DRDY_MAG)?;
+        Ok(self.read_mag_scaled()?)
+    }
+
+    /// Read the offsets once a FOC run started with
+    /// [`Bmi160::start_foc`] has finished (`foc_rdy`)
+    pub fn try_foc_result(&mut self) -> nb::Result<Offsets, Error<E>> {
+        self.would_block_unless(StatusFlags::FOC_RDY)?;
+        Ok(self.read_offsets()?)
+    }
+
+    /// `WouldBlock` unless any of `flags` is set in STATUS
+    fn would_block_unless(&mut self, flags: StatusFlags) -> nb::Result<(), Error<E>> {
+        if self.get_status()?.intersects(flags) {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
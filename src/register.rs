@@ -1,7 +1,11 @@
 //! ADXL343 register addresses
-#![allow(non_camel_case_types, clippy::unreadable_literal)]
+#![allow(
+    non_camel_case_types,
+    clippy::unreadable_literal,
+    clippy::upper_case_acronyms
+)]
 
-//use bitflags::bitflags;
+use bitflags::bitflags;
 
 /// Register addresses
 /// Taken from the Bosch BMI160 data sheet (Register Map, p.47)
@@ -103,6 +107,11 @@ pub enum Register {
     /// used (header or headerlessmode).
     FIFO_CONFIG = 0x46,
 
+    /// FIFO_CONFIG_1 (Read/Write)
+    ///
+    /// Second byte of FIFO_CONFIG, selecting the FIFO data sources and frame format.
+    FIFO_CONFIG_1 = 0x47,
+
     /// MAG_IF (Read/Write)
     ///
     /// Register for indirect addressing of the magnetometer connected to the magnetometer
@@ -112,11 +121,41 @@ pub enum Register {
     /// no registers in the magnetometer may be accessed.
     MAG_IF = 0x4B,
 
+    /// MAG_IF_1 (Read/Write)
+    ///
+    /// Manual mode enable, read offset and burst length of the magnetometer interface.
+    MAG_IF_1 = 0x4C,
+
+    /// MAG_IF_2 (Read/Write)
+    ///
+    /// Magnetometer register to read from; writing it in manual mode starts a read.
+    MAG_IF_2 = 0x4D,
+
+    /// MAG_IF_3 (Read/Write)
+    ///
+    /// Magnetometer register to write to; writing it in manual mode starts a write.
+    MAG_IF_3 = 0x4E,
+
+    /// MAG_IF_4 (Read/Write)
+    ///
+    /// Data for the next manual magnetometer write.
+    MAG_IF_4 = 0x4F,
+
     /// INT_EN (Read/Write)
     ///
     /// Controls whichinterrupt engines are enabled.
     INT_EN = 0x50,
 
+    /// INT_EN_1 (Read/Write)
+    ///
+    /// Second byte of INT_EN: high-g, low-g, data ready and FIFO interrupts.
+    INT_EN_1 = 0x51,
+
+    /// INT_EN_2 (Read/Write)
+    ///
+    /// Third byte of INT_EN: no-motion/slow-motion and step detector interrupts.
+    INT_EN_2 = 0x52,
+
     ///  INT_OUT_CTRL (Read/Write)
     ///
     /// Contains the behavioral configuration (electrical definition of the interrupt pins.
@@ -132,6 +171,16 @@ pub enum Register {
     /// Controls which interrupt signals are mapped to the INT1 and INT2 pin.
     INT_MAP = 0x55,
 
+    /// INT_MAP_1 (Read/Write)
+    ///
+    /// Second byte of INT_MAP: data ready, FIFO and PMU trigger interrupts for both pins.
+    INT_MAP_1 = 0x56,
+
+    /// INT_MAP_2 (Read/Write)
+    ///
+    /// Third byte of INT_MAP: motion, tap, orientation and flat interrupts for INT2.
+    INT_MAP_2 = 0x57,
+
     /// INT_DATA (Read/Write)
     ///
     /// Contains the data source definition for the two interrupt groups.
@@ -142,16 +191,51 @@ pub enum Register {
     /// Contains the configuration for the low g interrupt.
     INT_LOWHIGH = 0x5A,
 
+    /// INT_LOWHIGH_2 (Read/Write)
+    ///
+    /// Low-g hysteresis and mode, high-g hysteresis.
+    INT_LOWHIGH_2 = 0x5C,
+
+    /// INT_LOWHIGH_3 (Read/Write)
+    ///
+    /// High-g duration; followed by the high-g threshold in INT_LOWHIGH_4.
+    INT_LOWHIGH_3 = 0x5D,
+
+    /// INT_LOWHIGH_4 (Read/Write)
+    ///
+    /// High-g threshold.
+    INT_LOWHIGH_4 = 0x5E,
+
     /// INT_MOTION (Read/Write)
     ///
     /// Contains the configuration for the anymotion and nomotion interrupts.
     INT_MOTION = 0x5F,
 
+    /// INT_MOTION_1 (Read/Write)
+    ///
+    /// Any-motion threshold.
+    INT_MOTION_1 = 0x60,
+
+    /// INT_MOTION_2 (Read/Write)
+    ///
+    /// No-motion / slow-motion threshold.
+    INT_MOTION_2 = 0x61,
+
+    /// INT_MOTION_3 (Read/Write)
+    ///
+    /// No-motion and significant motion mode selection.
+    INT_MOTION_3 = 0x62,
+
     /// INT_TAP (Read/Write)
     ///
     /// Contains the configuration for the tap interrupts.
     INT_TAP = 0x63,
 
+    /// INT_TAP_1 (Read/Write)
+    ///
+    /// Tap threshold.
+    INT_TAP_1 = 0x64,
+
     /// INT_ORIENT (Read/Write)
     ///
     /// Contains the configuration for the orientation interrupt.
@@ -198,6 +282,11 @@ pub enum Register {
     /// Contains the offset compensation values for accelerometer and gyroscope.
     OFFSET = 0x71,
 
+    /// OFFSET_6 (Read/Write)
+    ///
+    /// Gyroscope offset MSBs and the offset compensation enables.
+    OFFSET_6 = 0x77,
+
     /// STEP_CNT (Read Only)
     ///
     /// Contains the number of steps.
@@ -208,6 +297,11 @@ pub enum Register {
     /// Contains configuration of the step detector.
     STEP_CONF = 0x7A,
 
+    /// STEP_CONF_1 (Read/Write)
+    ///
+    /// Second byte of STEP_CONF, including the step counter enable.
+    STEP_CONF_1 = 0x7B,
+
     /// CMD (Write Only)
     ///
     /// Command register triggers operations like softreset, NVM programming, etc.
@@ -222,20 +316,20 @@ impl Register {
 
     /// Is the register read-only?
     pub fn read_only(self) -> bool {
-        match self {
+        matches!(
+            self,
             Register::CHIP_ID
-            | Register::ERROR_REG
-            | Register::PMU_STATUS
-            | Register::DATA
-            | Register::SENSORTIME
-            | Register::STATUS
-            | Register::INT_STATUS
-            | Register::TEMPERATURE
-            | Register::FIFO_LENGTH
-            | Register::FIFO_DATA
-            | Register::STEP_CNT => true,
-            _ => false,
-        }
+                | Register::ERROR_REG
+                | Register::PMU_STATUS
+                | Register::DATA
+                | Register::SENSORTIME
+                | Register::STATUS
+                | Register::INT_STATUS
+                | Register::TEMPERATURE
+                | Register::FIFO_LENGTH
+                | Register::FIFO_DATA
+                | Register::STEP_CNT
+        )
     }
 }
 /// Commands that can be used passed into CMD Register
@@ -246,6 +340,10 @@ pub enum Cmd {
     /// FOC_CONF and stores the result into the Register (0x71-0x77) OFFSET register.
     START_OFC = 0x03,
 
+    /// Writes the NVM backed registers (NV_CONF and OFFSET) into NVM.
+    /// Requires `nvm_prog_en` in CONF.
+    PROG_NVM = 0xA0,
+
     /// Sets the PMU mode for the accelerometer to Suspend.
     ACC_SET_PMU_MODE_SUSPEND = 0b00010000,
 
@@ -266,4 +364,1198 @@ pub enum Cmd {
 
     /// Sets the PMU mode for the gyroscope to Fast Start-up.
     GYR_SET_PMU_MODE_FAST_STARTUP = 0b00010111,
+
+    /// Sets the PMU mode for the magnetometer interface to Suspend.
+    MAG_IF_SET_PMU_MODE_SUSPEND = 0b00011000,
+
+    /// Sets the PMU mode for the magnetometer interface to Normal.
+    MAG_IF_SET_PMU_MODE_NORMAL = 0b00011001,
+
+    /// Sets the PMU mode for the magnetometer interface to Low Power.
+    MAG_IF_SET_PMU_MODE_LOW_POWER = 0b00011010,
+
+    /// Clears all data in the FIFO. Does not change the FIFO_CONFIG and
+    /// FIFO_DOWNS registers.
+    FIFO_FLUSH = 0xB0,
+
+    /// Resets the interrupt engines and clears all latched interrupts.
+    INT_RESET = 0xB1,
+
+    /// Clears the step counter.
+    STEP_CNT_CLR = 0xB2,
+}
+
+/// Accelerometer power mode
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AccelPowerMode {
+    /// Suspend (default after power-on)
+    #[default]
+    Suspend,
+
+    /// Normal
+    Normal,
+
+    /// Low power (duty-cycled, requires undersampling)
+    LowPower,
+}
+
+/// Gyroscope power mode
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum GyroPowerMode {
+    /// Suspend (default after power-on)
+    #[default]
+    Suspend,
+
+    /// Normal
+    Normal,
+
+    /// Fast start-up: drive stays on so normal mode is reached in ~10 ms
+    FastStartUp,
+}
+
+/// Magnetometer interface power mode
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MagPowerMode {
+    /// Suspend (default after power-on)
+    #[default]
+    Suspend,
+
+    /// Normal
+    Normal,
+
+    /// Low power
+    LowPower,
+}
+
+impl AccelPowerMode {
+    /// Decode the `acc_pmu_status` field of PMU_STATUS
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0b11 {
+            0b00 => Some(AccelPowerMode::Suspend),
+            0b01 => Some(AccelPowerMode::Normal),
+            0b10 => Some(AccelPowerMode::LowPower),
+            _ => None,
+        }
+    }
+}
+
+impl GyroPowerMode {
+    /// Decode the `gyr_pmu_status` field of PMU_STATUS
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0b11 {
+            0b00 => Some(GyroPowerMode::Suspend),
+            0b01 => Some(GyroPowerMode::Normal),
+            0b11 => Some(GyroPowerMode::FastStartUp),
+            _ => None,
+        }
+    }
+}
+
+impl MagPowerMode {
+    /// Decode the `mag_pmu_status` field of PMU_STATUS
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0b11 {
+            0b00 => Some(MagPowerMode::Suspend),
+            0b01 => Some(MagPowerMode::Normal),
+            0b10 => Some(MagPowerMode::LowPower),
+            _ => None,
+        }
+    }
+}
+
+/// Accelerometer output data rate (ACC_CONF `acc_odr`)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AccelOdr {
+    /// 25/32 Hz
+    Hz0_78 = 0b0001,
+
+    /// 25/16 Hz
+    Hz1_56 = 0b0010,
+
+    /// 25/8 Hz
+    Hz3_12 = 0b0011,
+
+    /// 25/4 Hz
+    Hz6_25 = 0b0100,
+
+    /// 25/2 Hz
+    Hz12_5 = 0b0101,
+
+    /// 25 Hz
+    Hz25 = 0b0110,
+
+    /// 50 Hz
+    Hz50 = 0b0111,
+
+    /// 100 Hz (default)
+    Hz100 = 0b1000,
+
+    /// 200 Hz
+    Hz200 = 0b1001,
+
+    /// 400 Hz
+    Hz400 = 0b1010,
+
+    /// 800 Hz
+    Hz800 = 0b1011,
+
+    /// 1600 Hz
+    Hz1600 = 0b1100,
+}
+
+impl AccelOdr {
+    /// Decode the `acc_odr` field
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        Some(match bits & 0x0F {
+            0b0001 => AccelOdr::Hz0_78,
+            0b0010 => AccelOdr::Hz1_56,
+            0b0011 => AccelOdr::Hz3_12,
+            0b0100 => AccelOdr::Hz6_25,
+            0b0101 => AccelOdr::Hz12_5,
+            0b0110 => AccelOdr::Hz25,
+            0b0111 => AccelOdr::Hz50,
+            0b1000 => AccelOdr::Hz100,
+            0b1001 => AccelOdr::Hz200,
+            0b1010 => AccelOdr::Hz400,
+            0b1011 => AccelOdr::Hz800,
+            0b1100 => AccelOdr::Hz1600,
+            _ => return None,
+        })
+    }
+
+    /// Get the output data rate in Hz
+    pub fn hz(self) -> f32 {
+        match self {
+            AccelOdr::Hz0_78 => 0.78125,
+            AccelOdr::Hz1_56 => 1.5625,
+            AccelOdr::Hz3_12 => 3.125,
+            AccelOdr::Hz6_25 => 6.25,
+            AccelOdr::Hz12_5 => 12.5,
+            AccelOdr::Hz25 => 25.0,
+            AccelOdr::Hz50 => 50.0,
+            AccelOdr::Hz100 => 100.0,
+            AccelOdr::Hz200 => 200.0,
+            AccelOdr::Hz400 => 400.0,
+            AccelOdr::Hz800 => 800.0,
+            AccelOdr::Hz1600 => 1600.0,
+        }
+    }
+}
+
+/// Accelerometer filter mode (ACC_CONF `acc_us` and `acc_bwp`)
+///
+/// The `Osr*`/`Normal` variants are used with `acc_us = 0`. The `Avg*`
+/// variants enable undersampling (`acc_us = 1`) and select how many samples
+/// are averaged per output sample.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccelBandwidth {
+    /// 4x oversampling
+    Osr4,
+
+    /// 2x oversampling
+    Osr2,
+
+    /// Normal mode (default)
+    Normal,
+
+    /// Undersampling, no averaging
+    Avg1,
+
+    /// Undersampling, average of 2 samples
+    Avg2,
+
+    /// Undersampling, average of 4 samples
+    Avg4,
+
+    /// Undersampling, average of 8 samples
+    Avg8,
+
+    /// Undersampling, average of 16 samples
+    Avg16,
+
+    /// Undersampling, average of 32 samples
+    Avg32,
+
+    /// Undersampling, average of 64 samples
+    Avg64,
+
+    /// Undersampling, average of 128 samples
+    Avg128,
+}
+
+impl AccelBandwidth {
+    /// Does this filter mode use undersampling (`acc_us = 1`)?
+    pub fn undersampling(self) -> bool {
+        !matches!(
+            self,
+            AccelBandwidth::Osr4 | AccelBandwidth::Osr2 | AccelBandwidth::Normal
+        )
+    }
+
+    /// `acc_us` and `acc_bwp` bits in their ACC_CONF positions
+    pub(crate) fn bits(self) -> u8 {
+        let bwp = match self {
+            AccelBandwidth::Osr4 | AccelBandwidth::Avg1 => 0b000,
+            AccelBandwidth::Osr2 | AccelBandwidth::Avg2 => 0b001,
+            AccelBandwidth::Normal | AccelBandwidth::Avg4 => 0b010,
+            AccelBandwidth::Avg8 => 0b011,
+            AccelBandwidth::Avg16 => 0b100,
+            AccelBandwidth::Avg32 => 0b101,
+            AccelBandwidth::Avg64 => 0b110,
+            AccelBandwidth::Avg128 => 0b111,
+        };
+
+        (self.undersampling() as u8) << 7 | bwp << 4
+    }
+
+    /// Decode the `acc_us` and `acc_bwp` fields of ACC_CONF
+    pub(crate) fn from_bits(acc_conf: u8) -> Option<Self> {
+        let bwp = (acc_conf >> 4) & 0b111;
+
+        if acc_conf & 0x80 == 0 {
+            return match bwp {
+                0b000 => Some(AccelBandwidth::Osr4),
+                0b001 => Some(AccelBandwidth::Osr2),
+                0b010 => Some(AccelBandwidth::Normal),
+                _ => None,
+            };
+        }
+
+        Some(match bwp {
+            0b000 => AccelBandwidth::Avg1,
+            0b001 => AccelBandwidth::Avg2,
+            0b010 => AccelBandwidth::Avg4,
+            0b011 => AccelBandwidth::Avg8,
+            0b100 => AccelBandwidth::Avg16,
+            0b101 => AccelBandwidth::Avg32,
+            0b110 => AccelBandwidth::Avg64,
+            _ => AccelBandwidth::Avg128,
+        })
+    }
+}
+
+/// Accelerometer g-range (ACC_RANGE)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AccelRange {
+    /// ±2 g (default)
+    G2 = 0b0011,
+
+    /// ±4 g
+    G4 = 0b0101,
+
+    /// ±8 g
+    G8 = 0b1000,
+
+    /// ±16 g
+    G16 = 0b1100,
+}
+
+impl AccelRange {
+    /// Decode the `acc_range` field
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0x0F {
+            0b0011 => Some(AccelRange::G2),
+            0b0101 => Some(AccelRange::G4),
+            0b1000 => Some(AccelRange::G8),
+            0b1100 => Some(AccelRange::G16),
+            _ => None,
+        }
+    }
+
+    /// Sensitivity in LSB per g
+    pub fn lsb_per_g(self) -> f32 {
+        match self {
+            AccelRange::G2 => 16384.0,
+            AccelRange::G4 => 8192.0,
+            AccelRange::G8 => 4096.0,
+            AccelRange::G16 => 2048.0,
+        }
+    }
+
+    /// Full scale in g
+    pub fn g(self) -> f32 {
+        match self {
+            AccelRange::G2 => 2.0,
+            AccelRange::G4 => 4.0,
+            AccelRange::G8 => 8.0,
+            AccelRange::G16 => 16.0,
+        }
+    }
+}
+
+/// Gyroscope output data rate (GYR_CONF `gyr_odr`)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum GyroOdr {
+    /// 25 Hz
+    Hz25 = 0b0110,
+
+    /// 50 Hz
+    Hz50 = 0b0111,
+
+    /// 100 Hz (default)
+    Hz100 = 0b1000,
+
+    /// 200 Hz
+    Hz200 = 0b1001,
+
+    /// 400 Hz
+    Hz400 = 0b1010,
+
+    /// 800 Hz
+    Hz800 = 0b1011,
+
+    /// 1600 Hz
+    Hz1600 = 0b1100,
+
+    /// 3200 Hz
+    Hz3200 = 0b1101,
+}
+
+impl GyroOdr {
+    /// Decode the `gyr_odr` field
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        Some(match bits & 0x0F {
+            0b0110 => GyroOdr::Hz25,
+            0b0111 => GyroOdr::Hz50,
+            0b1000 => GyroOdr::Hz100,
+            0b1001 => GyroOdr::Hz200,
+            0b1010 => GyroOdr::Hz400,
+            0b1011 => GyroOdr::Hz800,
+            0b1100 => GyroOdr::Hz1600,
+            0b1101 => GyroOdr::Hz3200,
+            _ => return None,
+        })
+    }
+
+    /// Get the output data rate in Hz
+    pub fn hz(self) -> f32 {
+        match self {
+            GyroOdr::Hz25 => 25.0,
+            GyroOdr::Hz50 => 50.0,
+            GyroOdr::Hz100 => 100.0,
+            GyroOdr::Hz200 => 200.0,
+            GyroOdr::Hz400 => 400.0,
+            GyroOdr::Hz800 => 800.0,
+            GyroOdr::Hz1600 => 1600.0,
+            GyroOdr::Hz3200 => 3200.0,
+        }
+    }
+}
+
+/// Gyroscope filter mode (GYR_CONF `gyr_bwp`)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum GyroBandwidth {
+    /// 4x oversampling, 3dB cutoff at ~ODR/10
+    Osr4 = 0b00,
+
+    /// 2x oversampling, 3dB cutoff at ~ODR/5
+    Osr2 = 0b01,
+
+    /// Normal mode, 3dB cutoff at ~ODR/2.5 (default)
+    Normal = 0b10,
+}
+
+impl GyroBandwidth {
+    /// Decode the `gyr_bwp` field
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0b11 {
+            0b00 => Some(GyroBandwidth::Osr4),
+            0b01 => Some(GyroBandwidth::Osr2),
+            0b10 => Some(GyroBandwidth::Normal),
+            _ => None,
+        }
+    }
+}
+
+/// Gyroscope angular rate measurement range (GYR_RANGE)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum GyroRange {
+    /// ±2000 °/s (default)
+    Dps2000 = 0b000,
+
+    /// ±1000 °/s
+    Dps1000 = 0b001,
+
+    /// ±500 °/s
+    Dps500 = 0b010,
+
+    /// ±250 °/s
+    Dps250 = 0b011,
+
+    /// ±125 °/s
+    Dps125 = 0b100,
+}
+
+impl GyroRange {
+    /// Decode the `gyr_range` field
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits & 0b111 {
+            0b000 => Some(GyroRange::Dps2000),
+            0b001 => Some(GyroRange::Dps1000),
+            0b010 => Some(GyroRange::Dps500),
+            0b011 => Some(GyroRange::Dps250),
+            0b100 => Some(GyroRange::Dps125),
+            _ => None,
+        }
+    }
+
+    /// Sensitivity in LSB per °/s
+    pub fn lsb_per_dps(self) -> f32 {
+        match self {
+            GyroRange::Dps2000 => 16.4,
+            GyroRange::Dps1000 => 32.8,
+            GyroRange::Dps500 => 65.6,
+            GyroRange::Dps250 => 131.2,
+            GyroRange::Dps125 => 262.4,
+        }
+    }
+}
+
+/// SPI wire mode (IF_CONF `spi3`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SpiWireMode {
+    /// Standard 4-wire SPI (default)
+    #[default]
+    FourWire = 0,
+
+    /// 3-wire SPI, with SDI used for both directions
+    ThreeWire = 1,
+}
+
+/// Secondary interface mode (IF_CONF `if_mode`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SecondaryInterface {
+    /// Secondary interface off, primary interface auto-configured (default)
+    #[default]
+    Off = 0b00,
+
+    /// Secondary interface serves OIS. Primary interface is fixed to I2C.
+    Ois = 0b01,
+
+    /// Secondary interface drives an attached magnetometer, primary
+    /// interface auto-configured
+    Magnetometer = 0b10,
+}
+
+impl SecondaryInterface {
+    /// Decode the `if_mode` field. The reserved value reads as `Off`.
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b01 => SecondaryInterface::Ois,
+            0b10 => SecondaryInterface::Magnetometer,
+            _ => SecondaryInterface::Off,
+        }
+    }
+}
+
+bitflags! {
+    /// STATUS register (0x1B) flags
+    pub struct StatusFlags: u8 {
+        /// Gyroscope self-test passed
+        const GYR_SELF_TEST_OK = 0b0000_0010;
+
+        /// A manual magnetometer interface operation is in progress
+        const MAG_MAN_OP = 0b0000_0100;
+
+        /// Fast offset compensation has completed
+        const FOC_RDY = 0b0000_1000;
+
+        /// NVM is ready for a new write
+        const NVM_RDY = 0b0001_0000;
+
+        /// New magnetometer data is available
+        const DRDY_MAG = 0b0010_0000;
+
+        /// New gyroscope data is available
+        const DRDY_GYR = 0b0100_0000;
+
+        /// New accelerometer data is available
+        const DRDY_ACC = 0b1000_0000;
+    }
+}
+
+bitflags! {
+    /// CONF register (0x6A) flags
+    pub struct ConfFlags: u8 {
+        /// Enable NVM programming
+        const NVM_PROG_EN = 0b0000_0010;
+    }
+}
+
+bitflags! {
+    /// NV_CONF register (0x70) flags
+    pub struct NvConfFlags: u8 {
+        /// Permanently select SPI as the primary interface
+        const SPI_EN = 0b0000_0001;
+
+        /// I2C watchdog timeout: set for 50 ms, clear for 1 ms
+        const I2C_WDT_SEL = 0b0000_0010;
+
+        /// Enable the I2C watchdog
+        const I2C_WDT_EN = 0b0000_0100;
+    }
+}
+
+/// I2C watchdog setting (NV_CONF `i2c_wdt_en` and `i2c_wdt_sel`)
+///
+/// When enabled, the watchdog releases SDA if the bus is held by the
+/// BMI160 for longer than the timeout, recovering from a hung transfer.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum I2cWatchdog {
+    /// Watchdog disabled (default)
+    #[default]
+    Disabled,
+
+    /// Watchdog enabled with a 1 ms timeout
+    Ms1,
+
+    /// Watchdog enabled with a 50 ms timeout
+    Ms50,
+}
+
+bitflags! {
+    /// FIFO_CONFIG_1 register (0x47) flags
+    pub struct FifoConfigFlags: u8 {
+        /// Store gyroscope data in the FIFO
+        const GYR_EN = 0b1000_0000;
+
+        /// Store accelerometer data in the FIFO
+        const ACC_EN = 0b0100_0000;
+
+        /// Store magnetometer data in the FIFO
+        const MAG_EN = 0b0010_0000;
+
+        /// Header mode (set) or headerless mode (clear)
+        const HEADER_EN = 0b0001_0000;
+
+        /// Tag INT1 events into the FIFO
+        const TAG_INT1_EN = 0b0000_1000;
+
+        /// Tag INT2 events into the FIFO
+        const TAG_INT2_EN = 0b0000_0100;
+
+        /// Return a sensortime frame after the last valid data frame
+        const TIME_EN = 0b0000_0010;
+    }
+}
+
+bitflags! {
+    /// Payload of a FIFO Fifo_Input_Config (configuration change) frame
+    pub struct FifoConfigChange: u8 {
+        /// Accelerometer configuration (ACC_CONF/ACC_RANGE) changed
+        const ACCEL = 0b0000_0001;
+
+        /// Gyroscope configuration (GYR_CONF/GYR_RANGE) changed
+        const GYRO = 0b0000_0010;
+    }
+}
+
+bitflags! {
+    /// Interrupt engine enables, covering INT_EN_0..2 (0x50-0x52).
+    ///
+    /// Bits 0-7 map to INT_EN_0, bits 8-15 to INT_EN_1 and bits 16-23 to
+    /// INT_EN_2, so `bits().to_le_bytes()` gives the register contents.
+    #[derive(Default)]
+    pub struct InterruptEnable: u32 {
+        /// Any-motion (slope) on the X axis
+        const ANY_MOTION_X = 1 << 0;
+
+        /// Any-motion (slope) on the Y axis
+        const ANY_MOTION_Y = 1 << 1;
+
+        /// Any-motion (slope) on the Z axis
+        const ANY_MOTION_Z = 1 << 2;
+
+        /// Double tap
+        const DOUBLE_TAP = 1 << 4;
+
+        /// Single tap
+        const SINGLE_TAP = 1 << 5;
+
+        /// Orientation change
+        const ORIENTATION = 1 << 6;
+
+        /// Flat detection
+        const FLAT = 1 << 7;
+
+        /// High-g on the X axis
+        const HIGH_G_X = 1 << 8;
+
+        /// High-g on the Y axis
+        const HIGH_G_Y = 1 << 9;
+
+        /// High-g on the Z axis
+        const HIGH_G_Z = 1 << 10;
+
+        /// Low-g (free fall)
+        const LOW_G = 1 << 11;
+
+        /// Data ready
+        const DATA_READY = 1 << 12;
+
+        /// FIFO full
+        const FIFO_FULL = 1 << 13;
+
+        /// FIFO watermark
+        const FIFO_WATERMARK = 1 << 14;
+
+        /// No-motion / slow-motion on the X axis
+        const NO_MOTION_X = 1 << 16;
+
+        /// No-motion / slow-motion on the Y axis
+        const NO_MOTION_Y = 1 << 17;
+
+        /// No-motion / slow-motion on the Z axis
+        const NO_MOTION_Z = 1 << 18;
+
+        /// Step detector
+        const STEP_DETECTOR = 1 << 19;
+
+        /// Any-motion on all axes
+        const ANY_MOTION = Self::ANY_MOTION_X.bits | Self::ANY_MOTION_Y.bits | Self::ANY_MOTION_Z.bits;
+
+        /// High-g on all axes
+        const HIGH_G = Self::HIGH_G_X.bits | Self::HIGH_G_Y.bits | Self::HIGH_G_Z.bits;
+
+        /// No-motion / slow-motion on all axes
+        const NO_MOTION = Self::NO_MOTION_X.bits | Self::NO_MOTION_Y.bits | Self::NO_MOTION_Z.bits;
+    }
+}
+
+/// Interrupt latching mode (INT_LATCH `int_latch`)
+///
+/// Temporary modes hold the interrupt output for the given time after the
+/// condition clears; `Latched` holds it until the interrupt is reset.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum InterruptLatch {
+    /// Non-latched (default)
+    #[default]
+    None = 0b0000,
+
+    /// Temporary, 312.5 µs
+    Us312_5 = 0b0001,
+
+    /// Temporary, 625 µs
+    Us625 = 0b0010,
+
+    /// Temporary, 1.25 ms
+    Ms1_25 = 0b0011,
+
+    /// Temporary, 2.5 ms
+    Ms2_5 = 0b0100,
+
+    /// Temporary, 5 ms
+    Ms5 = 0b0101,
+
+    /// Temporary, 10 ms
+    Ms10 = 0b0110,
+
+    /// Temporary, 20 ms
+    Ms20 = 0b0111,
+
+    /// Temporary, 40 ms
+    Ms40 = 0b1000,
+
+    /// Temporary, 80 ms
+    Ms80 = 0b1001,
+
+    /// Temporary, 160 ms
+    Ms160 = 0b1010,
+
+    /// Temporary, 320 ms
+    Ms320 = 0b1011,
+
+    /// Temporary, 640 ms
+    Ms640 = 0b1100,
+
+    /// Temporary, 1.28 s
+    Ms1280 = 0b1101,
+
+    /// Temporary, 2.56 s
+    Ms2560 = 0b1110,
+
+    /// Latched until reset
+    Latched = 0b1111,
+}
+
+impl InterruptLatch {
+    /// Decode the `int_latch` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0x0F {
+            0b0000 => InterruptLatch::None,
+            0b0001 => InterruptLatch::Us312_5,
+            0b0010 => InterruptLatch::Us625,
+            0b0011 => InterruptLatch::Ms1_25,
+            0b0100 => InterruptLatch::Ms2_5,
+            0b0101 => InterruptLatch::Ms5,
+            0b0110 => InterruptLatch::Ms10,
+            0b0111 => InterruptLatch::Ms20,
+            0b1000 => InterruptLatch::Ms40,
+            0b1001 => InterruptLatch::Ms80,
+            0b1010 => InterruptLatch::Ms160,
+            0b1011 => InterruptLatch::Ms320,
+            0b1100 => InterruptLatch::Ms640,
+            0b1101 => InterruptLatch::Ms1280,
+            0b1110 => InterruptLatch::Ms2560,
+            _ => InterruptLatch::Latched,
+        }
+    }
+
+    /// Hold time in milliseconds, `None` for non-latched and latched modes
+    pub fn millis(self) -> Option<f32> {
+        match self {
+            InterruptLatch::None | InterruptLatch::Latched => None,
+            // 312.5 µs, doubled for every step
+            temporary => Some(0.3125 * (1u32 << (temporary as u8 - 1)) as f32),
+        }
+    }
+}
+
+bitflags! {
+    /// Interrupt engines that fired, covering INT_STATUS_0 and INT_STATUS_1.
+    ///
+    /// Bits 0-7 map to INT_STATUS_0 and bits 8-15 to INT_STATUS_1.
+    pub struct InterruptFlags: u16 {
+        /// Step detector
+        const STEP = 1 << 0;
+
+        /// Significant motion
+        const SIGNIFICANT_MOTION = 1 << 1;
+
+        /// Any-motion
+        const ANY_MOTION = 1 << 2;
+
+        /// PMU trigger
+        const PMU_TRIGGER = 1 << 3;
+
+        /// Double tap
+        const DOUBLE_TAP = 1 << 4;
+
+        /// Single tap
+        const SINGLE_TAP = 1 << 5;
+
+        /// Orientation change
+        const ORIENTATION = 1 << 6;
+
+        /// Flat
+        const FLAT = 1 << 7;
+
+        /// High-g
+        const HIGH_G = 1 << 10;
+
+        /// Low-g
+        const LOW_G = 1 << 11;
+
+        /// Data ready
+        const DATA_READY = 1 << 12;
+
+        /// FIFO full
+        const FIFO_FULL = 1 << 13;
+
+        /// FIFO watermark
+        const FIFO_WATERMARK = 1 << 14;
+
+        /// No-motion / slow-motion
+        const NO_MOTION = 1 << 15;
+    }
+}
+
+/// Time window for the second tap of a double tap (INT_TAP `int_tap_dur`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum TapDuration {
+    /// 50 ms
+    Ms50 = 0b000,
+
+    /// 100 ms
+    Ms100 = 0b001,
+
+    /// 150 ms
+    Ms150 = 0b010,
+
+    /// 200 ms
+    Ms200 = 0b011,
+
+    /// 250 ms (default)
+    #[default]
+    Ms250 = 0b100,
+
+    /// 375 ms
+    Ms375 = 0b101,
+
+    /// 500 ms
+    Ms500 = 0b110,
+
+    /// 700 ms
+    Ms700 = 0b111,
+}
+
+impl TapDuration {
+    /// Decode the `int_tap_dur` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b111 {
+            0b000 => TapDuration::Ms50,
+            0b001 => TapDuration::Ms100,
+            0b010 => TapDuration::Ms150,
+            0b011 => TapDuration::Ms200,
+            0b100 => TapDuration::Ms250,
+            0b101 => TapDuration::Ms375,
+            0b110 => TapDuration::Ms500,
+            _ => TapDuration::Ms700,
+        }
+    }
+}
+
+/// Time during which the tap engine ignores further over-threshold
+/// samples after a tap (INT_TAP `int_tap_shock`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TapShock {
+    /// 50 ms (default)
+    #[default]
+    Ms50,
+
+    /// 75 ms
+    Ms75,
+}
+
+/// Time the signal must stay below threshold after a tap for it to count
+/// (INT_TAP `int_tap_quiet`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TapQuiet {
+    /// 30 ms (default)
+    #[default]
+    Ms30,
+
+    /// 20 ms
+    Ms20,
+}
+
+/// Significant motion skip time (INT_MOTION `int_sig_mot_skip`)
+///
+/// After the first motion is detected, the engine ignores motion for this
+/// long before looking for the proof motion.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SigMotionSkip {
+    /// 1.5 s
+    Ms1500 = 0b00,
+
+    /// 3 s (default)
+    #[default]
+    S3 = 0b01,
+
+    /// 6 s
+    S6 = 0b10,
+
+    /// 12 s
+    S12 = 0b11,
+}
+
+impl SigMotionSkip {
+    /// Decode the `int_sig_mot_skip` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => SigMotionSkip::Ms1500,
+            0b01 => SigMotionSkip::S3,
+            0b10 => SigMotionSkip::S6,
+            _ => SigMotionSkip::S12,
+        }
+    }
+}
+
+/// Significant motion proof time (INT_MOTION `int_sig_mot_proof`)
+///
+/// Motion must be detected again within this window after the skip time
+/// for the interrupt to fire.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SigMotionProof {
+    /// 0.25 s
+    Ms250 = 0b00,
+
+    /// 0.5 s (default)
+    #[default]
+    Ms500 = 0b01,
+
+    /// 1 s
+    S1 = 0b10,
+
+    /// 2 s
+    S2 = 0b11,
+}
+
+impl SigMotionProof {
+    /// Decode the `int_sig_mot_proof` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => SigMotionProof::Ms250,
+            0b01 => SigMotionProof::Ms500,
+            0b10 => SigMotionProof::S1,
+            _ => SigMotionProof::S2,
+        }
+    }
+}
+
+/// Orientation thresholds (INT_ORIENT `int_orient_mode`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum OrientMode {
+    /// Symmetrical thresholds (default)
+    #[default]
+    Symmetrical = 0b00,
+
+    /// High-asymmetrical thresholds
+    HighAsymmetrical = 0b01,
+
+    /// Low-asymmetrical thresholds
+    LowAsymmetrical = 0b10,
+}
+
+impl OrientMode {
+    /// Decode the `int_orient_mode` field. `0b11` is symmetrical as well.
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b01 => OrientMode::HighAsymmetrical,
+            0b10 => OrientMode::LowAsymmetrical,
+            _ => OrientMode::Symmetrical,
+        }
+    }
+}
+
+/// Conditions that block orientation changes (INT_ORIENT
+/// `int_orient_blocking`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum OrientBlocking {
+    /// No blocking
+    None = 0b00,
+
+    /// Block while tilted past theta or any axis exceeds 1.5 g
+    Theta = 0b01,
+
+    /// Block while tilted past theta, any axis exceeds 1.5 g or the slope
+    /// exceeds 0.2 g (default)
+    #[default]
+    ThetaSlow = 0b10,
+
+    /// Block while tilted past theta, any axis exceeds 1.5 g or the slope
+    /// exceeds 0.4 g
+    ThetaFast = 0b11,
+}
+
+impl OrientBlocking {
+    /// Decode the `int_orient_blocking` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => OrientBlocking::None,
+            0b01 => OrientBlocking::Theta,
+            0b10 => OrientBlocking::ThetaSlow,
+            _ => OrientBlocking::ThetaFast,
+        }
+    }
+}
+
+/// Time the device must stay flat before the flat interrupt fires
+/// (INT_FLAT `int_flat_hold`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FlatHold {
+    /// Immediately
+    Ms0 = 0b00,
+
+    /// 640 ms (default)
+    #[default]
+    Ms640 = 0b01,
+
+    /// 1.28 s
+    Ms1280 = 0b10,
+
+    /// 2.56 s
+    Ms2560 = 0b11,
+}
+
+impl FlatHold {
+    /// Decode the `int_flat_hold` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => FlatHold::Ms0,
+            0b01 => FlatHold::Ms640,
+            0b10 => FlatHold::Ms1280,
+            _ => FlatHold::Ms2560,
+        }
+    }
+}
+
+bitflags! {
+    /// PMU_TRIGGER register (0x6C) flags: automatic gyroscope sleep and
+    /// wakeup
+    pub struct PmuTriggerFlags: u8 {
+        /// Put the gyroscope to sleep on no-motion
+        const SLEEP_ON_NO_MOTION = 0b0000_0001;
+
+        /// Put the gyroscope to sleep on an INT1 input edge
+        const SLEEP_ON_INT1 = 0b0000_0010;
+
+        /// Put the gyroscope to sleep on an INT2 input edge
+        const SLEEP_ON_INT2 = 0b0000_0100;
+
+        /// Wake the gyroscope on any-motion
+        const WAKE_ON_ANY_MOTION = 0b0000_1000;
+
+        /// Wake the gyroscope on an input edge on the enabled INT input pin
+        const WAKE_ON_INT = 0b0001_0000;
+
+        /// Sleep in suspend rather than fast start-up
+        const SLEEP_SUSPEND = 0b0010_0000;
+
+        /// Raise the PMU trigger interrupt on wakeup
+        const WAKEUP_INT = 0b0100_0000;
+    }
+}
+
+/// Accelerometer fast offset compensation target for one axis
+/// (FOC_CONF `foc_acc_x/y/z`): the value the axis should read while the
+/// device is held still
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FocAccelTarget {
+    /// Don't compensate this axis (default)
+    #[default]
+    Disabled = 0b00,
+
+    /// +1 g, the axis points up
+    PlusOneG = 0b01,
+
+    /// -1 g, the axis points down
+    MinusOneG = 0b10,
+
+    /// 0 g, the axis is horizontal
+    Zero = 0b11,
+}
+
+impl FocAccelTarget {
+    /// Decode a `foc_acc_x/y/z` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => FocAccelTarget::Disabled,
+            0b01 => FocAccelTarget::PlusOneG,
+            0b10 => FocAccelTarget::MinusOneG,
+            _ => FocAccelTarget::Zero,
+        }
+    }
+}
+
+/// Number of bytes read from the magnetometer per read loop cycle
+/// (MAG_IF[1] `mag_rd_burst`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum MagBurst {
+    /// 1 byte
+    Bytes1 = 0b00,
+
+    /// 2 bytes
+    Bytes2 = 0b01,
+
+    /// 6 bytes
+    Bytes6 = 0b10,
+
+    /// 8 bytes (default)
+    #[default]
+    Bytes8 = 0b11,
+}
+
+impl MagBurst {
+    /// Decode the `mag_rd_burst` field
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => MagBurst::Bytes1,
+            0b01 => MagBurst::Bytes2,
+            0b10 => MagBurst::Bytes6,
+            _ => MagBurst::Bytes8,
+        }
+    }
+}
+
+/// Magnetometer interface read loop rate (MAG_CONF `mag_odr`)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum MagOdr {
+    /// 25/32 Hz
+    Hz0_78 = 0b0001,
+
+    /// 25/16 Hz
+    Hz1_56 = 0b0010,
+
+    /// 25/8 Hz
+    Hz3_12 = 0b0011,
+
+    /// 25/4 Hz
+    Hz6_25 = 0b0100,
+
+    /// 25/2 Hz
+    Hz12_5 = 0b0101,
+
+    /// 25 Hz
+    Hz25 = 0b0110,
+
+    /// 50 Hz
+    Hz50 = 0b0111,
+
+    /// 100 Hz
+    Hz100 = 0b1000,
+
+    /// 200 Hz
+    Hz200 = 0b1001,
+
+    /// 400 Hz
+    Hz400 = 0b1010,
+
+    /// 800 Hz (default)
+    #[default]
+    Hz800 = 0b1011,
+}
+
+impl MagOdr {
+    /// Decode the `mag_odr` field
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        Some(match bits & 0x0F {
+            0b0001 => MagOdr::Hz0_78,
+            0b0010 => MagOdr::Hz1_56,
+            0b0011 => MagOdr::Hz3_12,
+            0b0100 => MagOdr::Hz6_25,
+            0b0101 => MagOdr::Hz12_5,
+            0b0110 => MagOdr::Hz25,
+            0b0111 => MagOdr::Hz50,
+            0b1000 => MagOdr::Hz100,
+            0b1001 => MagOdr::Hz200,
+            0b1010 => MagOdr::Hz400,
+            0b1011 => MagOdr::Hz800,
+            _ => return None,
+        })
+    }
+
+    /// Get the read loop rate in Hz
+    pub fn hz(self) -> f32 {
+        match self {
+            MagOdr::Hz0_78 => 0.78125,
+            MagOdr::Hz1_56 => 1.5625,
+            MagOdr::Hz3_12 => 3.125,
+            MagOdr::Hz6_25 => 6.25,
+            MagOdr::Hz12_5 => 12.5,
+            MagOdr::Hz25 => 25.0,
+            MagOdr::Hz50 => 50.0,
+            MagOdr::Hz100 => 100.0,
+            MagOdr::Hz200 => 200.0,
+            MagOdr::Hz400 => 400.0,
+            MagOdr::Hz800 => 800.0,
+        }
+    }
 }
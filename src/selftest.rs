@@ -0,0 +1,187 @@
+//! Built-in self-test (SELF_TEST)
+
+use crate::config::AccelConfig;
+use crate::interface::Interface;
+use crate::register::{AccelBandwidth, AccelOdr, AccelRange, Register};
+use crate::{Bmi160, Error};
+use hal::blocking::delay::DelayMs;
+
+/// SELF_TEST `acc_self_test_enable`
+const SELF_TEST_ACC_EN: u8 = 0b0000_0001;
+
+/// SELF_TEST `acc_self_test_sign`, set for positive deflection
+const SELF_TEST_ACC_SIGN: u8 = 0b0000_0100;
+
+/// SELF_TEST `acc_self_test_amp`, set for high amplitude
+const SELF_TEST_ACC_AMP: u8 = 0b0000_1000;
+
+/// Settling time after changing the self-test deflection, in milliseconds
+const SELF_TEST_SETTLE_MS: u8 = 50;
+
+/// Accelerometer setup the datasheet prescribes for the self-test
+const SELF_TEST_CONFIG: AccelConfig = AccelConfig {
+    odr: AccelOdr::Hz1600,
+    bandwidth: AccelBandwidth::Normal,
+    range: AccelRange::G8,
+};
+
+/// Minimum difference between the positive and negative deflection on the
+/// X, Y and Z axis, in mg
+pub const ACCEL_SELF_TEST_MIN_MG: [f32; 3] = [2000.0, 2000.0, 1000.0];
+
+/// Result of the accelerometer self-test
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AccelSelfTest {
+    /// Difference between the positive and negative deflection per axis,
+    /// in mg. The sign depends on which way the part deflects; only the
+    /// magnitude matters.
+    pub difference_mg: [f32; 3],
+}
+
+impl AccelSelfTest {
+    /// Per-axis pass/fail: did the X, Y and Z deflection reach
+    /// [`ACCEL_SELF_TEST_MIN_MG`] in either direction?
+    pub fn axes(&self) -> [bool; 3] {
+        let pass = |axis: usize| self.difference_mg[axis].abs() >= ACCEL_SELF_TEST_MIN_MG[axis];
+        [pass(0), pass(1), pass(2)]
+    }
+
+    /// Did all three axes pass?
+    pub fn passed(&self) -> bool {
+        self.axes().iter().all(|&pass| pass)
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Run the accelerometer self-test.
+    ///
+    /// Puts the accelerometer in normal mode at 1600 Hz and ±8 g, the setup
+    /// the datasheet prescribes (a 2 g deflection would clip at ±2 g),
+    /// then measures the high amplitude positive and negative deflections
+    /// after letting each settle for 50 ms. The previous accelerometer
+    /// configuration and power mode are restored afterwards, including
+    /// when the test fails. Keep the device still while it runs.
+    ///
+    /// ACC_CONF is only changed while the accelerometer is suspended, so a
+    /// low power setup with undersampling is left and restored without
+    /// ever running undersampled in normal mode.
+    pub fn accel_self_test<D>(&mut self, delay: &mut D) -> Result<AccelSelfTest, Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let previous = self.get_accel_config()?;
+        let previous_mode = self.get_power_status()?.accel;
+
+        self.enter_accel_normal_mode(SELF_TEST_CONFIG, delay)?;
+        let result = self.accel_self_test_deflections(delay);
+
+        // Always leave self-test mode and restore the previous setup: the
+        // range while still in normal mode, ACC_CONF in suspend
+        self.write_register(Register::SELF_TEST, 0)?;
+        self.apply_accel_config(AccelConfig {
+            range: previous.range,
+            ..SELF_TEST_CONFIG
+        })?;
+        self.switch_accel_mode(previous_mode, previous, delay)?;
+
+        result
+    }
+
+    /// Measure both deflections with the accelerometer in normal mode
+    fn accel_self_test_deflections<D>(&mut self, delay: &mut D) -> Result<AccelSelfTest, Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let range = SELF_TEST_CONFIG.range;
+        self.apply_accel_config(SELF_TEST_CONFIG)?;
+
+        self.write_register(
+            Register::SELF_TEST,
+            SELF_TEST_ACC_EN | SELF_TEST_ACC_AMP | SELF_TEST_ACC_SIGN,
+        )?;
+        delay.delay_ms(SELF_TEST_SETTLE_MS);
+        let positive = self.read_data()?.accel.scaled(range.lsb_per_g());
+
+        self.write_register(Register::SELF_TEST, SELF_TEST_ACC_EN | SELF_TEST_ACC_AMP)?;
+        delay.delay_ms(SELF_TEST_SETTLE_MS);
+        let negative = self.read_data()?.accel.scaled(range.lsb_per_g());
+
+        let mg = |axis: usize| (positive[axis] - negative[axis]) * 1000.0;
+        Ok(AccelSelfTest {
+            difference_mg: [mg(0), mg(1), mg(2)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Preset;
+    use crate::mock::{Mock, NoDelay};
+    use crate::register::AccelPowerMode;
+
+    #[test]
+    fn pass_uses_magnitude() {
+        let result = AccelSelfTest {
+            difference_mg: [-2100.0, 2100.0, -1100.0],
+        };
+        assert_eq!(result.axes(), [true, true, true]);
+        assert!(result.passed());
+    }
+
+    #[test]
+    fn per_axis_limits() {
+        let result = AccelSelfTest {
+            difference_mg: [1999.0, -2000.0, 1000.0],
+        };
+        assert_eq!(result.axes(), [false, true, true]);
+        assert!(!result.passed());
+
+        let result = AccelSelfTest {
+            difference_mg: [2000.0, 2000.0, -999.0],
+        };
+        assert_eq!(result.axes(), [true, true, false]);
+    }
+
+    #[test]
+    fn self_test_from_low_power() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.configure(&Preset::Pedometer.config(), &mut NoDelay)
+            .unwrap();
+        let previous = imu.get_accel_config().unwrap();
+        assert!(previous.bandwidth.undersampling());
+
+        // 2.5 g, -2.5 g and 1.25 g at ±8 g: 5000, -5000 and 2500 mg apart
+        imu.iface.self_test_deflection = [10240, -10240, 5120];
+        let result = imu.accel_self_test(&mut NoDelay).unwrap();
+
+        assert_eq!(result.difference_mg, [5000.0, -5000.0, 2500.0]);
+        assert!(result.passed());
+
+        let mock = &imu.iface;
+        assert!(!mock.invalid_acc_conf);
+        assert_eq!(mock.acc_pmu(), 0b10);
+        assert_eq!(mock.reg(Register::SELF_TEST), 0);
+        assert_eq!(imu.get_accel_config().unwrap(), previous);
+        assert_eq!(imu.accel_range(), previous.range);
+    }
+
+    #[test]
+    fn self_test_failure_restores_normal_mode() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.set_accel_power_mode(AccelPowerMode::Normal, &mut NoDelay)
+            .unwrap();
+        let previous = imu.get_accel_config().unwrap();
+
+        imu.iface.self_test_deflection = [10240, 10240, 1024];
+        let result = imu.accel_self_test(&mut NoDelay).unwrap();
+
+        assert_eq!(result.axes(), [true, true, false]);
+        assert!(!imu.iface.invalid_acc_conf);
+        assert_eq!(imu.iface.acc_pmu(), 0b01);
+        assert_eq!(imu.get_accel_config().unwrap(), previous);
+    }
+}
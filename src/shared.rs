@@ -0,0 +1,93 @@
+//! Running the driver on an I2C bus shared with other devices
+//!
+//! Bus-sharing wrappers (e.g. `shared-bus` proxies, or `embedded-hal-bus`
+//! devices with the `embedded-hal-1` feature) implement the bus traits and
+//! can be passed to [`Bmi160::new`] directly. Without such a wrapper, a
+//! driver from [`Bmi160::new_shared`] keeps only its state and borrows the
+//! bus for each call through [`Bmi160::with_bus`].
+
+use crate::interface::I2cInterface;
+use crate::Bmi160;
+use hal::blocking::i2c::{Write, WriteRead};
+
+/// Placeholder interface of a driver that doesn't own its bus, see
+/// [`Bmi160::new_shared`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SharedI2c {
+    address: u8,
+}
+
+/// I2C bus borrowed for one [`Bmi160::with_bus`] call
+#[derive(Debug)]
+pub struct BusRef<'a, I2C>(&'a mut I2C);
+
+impl<'a, I2C, E> Write for BusRef<'a, I2C>
+where
+    I2C: Write<Error = E>,
+{
+    type Error = E;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), E> {
+        self.0.write(address, bytes)
+    }
+}
+
+impl<'a, I2C, E> WriteRead for BusRef<'a, I2C>
+where
+    I2C: WriteRead<Error = E>,
+{
+    type Error = E;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), E> {
+        self.0.write_read(address, bytes, buffer)
+    }
+}
+
+impl<DI> Bmi160<DI> {
+    /// Move the driver state onto another interface
+    fn rebind<B>(&self, iface: B) -> Bmi160<B> {
+        Bmi160 {
+            iface,
+            accel_range: self.accel_range,
+            gyro_range: self.gyro_range,
+            max_transfer_len: self.max_transfer_len,
+            fifo_stats: self.fifo_stats,
+            masked_interrupts: self.masked_interrupts,
+            mg_thresholds: self.mg_thresholds,
+            gyro_bias: self.gyro_bias,
+            accel_correction: self.accel_correction,
+            bmm150: self.bmm150,
+        }
+    }
+}
+
+impl Bmi160<SharedI2c> {
+    /// Create a driver for the BMI160 at the given I2C `address` that
+    /// doesn't own a bus. Every access goes through [`Bmi160::with_bus`].
+    pub fn new_shared(address: u8) -> Self {
+        Bmi160::with_defaults(SharedI2c { address })
+    }
+
+    /// Run `f` on the driver with `i2c` borrowed for the duration of the
+    /// call.
+    ///
+    /// Driver state such as the cached ranges, software calibration and
+    /// FIFO counters carries over between calls, so one I2C peripheral can
+    /// serve the BMI160 and other devices in turn:
+    ///
+    /// ```ignore
+    /// let mut imu = Bmi160::new_shared(bmi160::ADDRESS);
+    /// let data = imu.with_bus(&mut i2c, |imu| imu.read_data())?;
+    /// other_sensor.read(&mut i2c)?;
+    /// ```
+    pub fn with_bus<'a, I2C, E, F, R>(&mut self, i2c: &'a mut I2C, f: F) -> R
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E>,
+        F: FnOnce(&mut Bmi160<I2cInterface<BusRef<'a, I2C>>>) -> R,
+    {
+        let mut bound = self.rebind(I2cInterface::new(BusRef(i2c), self.iface.address));
+        let result = f(&mut bound);
+        *self = bound.rebind(self.iface);
+        result
+    }
+}
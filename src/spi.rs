@@ -0,0 +1,280 @@
+//! 4-wire and 3-wire SPI interfaces
+//!
+//! The BMI160 starts up in I2C mode and switches to SPI on the first rising
+//! edge of CSB. Registers are read by setting bit 7 of the address byte and
+//! written with it cleared; burst accesses auto-increment the address like
+//! on I2C. In 3-wire mode (IF_CONF `spi3`) the device answers on SDx
+//! instead of SDO, so reads are half-duplex.
+
+use crate::config::InterfaceConfig;
+use crate::interface::Interface;
+use crate::register::{Register, SpiWireMode};
+use crate::{Bmi160, Error};
+use hal::blocking::spi;
+use hal::digital::v2::OutputPin;
+
+/// Read flag set in the address byte of SPI reads
+pub(crate) const SPI_READ: u8 = 0x80;
+
+/// Register accessed once after power-up to switch the device to SPI mode.
+/// Any register works; the datasheet suggests 0x7F.
+pub(crate) const SPI_DUMMY_REGISTER: u8 = 0x7F;
+
+/// SPI interface errors
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SpiError<S, P> {
+    /// Error from the SPI peripheral
+    Spi(S),
+
+    /// Error driving the chip select pin
+    Pin(P),
+}
+
+/// Half-duplex reads for 3-wire SPI, where SDx carries both directions.
+///
+/// Implement this for an SPI peripheral in bidirectional mode: `read`
+/// releases the data line and clocks in `buffer.len()` bytes. Writes use
+/// [`spi::Write`] as usual.
+pub trait SpiHalfDuplex {
+    /// SPI error
+    type Error;
+
+    /// Clock in `buffer.len()` bytes without driving the data line
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+/// Run `f` with the active-low chip select asserted. CS is released even if
+/// `f` fails.
+fn select<SPI, CS, S, P, F>(spi: &mut SPI, cs: &mut CS, f: F) -> Result<(), SpiError<S, P>>
+where
+    CS: OutputPin<Error = P>,
+    F: FnOnce(&mut SPI) -> Result<(), S>,
+{
+    cs.set_low().map_err(SpiError::Pin)?;
+    let result = f(spi).map_err(SpiError::Spi);
+    cs.set_high().map_err(SpiError::Pin)?;
+    result
+}
+
+/// SPI bus and active-low chip select.
+///
+/// The SPI peripheral must use mode 0 or mode 3 at up to 10 MHz. The read
+/// flag and the switch to SPI mode are handled here, so register accesses
+/// look the same as on I2C.
+#[derive(Debug)]
+pub struct SpiInterface<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS, S, P> SpiInterface<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = S> + spi::Write<u8, Error = S>,
+    CS: OutputPin<Error = P>,
+{
+    /// Take the bus and switch the device to SPI mode.
+    ///
+    /// Drives chip select high, then does a dummy read so the rising edge
+    /// of CSB at its end switches the device from I2C to SPI mode. The
+    /// device stays in SPI mode until the next power cycle or soft reset.
+    pub fn new(spi: SPI, mut cs: CS) -> Result<Self, SpiError<S, P>> {
+        cs.set_high().map_err(SpiError::Pin)?;
+
+        let mut interface = SpiInterface { spi, cs };
+        interface.read(SPI_DUMMY_REGISTER, &mut [0u8])?;
+        Ok(interface)
+    }
+}
+
+impl<SPI, CS, S, P> Interface for SpiInterface<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = S> + spi::Write<u8, Error = S>,
+    CS: OutputPin<Error = P>,
+{
+    type Error = SpiError<S, P>;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        select(&mut self.spi, &mut self.cs, |spi| spi.write(bytes))
+    }
+
+    fn write_registers(&mut self, register: u8, data: &[u8]) -> Result<(), Self::Error> {
+        select(&mut self.spi, &mut self.cs, |spi| {
+            spi.write(&[register])?;
+            spi.write(data)
+        })
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        select(&mut self.spi, &mut self.cs, |spi| {
+            spi.write(&[register | SPI_READ])?;
+            for byte in buffer.iter_mut() {
+                *byte = 0;
+            }
+            spi.transfer(buffer)?;
+            Ok(())
+        })
+    }
+}
+
+impl<SPI, CS, S, P> Bmi160<SpiInterface<SPI, CS>>
+where
+    SPI: spi::Transfer<u8, Error = S> + spi::Write<u8, Error = S>,
+    CS: OutputPin<Error = P>,
+{
+    /// Create a new BMI160 driver on a 4-wire SPI bus, see
+    /// [`SpiInterface::new`]
+    pub fn new_spi(spi: SPI, cs: CS) -> Result<Self, Error<SpiError<S, P>>> {
+        Self::from_interface(SpiInterface::new(spi, cs).map_err(Error::Bus)?)
+    }
+}
+
+/// Half-duplex SPI bus and active-low chip select for 3-wire mode
+#[derive(Debug)]
+pub struct Spi3Interface<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS, S, P> Spi3Interface<SPI, CS>
+where
+    SPI: SpiHalfDuplex<Error = S> + spi::Write<u8, Error = S>,
+    CS: OutputPin<Error = P>,
+{
+    /// Take the bus and switch the device to 3-wire SPI mode.
+    ///
+    /// Drives chip select high, then does a dummy access to 0x7F whose
+    /// rising edge of CSB switches the device from I2C to SPI mode; the
+    /// device ignores that first transfer. It then writes IF_CONF `spi3`,
+    /// after which all transfers use 3-wire mode. The dummy access is
+    /// write-only, so nothing has to come back on the shared data line
+    /// before 3-wire mode is set. The rest of IF_CONF is reset to its
+    /// defaults. Keep [`SpiWireMode::ThreeWire`] in any later
+    /// [`Bmi160::apply_interface_config`], or reads stop working.
+    pub fn new(spi: SPI, mut cs: CS) -> Result<Self, SpiError<S, P>> {
+        cs.set_high().map_err(SpiError::Pin)?;
+
+        let config = InterfaceConfig {
+            spi_mode: SpiWireMode::ThreeWire,
+            ..InterfaceConfig::default()
+        };
+        let mut interface = Spi3Interface { spi, cs };
+        interface.write(&[SPI_DUMMY_REGISTER])?;
+        interface.write(&[Register::IF_CONF.addr(), config.bits()])?;
+        Ok(interface)
+    }
+}
+
+impl<SPI, CS, S, P> Interface for Spi3Interface<SPI, CS>
+where
+    SPI: SpiHalfDuplex<Error = S> + spi::Write<u8, Error = S>,
+    CS: OutputPin<Error = P>,
+{
+    type Error = SpiError<S, P>;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        select(&mut self.spi, &mut self.cs, |spi| spi.write(bytes))
+    }
+
+    fn write_registers(&mut self, register: u8, data: &[u8]) -> Result<(), Self::Error> {
+        select(&mut self.spi, &mut self.cs, |spi| {
+            spi.write(&[register])?;
+            spi.write(data)
+        })
+    }
+
+    /// Send the register address, then turn the line around and read
+    /// `buffer.len()` bytes
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        select(&mut self.spi, &mut self.cs, |spi| {
+            spi.write(&[register | SPI_READ])?;
+            SpiHalfDuplex::read(spi, buffer)
+        })
+    }
+}
+
+impl<SPI, CS, S, P> Bmi160<Spi3Interface<SPI, CS>>
+where
+    SPI: SpiHalfDuplex<Error = S> + spi::Write<u8, Error = S>,
+    CS: OutputPin<Error = P>,
+{
+    /// Create a new BMI160 driver on a 3-wire SPI bus, see
+    /// [`Spi3Interface::new`]
+    pub fn new_spi3(spi: SPI, cs: CS) -> Result<Self, Error<SpiError<S, P>>> {
+        Self::from_interface(Spi3Interface::new(spi, cs).map_err(Error::Bus)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Event {
+        CsLow,
+        CsHigh,
+        Write(Vec<u8>),
+    }
+
+    type Log = Rc<RefCell<Vec<Event>>>;
+
+    struct Bus(Log);
+
+    impl spi::Write<u8> for Bus {
+        type Error = ();
+
+        fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+            self.0.borrow_mut().push(Event::Write(words.to_vec()));
+            Ok(())
+        }
+    }
+
+    impl SpiHalfDuplex for Bus {
+        type Error = ();
+
+        fn read(&mut self, _buffer: &mut [u8]) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    struct Cs(Log);
+
+    impl OutputPin for Cs {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), ()> {
+            self.0.borrow_mut().push(Event::CsLow);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), ()> {
+            self.0.borrow_mut().push(Event::CsHigh);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn spi3_switches_to_spi_before_writing_if_conf() {
+        let log = Log::default();
+        Spi3Interface::new(Bus(log.clone()), Cs(log.clone())).unwrap();
+
+        let spi3 = InterfaceConfig {
+            spi_mode: SpiWireMode::ThreeWire,
+            ..InterfaceConfig::default()
+        };
+        assert_eq!(
+            *log.borrow(),
+            [
+                Event::CsHigh,
+                Event::CsLow,
+                Event::Write(std::vec![SPI_DUMMY_REGISTER]),
+                Event::CsHigh,
+                Event::CsLow,
+                Event::Write(std::vec![Register::IF_CONF.addr(), spi3.bits()]),
+                Event::CsHigh,
+            ]
+        );
+    }
+}
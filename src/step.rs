@@ -0,0 +1,419 @@
+//! Step detector and step counter
+
+use crate::config::{int1_config, AccelConfig, Preset};
+use crate::interface::Interface;
+use crate::interrupt::{
+    Axes, IntPinConfig, InterruptPin, InterruptRoute, InterruptSource, InterruptStatus,
+};
+use crate::motion::NoMotionConfig;
+use crate::register::{
+    AccelBandwidth, AccelOdr, AccelPowerMode, Cmd, InterruptEnable, InterruptFlags, Register,
+};
+use crate::{Bmi160, Error, SensorTime};
+use hal::blocking::delay::DelayMs;
+
+/// No-motion delay used by [`Bmi160::enable_low_power_pedometer`], in
+/// seconds
+const PEDOMETER_NO_MOTION_SECS: f32 = 5.0;
+
+/// How often [`Bmi160::read_step_count_with_time`] retries when a step
+/// lands between its reads
+pub const STEP_TIME_READ_ATTEMPTS: usize = 3;
+
+/// STEP_CONF[0] `min_threshold`, 2 bits starting at bit 3
+const STEP_CONF_MIN_THRESHOLD_SHIFT: u8 = 3;
+
+/// STEP_CONF[0] `alpha`, 3 bits starting at bit 5
+const STEP_CONF_ALPHA_SHIFT: u8 = 5;
+
+/// STEP_CONF[1] `min_step_buf`
+const STEP_CONF_MIN_STEP_BUF_MASK: u8 = 0b0000_0111;
+
+/// STEP_CONF[1] `step_cnt_en`
+const STEP_CONF_STEP_CNT_EN: u8 = 0b0000_1000;
+
+/// Step detection mode, selecting one of the STEP_CONF settings
+/// recommended by the datasheet
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum StepMode {
+    /// Balanced between false positives and missed steps (default)
+    #[default]
+    Normal,
+
+    /// Detects light steps, at the cost of more false positives
+    Sensitive,
+
+    /// Fewer false positives, at the cost of missing light steps
+    Robust,
+}
+
+impl StepMode {
+    /// Step detector parameters for this mode
+    pub fn config(self) -> StepConfig {
+        let bytes = match self {
+            StepMode::Normal => [0x15, 0x03],
+            StepMode::Sensitive => [0x2D, 0x00],
+            StepMode::Robust => [0x1D, 0x07],
+        };
+        StepConfig::from_bytes(bytes)
+    }
+}
+
+/// Step detector parameters, covering STEP_CONF (0x7A-0x7B) apart from
+/// the step counter enable.
+///
+/// Start from a [`StepMode`] preset and adjust individual fields to tune
+/// detection for unusual wear locations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StepConfig {
+    /// Minimum time between two steps (`steptime_min`, 0-7)
+    pub steptime_min: u8,
+
+    /// Minimum acceleration threshold for a step (`min_threshold`, 0-3)
+    pub min_threshold: u8,
+
+    /// Smoothing of the acceleration signal (`alpha`, 0-7)
+    pub alpha: u8,
+
+    /// Consecutive steps required before counting starts (`min_step_buf`,
+    /// 0-7)
+    pub min_step_buf: u8,
+}
+
+impl Default for StepConfig {
+    fn default() -> Self {
+        StepMode::Normal.config()
+    }
+}
+
+impl From<StepMode> for StepConfig {
+    fn from(mode: StepMode) -> Self {
+        mode.config()
+    }
+}
+
+impl StepConfig {
+    /// STEP_CONF[0..1] values, without `step_cnt_en`
+    pub(crate) fn bytes(&self) -> [u8; 2] {
+        [
+            (self.steptime_min & 0b111)
+                | (self.min_threshold & 0b11) << STEP_CONF_MIN_THRESHOLD_SHIFT
+                | (self.alpha & 0b111) << STEP_CONF_ALPHA_SHIFT,
+            self.min_step_buf & STEP_CONF_MIN_STEP_BUF_MASK,
+        ]
+    }
+
+    /// Decode STEP_CONF[0..1] values
+    pub(crate) fn from_bytes(bytes: [u8; 2]) -> Self {
+        StepConfig {
+            steptime_min: bytes[0] & 0b111,
+            min_threshold: (bytes[0] >> STEP_CONF_MIN_THRESHOLD_SHIFT) & 0b11,
+            alpha: bytes[0] >> STEP_CONF_ALPHA_SHIFT,
+            min_step_buf: bytes[1] & STEP_CONF_MIN_STEP_BUF_MASK,
+        }
+    }
+}
+
+impl InterruptStatus {
+    /// Whether the step detector interrupt fired
+    pub fn step_detected(&self) -> bool {
+        self.fired.contains(InterruptFlags::STEP)
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Configure the step detector for `mode` and enable the step counter.
+    ///
+    /// The accelerometer must be running (normal or low power mode) for
+    /// steps to be counted.
+    pub fn enable_step_counter(&mut self, mode: StepMode) -> Result<(), Error<E>> {
+        let [conf0, conf1] = mode.config().bytes();
+        self.write_registers(Register::STEP_CONF, &[conf0, conf1 | STEP_CONF_STEP_CNT_EN])
+    }
+
+    /// Write custom step detector parameters. Whether the step counter is
+    /// enabled is left unchanged.
+    pub fn configure_step_detector(&mut self, config: StepConfig) -> Result<(), Error<E>> {
+        let enabled = self.read_register(Register::STEP_CONF_1)? & STEP_CONF_STEP_CNT_EN;
+        let [conf0, conf1] = config.bytes();
+        self.write_registers(Register::STEP_CONF, &[conf0, conf1 | enabled])
+    }
+
+    /// Read the step detector parameters.
+    pub fn get_step_config(&mut self) -> Result<StepConfig, Error<E>> {
+        let mut bytes = [0u8; 2];
+        self.write_read_register(Register::STEP_CONF, &mut bytes)?;
+        Ok(StepConfig::from_bytes(bytes))
+    }
+
+    /// Whether the step counter is enabled (`step_cnt_en`).
+    pub fn step_counter_enabled(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.read_register(Register::STEP_CONF_1)? & STEP_CONF_STEP_CNT_EN != 0)
+    }
+
+    /// Read the 16-bit step count (STEP_CNT) in a single burst.
+    ///
+    /// The counter wraps around after 65535 steps.
+    pub fn read_step_count(&mut self) -> Result<u16, Error<E>> {
+        let mut buffer = [0u8; 2];
+        self.write_read_register(Register::STEP_CNT, &mut buffer)?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    /// Read the step count together with the SENSORTIME it belongs to, for
+    /// computing cadence on the host.
+    ///
+    /// STEP_CNT and SENSORTIME are not adjacent, so the count is read on
+    /// both sides of the sensortime read and the pair is only returned once
+    /// both counts agree, i.e. no step was counted in between. Steps are at
+    /// most a few Hz, so this normally succeeds on the first try; gives up
+    /// with [`Error::Timeout`] after [`STEP_TIME_READ_ATTEMPTS`] attempts.
+    pub fn read_step_count_with_time(&mut self) -> Result<(u16, SensorTime), Error<E>> {
+        let mut before = self.read_step_count()?;
+
+        for _ in 0..STEP_TIME_READ_ATTEMPTS {
+            let time = self.read_sensortime()?;
+            let after = self.read_step_count()?;
+            if after == before {
+                return Ok((after, time));
+            }
+            before = after;
+        }
+
+        Err(Error::Timeout)
+    }
+
+    /// Reset the step count to zero (`step_cnt_clr` command). The step
+    /// counter keeps running.
+    pub fn reset_step_count(&mut self) -> Result<(), Error<E>> {
+        self.send_command(Cmd::STEP_CNT_CLR)
+    }
+
+    /// Put the device into the lowest-current step counting setup.
+    ///
+    /// Applies [`Preset::Pedometer`] (accelerometer only, low power, 25 Hz),
+    /// enables the step counter in [`StepMode::Normal`] and enables
+    /// no-motion detection on all axes with the default threshold and a
+    /// ~5 s delay, so the host can also tell when the wearer stops moving.
+    /// Route the no-motion interrupt with [`Bmi160::map_interrupt`] if
+    /// needed. The step count is not reset.
+    pub fn enable_low_power_pedometer<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        // The step and no-motion registers take burst writes, so write them
+        // in normal mode before the preset drops the accelerometer to low
+        // power
+        self.enter_accel_normal_mode(AccelConfig::default(), delay)?;
+        self.enable_step_counter(StepMode::Normal)?;
+        self.configure_no_motion(NoMotionConfig::no_motion(
+            NoMotionConfig::default().threshold,
+            PEDOMETER_NO_MOTION_SECS,
+            Axes::ALL,
+        ))?;
+
+        // Keep no-motion enabled when the preset writes INT_EN
+        let config = Preset::Pedometer.config().interrupts(int1_config(
+            InterruptEnable::STEP_DETECTOR | InterruptEnable::NO_MOTION,
+            InterruptSource::StepDetector,
+        ));
+        self.configure(&config, delay)
+    }
+
+    /// Disable the step counter. The step detector configuration is kept.
+    pub fn disable_step_counter(&mut self) -> Result<(), Error<E>> {
+        self.modify_register(Register::STEP_CONF_1, STEP_CONF_STEP_CNT_EN, 0)
+    }
+
+    /// Enable the step detector interrupt and route it to `route`.
+    ///
+    /// The step detector shares its pin mapping with low-g, so routing one
+    /// reroutes the other. The accelerometer must be running for steps to
+    /// be detected.
+    pub fn enable_step_detector(&mut self, route: InterruptRoute) -> Result<(), Error<E>> {
+        self.map_interrupt(InterruptSource::StepDetector, route)?;
+        self.enable_interrupts(InterruptEnable::STEP_DETECTOR)
+    }
+
+    /// Disable the step detector interrupt.
+    pub fn disable_step_detector(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(InterruptEnable::STEP_DETECTOR)
+    }
+}
+
+/// High-level step counting, borrowed from the driver with
+/// [`Bmi160::pedometer`].
+///
+/// ```ignore
+/// let mut pedometer = bmi160.pedometer();
+/// pedometer.start(StepMode::Normal, Some(InterruptPin::Int1), &mut delay)?;
+/// // later, e.g. from the INT1 handler
+/// if pedometer.on_step_event()? {
+///     let steps = pedometer.steps()?;
+/// }
+/// ```
+pub struct Pedometer<'a, DI> {
+    bmi160: &'a mut Bmi160<DI>,
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Borrow the driver as a [`Pedometer`]
+    pub fn pedometer(&mut self) -> Pedometer<'_, DI> {
+        Pedometer { bmi160: self }
+    }
+}
+
+impl<'a, DI, E> Pedometer<'a, DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Start counting steps.
+    ///
+    /// Runs the accelerometer at 50 Hz in low power mode (keeping the
+    /// current range), configures the step detector for `mode` and enables
+    /// the step counter. With `step_events`, the step detector interrupt is
+    /// also routed to that pin, configured as an enabled push-pull,
+    /// active-high output. The step count is not reset.
+    ///
+    /// The step and interrupt registers are written in normal mode with a
+    /// normal-mode filter; the undersampling ACC_CONF follows in suspend,
+    /// right before entering low power mode.
+    pub fn start<D>(
+        &mut self,
+        mode: StepMode,
+        step_events: Option<InterruptPin>,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u8>,
+    {
+        let accel = AccelConfig {
+            odr: AccelOdr::Hz50,
+            bandwidth: AccelBandwidth::Avg2,
+            range: self.bmi160.accel_range(),
+        };
+        accel.validate_for(AccelPowerMode::LowPower)?;
+
+        let normal_accel = AccelConfig {
+            range: accel.range,
+            ..AccelConfig::default()
+        };
+        self.bmi160.enter_accel_normal_mode(normal_accel, delay)?;
+        self.bmi160.enable_step_counter(mode)?;
+
+        if let Some(pin) = step_events {
+            self.bmi160
+                .set_int_pin_config(pin, IntPinConfig::ACTIVE_HIGH_OUTPUT)?;
+            self.bmi160.enable_step_detector(pin.into())?;
+        }
+
+        self.bmi160
+            .switch_accel_mode(AccelPowerMode::LowPower, accel, delay)
+    }
+
+    /// Stop counting steps and disable the step detector interrupt. The
+    /// accelerometer is left running.
+    pub fn stop(&mut self) -> Result<(), Error<E>> {
+        self.bmi160.disable_step_detector()?;
+        self.bmi160.disable_step_counter()
+    }
+
+    /// Steps counted so far
+    pub fn steps(&mut self) -> Result<u16, Error<E>> {
+        self.bmi160.read_step_count()
+    }
+
+    /// Reset the step count to zero
+    pub fn reset(&mut self) -> Result<(), Error<E>> {
+        self.bmi160.reset_step_count()
+    }
+
+    /// Whether the step detector interrupt fired. Call from the handler of
+    /// the pin passed to [`Pedometer::start`].
+    pub fn on_step_event(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.bmi160.get_interrupt_status()?.step_detected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Mock, NoDelay};
+
+    #[test]
+    fn low_power_pedometer() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.enable_low_power_pedometer(&mut NoDelay).unwrap();
+
+        let mock = &imu.iface;
+        assert!(!mock.invalid_acc_conf);
+        assert_eq!(mock.acc_pmu(), 0b10);
+        assert_ne!(mock.reg(Register::STEP_CONF_1) & STEP_CONF_STEP_CNT_EN, 0);
+
+        let enabled = imu.get_enabled_interrupts().unwrap();
+        assert!(enabled.contains(InterruptEnable::STEP_DETECTOR | InterruptEnable::NO_MOTION));
+        assert_eq!(imu.get_step_config().unwrap(), StepMode::Normal.config());
+
+        let accel = imu.get_accel_config().unwrap();
+        assert_eq!(accel.odr, AccelOdr::Hz25);
+        assert_eq!(accel.bandwidth, AccelBandwidth::Avg1);
+    }
+
+    #[test]
+    fn pedometer_start() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.pedometer()
+            .start(StepMode::Robust, Some(InterruptPin::Int2), &mut NoDelay)
+            .unwrap();
+
+        let mock = &imu.iface;
+        assert!(!mock.invalid_acc_conf);
+        assert_eq!(mock.acc_pmu(), 0b10);
+        assert_ne!(mock.reg(Register::STEP_CONF_1) & STEP_CONF_STEP_CNT_EN, 0);
+        assert_eq!(imu.get_step_config().unwrap(), StepMode::Robust.config());
+
+        let accel = imu.get_accel_config().unwrap();
+        assert_eq!(accel.odr, AccelOdr::Hz50);
+        assert_eq!(accel.bandwidth, AccelBandwidth::Avg2);
+        assert_eq!(accel.range, AccelConfig::default().range);
+
+        assert!(imu
+            .get_enabled_interrupts()
+            .unwrap()
+            .contains(InterruptEnable::STEP_DETECTOR));
+        assert_eq!(
+            imu.get_interrupt_map()
+                .unwrap()
+                .route(InterruptSource::StepDetector),
+            InterruptRoute::Int2
+        );
+
+        imu.pedometer().stop().unwrap();
+        assert_eq!(
+            imu.iface.reg(Register::STEP_CONF_1) & STEP_CONF_STEP_CNT_EN,
+            0
+        );
+        assert!(!imu
+            .get_enabled_interrupts()
+            .unwrap()
+            .contains(InterruptEnable::STEP_DETECTOR));
+    }
+
+    #[test]
+    fn pedometer_start_without_events() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.pedometer()
+            .start(StepMode::Normal, None, &mut NoDelay)
+            .unwrap();
+
+        assert!(!imu.iface.invalid_acc_conf);
+        assert_eq!(imu.iface.acc_pmu(), 0b10);
+        assert!(imu.get_enabled_interrupts().unwrap().is_empty());
+    }
+}
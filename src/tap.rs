@@ -0,0 +1,343 @@
+//! Tap detection (INT_TAP)
+
+use crate::interface::Interface;
+use crate::interrupt::{
+    mg_to_steps, Axis, Direction, InterruptRoute, InterruptSource, InterruptStatus,
+};
+use crate::register::{
+    AccelRange, InterruptEnable, InterruptFlags, Register, TapDuration, TapQuiet, TapShock,
+};
+use crate::{Bmi160, Error};
+
+/// INT_TAP[0] `int_tap_shock`
+const INT_TAP_SHOCK: u8 = 0b0100_0000;
+
+/// INT_TAP[0] `int_tap_quiet`
+const INT_TAP_QUIET: u8 = 0b1000_0000;
+
+/// INT_TAP[1] `int_tap_th`
+const INT_TAP_TH_MASK: u8 = 0b0001_1111;
+
+/// Tap threshold step at the ±2 g range, in mg
+const TAP_TH_MG_2G: f32 = 62.5;
+
+/// Tap threshold step in mg for the given range
+fn tap_threshold_step_mg(range: AccelRange) -> f32 {
+    TAP_TH_MG_2G * range.g() / 2.0
+}
+
+/// Which tap gestures to detect
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TapMode {
+    /// Single taps only
+    Single,
+
+    /// Double taps only
+    Double,
+
+    /// Both single and double taps
+    Both,
+}
+
+impl TapMode {
+    /// INT_EN bits for this mode
+    fn interrupts(self) -> InterruptEnable {
+        match self {
+            TapMode::Single => InterruptEnable::SINGLE_TAP,
+            TapMode::Double => InterruptEnable::DOUBLE_TAP,
+            TapMode::Both => InterruptEnable::SINGLE_TAP | InterruptEnable::DOUBLE_TAP,
+        }
+    }
+}
+
+/// Tap detection configuration, covering INT_TAP[0] (0x63) and INT_TAP[1]
+/// (0x64)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TapConfig {
+    /// Raw tap threshold (`int_tap_th`, 0-31). One step is 62.5 mg at
+    /// ±2 g and scales with the accelerometer range.
+    pub threshold: u8,
+
+    /// Double tap window
+    pub duration: TapDuration,
+
+    /// Shock suppression time
+    pub shock: TapShock,
+
+    /// Quiet time
+    pub quiet: TapQuiet,
+}
+
+impl Default for TapConfig {
+    /// Power-on reset values
+    fn default() -> Self {
+        TapConfig {
+            threshold: 0x0A,
+            duration: TapDuration::default(),
+            shock: TapShock::default(),
+            quiet: TapQuiet::default(),
+        }
+    }
+}
+
+impl TapConfig {
+    /// Threshold in mg for the given accelerometer range
+    pub fn threshold_mg(&self, range: AccelRange) -> f32 {
+        f32::from(self.threshold) * tap_threshold_step_mg(range)
+    }
+
+    /// Set the threshold in mg for the given accelerometer range, rounded
+    /// to the nearest step
+    pub fn with_threshold_mg(mut self, mg: f32, range: AccelRange) -> Self {
+        self.threshold = mg_to_steps(mg, tap_threshold_step_mg(range), INT_TAP_TH_MASK);
+        self
+    }
+
+    /// INT_TAP[0..1] register values
+    pub(crate) fn bytes(&self) -> [u8; 2] {
+        let mut tap0 = self.duration as u8;
+        if self.shock == TapShock::Ms75 {
+            tap0 |= INT_TAP_SHOCK;
+        }
+        if self.quiet == TapQuiet::Ms20 {
+            tap0 |= INT_TAP_QUIET;
+        }
+
+        [tap0, self.threshold & INT_TAP_TH_MASK]
+    }
+
+    /// Decode INT_TAP[0..1] register values
+    pub(crate) fn from_bytes(bytes: [u8; 2]) -> Self {
+        TapConfig {
+            threshold: bytes[1] & INT_TAP_TH_MASK,
+            duration: TapDuration::from_bits(bytes[0]),
+            shock: if bytes[0] & INT_TAP_SHOCK != 0 {
+                TapShock::Ms75
+            } else {
+                TapShock::Ms50
+            },
+            quiet: if bytes[0] & INT_TAP_QUIET != 0 {
+                TapQuiet::Ms20
+            } else {
+                TapQuiet::Ms30
+            },
+        }
+    }
+}
+
+/// A detected tap
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TapEvent {
+    /// Axis the tap was detected on, `None` if the engine didn't flag one
+    pub axis: Option<Axis>,
+
+    /// Direction of the tap along `axis`
+    pub direction: Direction,
+
+    /// Double tap rather than single tap
+    pub double: bool,
+}
+
+impl TapEvent {
+    /// Decode the tap event in `status`, if a tap interrupt fired. A double
+    /// tap takes precedence when both tap interrupts are flagged.
+    pub fn from_status(status: &InterruptStatus) -> Option<Self> {
+        let double = if status.fired.contains(InterruptFlags::DOUBLE_TAP) {
+            true
+        } else if status.fired.contains(InterruptFlags::SINGLE_TAP) {
+            false
+        } else {
+            return None;
+        };
+
+        Some(TapEvent {
+            axis: status.tap.axis(),
+            direction: status.tap.direction(),
+            double,
+        })
+    }
+}
+
+impl<DI, E> Bmi160<DI>
+where
+    DI: Interface<Error = E>,
+{
+    /// Write the tap detection configuration (INT_TAP).
+    ///
+    /// Both INT_TAP registers are written with a single burst, so the tap
+    /// engine never sees a half-updated configuration.
+    pub fn configure_tap(&mut self, config: TapConfig) -> Result<(), Error<E>> {
+        self.write_registers(Register::INT_TAP, &config.bytes())?;
+        self.mg_thresholds.tap = None;
+        Ok(())
+    }
+
+    /// Set the tap threshold in mg.
+    ///
+    /// Converted using the driver's cached accelerometer range and
+    /// rewritten whenever [`Bmi160::apply_accel_config`] changes the range,
+    /// so the sensitivity stays the same. Writing a raw threshold with
+    /// [`Bmi160::configure_tap`] stops the rescaling.
+    pub fn set_tap_threshold_mg(&mut self, mg: f32) -> Result<(), Error<E>> {
+        let steps = mg_to_steps(mg, tap_threshold_step_mg(self.accel_range), INT_TAP_TH_MASK);
+        self.write_register(Register::INT_TAP_1, steps)?;
+        self.mg_thresholds.tap = Some(mg);
+        Ok(())
+    }
+
+    /// Read the tap detection configuration (INT_TAP).
+    pub fn get_tap_config(&mut self) -> Result<TapConfig, Error<E>> {
+        let mut bytes = [0u8; 2];
+        self.write_read_register(Register::INT_TAP, &mut bytes)?;
+        Ok(TapConfig::from_bytes(bytes))
+    }
+
+    /// Enable tap detection for `mode` and route the enabled tap
+    /// interrupts to `route`.
+    ///
+    /// Tap gestures not selected by `mode` are disabled and unmapped. Pin
+    /// electrical settings are left untouched, see
+    /// [`Bmi160::set_int_pin_config`].
+    pub fn enable_tap(&mut self, mode: TapMode, route: InterruptRoute) -> Result<(), Error<E>> {
+        let all = TapMode::Both.interrupts();
+        let enabled = mode.interrupts();
+
+        let single = if enabled.contains(InterruptEnable::SINGLE_TAP) {
+            route
+        } else {
+            InterruptRoute::None
+        };
+        let double = if enabled.contains(InterruptEnable::DOUBLE_TAP) {
+            route
+        } else {
+            InterruptRoute::None
+        };
+
+        let map = self
+            .get_interrupt_map()?
+            .map(InterruptSource::SingleTap, single)
+            .map(InterruptSource::DoubleTap, double);
+        self.set_interrupt_map(map)?;
+
+        let current = self.get_enabled_interrupts()?;
+        self.set_enabled_interrupts((current - all) | enabled)
+    }
+
+    /// Read INT_STATUS and decode the tap event, if a tap interrupt fired.
+    pub fn tap_event(&mut self) -> Result<Option<TapEvent>, Error<E>> {
+        let status = self.get_interrupt_status()?;
+        Ok(TapEvent::from_status(&status))
+    }
+
+    /// Disable single and double tap detection.
+    pub fn disable_tap(&mut self) -> Result<(), Error<E>> {
+        self.disable_interrupts(TapMode::Both.interrupts())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::Mock;
+
+    #[test]
+    fn power_on_bytes() {
+        assert_eq!(TapConfig::from_bytes([0x04, 0x0A]), TapConfig::default());
+        assert_eq!(TapConfig::default().bytes(), [0x04, 0x0A]);
+    }
+
+    #[test]
+    fn shock_and_quiet_bits() {
+        // Bit 6 set is the longer shock time, bit 7 set the shorter quiet
+        // time
+        let config = TapConfig {
+            threshold: 0x1F,
+            duration: TapDuration::Ms700,
+            shock: TapShock::Ms75,
+            quiet: TapQuiet::Ms20,
+        };
+        assert_eq!(config.bytes(), [0b1100_0111, 0x1F]);
+        assert_eq!(TapConfig::from_bytes(config.bytes()), config);
+
+        let config = TapConfig {
+            shock: TapShock::Ms50,
+            quiet: TapQuiet::Ms30,
+            ..config
+        };
+        assert_eq!(config.bytes(), [0b0000_0111, 0x1F]);
+        assert_eq!(TapConfig::from_bytes(config.bytes()), config);
+    }
+
+    #[test]
+    fn reserved_bits_ignored() {
+        let config = TapConfig::from_bytes([0b0011_1001, 0xEA]);
+        assert_eq!(config.duration, TapDuration::Ms100);
+        assert_eq!(config.threshold, 0x0A);
+        assert_eq!(config.bytes(), [0b0000_0001, 0x0A]);
+    }
+
+    #[test]
+    fn single_tap_mode_disables_double_tap() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.enable_interrupts(InterruptEnable::DATA_READY).unwrap();
+        imu.enable_tap(TapMode::Both, InterruptRoute::Int1).unwrap();
+
+        imu.enable_tap(TapMode::Single, InterruptRoute::Int2)
+            .unwrap();
+
+        let enabled = imu.get_enabled_interrupts().unwrap();
+        assert!(enabled.contains(InterruptEnable::SINGLE_TAP | InterruptEnable::DATA_READY));
+        assert!(!enabled.contains(InterruptEnable::DOUBLE_TAP));
+
+        let map = imu.get_interrupt_map().unwrap();
+        assert_eq!(map.route(InterruptSource::SingleTap), InterruptRoute::Int2);
+        assert_eq!(map.route(InterruptSource::DoubleTap), InterruptRoute::None);
+
+        imu.disable_tap().unwrap();
+        let enabled = imu.get_enabled_interrupts().unwrap();
+        assert_eq!(enabled, InterruptEnable::DATA_READY);
+    }
+
+    #[test]
+    fn double_tap_mode_disables_single_tap() {
+        let mut imu = Bmi160::from_interface(Mock::new()).unwrap();
+        imu.enable_tap(TapMode::Both, InterruptRoute::Both).unwrap();
+
+        imu.enable_tap(TapMode::Double, InterruptRoute::Int1)
+            .unwrap();
+
+        let enabled = imu.get_enabled_interrupts().unwrap();
+        assert_eq!(enabled, InterruptEnable::DOUBLE_TAP);
+        let map = imu.get_interrupt_map().unwrap();
+        assert_eq!(map.route(InterruptSource::SingleTap), InterruptRoute::None);
+        assert_eq!(map.route(InterruptSource::DoubleTap), InterruptRoute::Int1);
+    }
+
+    #[test]
+    fn tap_event_prefers_double_tap() {
+        // INT_STATUS_0 `d_tap_int` and `s_tap_int`; INT_STATUS_2
+        // `tap_first_x` and `tap_sign`
+        let status = InterruptStatus::from_bytes(0, [0b0011_0000, 0, 0b1001_0000, 0]);
+        assert_eq!(
+            TapEvent::from_status(&status),
+            Some(TapEvent {
+                axis: Some(Axis::X),
+                direction: Direction::Negative,
+                double: true,
+            })
+        );
+
+        let status = InterruptStatus::from_bytes(0, [0b0010_0000, 0, 0b0100_0000, 0]);
+        assert_eq!(
+            TapEvent::from_status(&status),
+            Some(TapEvent {
+                axis: Some(Axis::Z),
+                direction: Direction::Positive,
+                double: false,
+            })
+        );
+
+        let status = InterruptStatus::from_bytes(0, [0, 0, 0b0100_0000, 0]);
+        assert_eq!(TapEvent::from_status(&status), None);
+    }
+}